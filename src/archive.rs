@@ -0,0 +1,378 @@
+/*!
+ * Reading of zip and tar archives
+ *
+ * With `--expand-archives`, archives are scanned as virtual directories whose
+ * entries are processed like files on disk. Only regular files are read;
+ * directories are implied by the paths of their files, and links, devices
+ * and entries whose paths would escape the archive are skipped.
+ *
+ * Entries are shown below the archive at paths such as
+ * `bundle.zip!/src/main.rs`. To keep archive bombs from exhausting memory,
+ * only the first `MAX_ENTRIES` files and `MAX_TOTAL_SIZE` uncompressed bytes
+ * are read; later files are listed without their content.
+ */
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+/// Marker appended to the path of an archive to form the path of its contents
+pub const SEPARATOR: &str = "!";
+
+/// Patterns matching the names of supported archives
+pub const PATTERNS: &[&str] = &["*.zip", "*.tar", "*.tar.gz", "*.tgz"];
+
+/// Number of files read from one archive; later files are listed without content
+pub const MAX_ENTRIES: usize = 10_000;
+
+/// Uncompressed bytes read from one archive; later files are listed without content
+pub const MAX_TOTAL_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Supported kinds of archives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Zip archive (`.zip`)
+    Zip,
+    /// Uncompressed tar archive (`.tar`)
+    Tar,
+    /// Gzip-compressed tar archive (`.tar.gz`, `.tgz`)
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Kind of archive a file is, from its name
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// A regular file inside an archive
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path of the file inside the archive
+    pub path: PathBuf,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Last modification time, if the archive records one
+    pub modified: Option<SystemTime>,
+    /// Unix permission bits, if the archive records them
+    pub mode: Option<u32>,
+    /// Content of the file (None if it is too large to read)
+    pub data: Option<Vec<u8>>,
+}
+
+/// Limits on how much of an archive is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Size from which a single file is listed without its content
+    pub max_size: u64,
+    /// Number of files read with their content
+    pub max_entries: usize,
+    /// Uncompressed bytes read over all files
+    pub max_total_size: u64,
+}
+
+impl Limits {
+    /// Limits for files of up to `max_size` bytes, with the default archive-wide caps
+    pub fn new(max_size: u64) -> Self {
+        Self {
+            max_size,
+            max_entries: MAX_ENTRIES,
+            max_total_size: MAX_TOTAL_SIZE,
+        }
+    }
+}
+
+/// What has been read from an archive so far, against its limits
+struct Reader {
+    limits: Limits,
+    entries: usize,
+    total_size: u64,
+}
+
+impl Reader {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            entries: 0,
+            total_size: 0,
+        }
+    }
+
+    /// Content of an entry, or None if it is too large or the archive's limits are reached
+    ///
+    /// At most `max_size` bytes are read, whatever size the archive claims.
+    fn read<R: Read>(&mut self, reader: R, size: u64) -> io::Result<Option<Vec<u8>>> {
+        let remaining = self.limits.max_total_size - self.total_size;
+        if size >= self.limits.max_size
+            || size > remaining
+            || self.entries >= self.limits.max_entries
+        {
+            return Ok(None);
+        }
+
+        let mut data = Vec::with_capacity(size as usize);
+        reader
+            .take(self.limits.max_size.min(remaining))
+            .read_to_end(&mut data)?;
+        self.entries += 1;
+        self.total_size += data.len() as u64;
+        Ok(Some(data))
+    }
+}
+
+/// Path of the contents of an archive, e.g. `bundle.zip!`
+pub fn contents_path(path: &Path) -> PathBuf {
+    let mut contents = path.as_os_str().to_owned();
+    contents.push(SEPARATOR);
+    PathBuf::from(contents)
+}
+
+/// Read the regular files of an archive whose paths `keep` accepts
+///
+/// Files of `limits.max_size` bytes or more, and files past the archive-wide
+/// limits, are listed without their content. Files `keep` rejects are skipped
+/// without being read, so they do not count against the limits.
+pub fn read_entries(
+    path: &Path,
+    kind: ArchiveKind,
+    limits: Limits,
+    keep: impl Fn(&Path) -> bool,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let file = BufReader::new(File::open(path)?);
+    match kind {
+        ArchiveKind::Zip => read_zip(file, limits, keep),
+        ArchiveKind::Tar => read_tar(file, limits, keep),
+        ArchiveKind::TarGz => read_tar(GzDecoder::new(file), limits, keep),
+    }
+}
+
+/// Read the regular files of a zip archive
+fn read_zip<R: Read + io::Seek>(
+    reader: R,
+    limits: Limits,
+    keep: impl Fn(&Path) -> bool,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut archive = ZipArchive::new(reader).map_err(io::Error::other)?;
+    let mut reader = Reader::new(limits);
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(io::Error::other)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(path) = file
+            .enclosed_name()
+            .and_then(|p| sanitize(&p))
+            .filter(|p| keep(p))
+        else {
+            continue;
+        };
+
+        let modified = file
+            .last_modified()
+            .and_then(|time| chrono::NaiveDateTime::try_from(time).ok())
+            .and_then(|time| u64::try_from(time.and_utc().timestamp()).ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let size = file.size();
+        entries.push(ArchiveEntry {
+            path,
+            size,
+            modified,
+            mode: file.unix_mode(),
+            data: reader.read(&mut file, size)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// Read the regular files of a tar archive
+fn read_tar<R: Read>(
+    reader: R,
+    limits: Limits,
+    keep: impl Fn(&Path) -> bool,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut reader = Reader::new(limits);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(path) = sanitize(&entry.path()?).filter(|p| keep(p)) else {
+            continue;
+        };
+
+        let header = entry.header();
+        let modified = header
+            .mtime()
+            .ok()
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let mode = header.mode().ok();
+        let size = entry.size();
+        entries.push(ArchiveEntry {
+            path,
+            size,
+            modified,
+            mode,
+            data: reader.read(&mut entry, size)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// Relative path of an entry, or None if it is empty or would leave the archive
+fn sanitize(path: &Path) -> Option<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!clean.as_os_str().is_empty()).then_some(clean)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn tar_bytes(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o640);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_kind_from_path() {
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("bundle.ZIP")),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("src.tar.gz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("src.tgz")),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::from_path(Path::new("src.tar")),
+            Some(ArchiveKind::Tar)
+        );
+        assert_eq!(ArchiveKind::from_path(Path::new("notes.gz")), None);
+        assert_eq!(
+            contents_path(Path::new("vendor/bundle.zip")),
+            PathBuf::from("vendor/bundle.zip!")
+        );
+    }
+
+    #[test]
+    fn test_read_zip() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().unix_permissions(0o755);
+        writer.add_directory("src/", options).unwrap();
+        writer.start_file("src/main.rs", options).unwrap();
+        writer.write_all(b"fn main() {}\n").unwrap();
+        writer.start_file("../escape.txt", options).unwrap();
+        writer.write_all(b"outside").unwrap();
+        writer.start_file("big.bin", options).unwrap();
+        writer.write_all(&[7; 64]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let entries = read_zip(Cursor::new(bytes), Limits::new(32), |_| true).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("src/main.rs"), PathBuf::from("big.bin")]
+        );
+        assert_eq!(entries[0].data.as_deref(), Some(&b"fn main() {}\n"[..]));
+        assert_eq!(entries[0].mode.map(|m| m & 0o777), Some(0o755));
+        assert_eq!((entries[1].size, entries[1].data.is_none()), (64, true));
+    }
+
+    #[test]
+    fn test_read_tar_gz() {
+        let tar = tar_bytes(&[("./docs/readme.md", b"# Docs\n"), ("notes.txt", b"hi\n")]);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        let entries =
+            read_tar(GzDecoder::new(Cursor::new(gz)), Limits::new(1024), |_| true).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("docs/readme.md"), PathBuf::from("notes.txt")]
+        );
+        assert_eq!(entries[0].data.as_deref(), Some(&b"# Docs\n"[..]));
+        assert_eq!(entries[0].mode, Some(0o640));
+        assert_eq!(
+            entries[0].modified,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+    }
+
+    #[test]
+    fn test_archive_limits() {
+        let files: Vec<(String, Vec<u8>)> = (0..6)
+            .map(|i| (format!("file{}.txt", i), vec![b'x'; 10]))
+            .collect();
+        let files: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(path, data)| (path.as_str(), data.as_slice()))
+            .collect();
+        let tar = tar_bytes(&files);
+        let read = |limits: Limits| {
+            read_tar(Cursor::new(&tar), limits, |p| p != Path::new("file0.txt"))
+                .unwrap()
+                .iter()
+                .map(|e| e.data.is_some())
+                .collect::<Vec<_>>()
+        };
+
+        // Skipped files are not read and do not count against the limits
+        let count = Limits {
+            max_entries: 2,
+            ..Limits::new(1024)
+        };
+        assert_eq!(read(count), vec![true, true, false, false, false]);
+
+        // Files past the total size are listed without content
+        let total = Limits {
+            max_total_size: 35,
+            ..Limits::new(1024)
+        };
+        assert_eq!(read(total), vec![true, true, true, false, false]);
+    }
+}
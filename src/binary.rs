@@ -0,0 +1,372 @@
+/*!
+ * Summaries of binary files
+ *
+ * Binary content is never dumped as is. Instead each binary file is described
+ * by its MIME type (detected from magic bytes), its dimensions if it is a PNG,
+ * JPEG, GIF or WebP image, and the SHA-256 of its content. Files above
+ * `MAX_HASH_SIZE` are not hashed, so large assets only have their start read.
+ * Small files can also carry a hex dump or base64 excerpt of their bytes.
+ */
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+
+use crate::types::BinarySummary;
+
+/// Size up to which binary files get an excerpt (if enabled)
+pub const DEFAULT_EXCERPT_SIZE: u64 = 1024;
+
+/// Size above which binary files are not hashed
+pub const MAX_HASH_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Number of leading bytes kept to detect the type and dimensions
+const HEADER_SIZE: usize = 64 * 1024;
+
+/// Bytes per line of a hex dump
+const HEX_LINE_BYTES: usize = 16;
+
+/// Characters per line of base64 output
+const BASE64_LINE_CHARS: usize = 76;
+
+/// Magic bytes at an offset, and the MIME type they identify
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xFF\xD8\xFF", "image/jpeg"),
+    (0, b"GIF87a", "image/gif"),
+    (0, b"GIF89a", "image/gif"),
+    (0, b"II*\x00", "image/tiff"),
+    (0, b"MM\x00*", "image/tiff"),
+    (0, b"\x00\x00\x01\x00", "image/x-icon"),
+    (0, b"BM", "image/bmp"),
+    (0, b"%PDF-", "application/pdf"),
+    (0, b"PK\x03\x04", "application/zip"),
+    (0, b"PK\x05\x06", "application/zip"),
+    (0, b"\x1F\x8B", "application/gzip"),
+    (0, b"BZh", "application/x-bzip2"),
+    (0, b"\xFD7zXZ\x00", "application/x-xz"),
+    (0, b"\x28\xB5\x2F\xFD", "application/zstd"),
+    (0, b"7z\xBC\xAF\x27\x1C", "application/x-7z-compressed"),
+    (0, b"Rar!\x1A\x07", "application/vnd.rar"),
+    (257, b"ustar", "application/x-tar"),
+    (0, b"\x7FELF", "application/x-elf"),
+    (0, b"\xCF\xFA\xED\xFE", "application/x-mach-binary"),
+    (0, b"\xCE\xFA\xED\xFE", "application/x-mach-binary"),
+    (0, b"\xCA\xFE\xBA\xBE", "application/java-vm"),
+    (0, b"MZ", "application/vnd.microsoft.portable-executable"),
+    (0, b"\x00asm", "application/wasm"),
+    (0, b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    (0, b"wOFF", "font/woff"),
+    (0, b"wOF2", "font/woff2"),
+    (0, b"\x00\x01\x00\x00\x00", "font/ttf"),
+    (0, b"OTTO", "font/otf"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (0, b"\x1A\x45\xDF\xA3", "video/webm"),
+];
+
+/// Encoding of the excerpt of small binary files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BinaryExcerpt {
+    /// Offsets, hex bytes and printable characters, like `hexdump -C`
+    Hex,
+    /// Base64 of the raw bytes
+    Base64,
+}
+
+impl BinaryExcerpt {
+    /// Name used in the output
+    pub fn name(&self) -> &'static str {
+        match self {
+            BinaryExcerpt::Hex => "hex",
+            BinaryExcerpt::Base64 => "base64",
+        }
+    }
+
+    /// Render bytes in this encoding
+    pub fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            BinaryExcerpt::Hex => hex_dump(bytes),
+            BinaryExcerpt::Base64 => {
+                let encoded = STANDARD.encode(bytes);
+                let mut text =
+                    String::with_capacity(encoded.len() + encoded.len() / BASE64_LINE_CHARS + 1);
+                for line in encoded.as_bytes().chunks(BASE64_LINE_CHARS) {
+                    // Base64 output is ASCII
+                    text.push_str(std::str::from_utf8(line).unwrap_or_default());
+                    text.push('\n');
+                }
+                text
+            }
+        }
+    }
+}
+
+/// Summarizer for binary file content
+#[derive(Debug, Clone, Default)]
+pub struct Summarizer {
+    /// Encoding of excerpts (None for no excerpts)
+    excerpt: Option<BinaryExcerpt>,
+    /// Size up to which files get an excerpt
+    excerpt_size: u64,
+}
+
+impl Summarizer {
+    /// Create a summarizer, with excerpts of files up to `excerpt_size` (default: 1 KiB)
+    pub fn new(excerpt: Option<BinaryExcerpt>, excerpt_size: Option<u64>) -> Self {
+        Self {
+            excerpt,
+            excerpt_size: excerpt_size.unwrap_or(DEFAULT_EXCERPT_SIZE),
+        }
+    }
+
+    /// Summarize a file, hashing its content without holding all of it in memory
+    ///
+    /// Files above `MAX_HASH_SIZE` are not hashed, and only their start is read.
+    pub fn summarize_file(&self, path: &Path) -> io::Result<BinarySummary> {
+        // Enough of the start of the file for detection and for an excerpt
+        let keep = match self.excerpt {
+            Some(_) => HEADER_SIZE.max(self.excerpt_size as usize),
+            None => HEADER_SIZE,
+        };
+
+        let mut file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+        if file_size > MAX_HASH_SIZE {
+            let mut head = Vec::with_capacity(keep);
+            file.take(keep as u64).read_to_end(&mut head)?;
+            return Ok(self.summary(&head, file_size, None));
+        }
+
+        let mut hasher = Sha256::new();
+        let mut head = Vec::new();
+        let mut size = 0u64;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            let take = keep.saturating_sub(head.len()).min(read);
+            head.extend_from_slice(&buffer[..take]);
+            size += read as u64;
+        }
+
+        Ok(self.summary(&head, size, Some(hasher)))
+    }
+
+    /// Summarize content held in memory
+    pub fn summarize(&self, bytes: &[u8]) -> BinarySummary {
+        let size = bytes.len() as u64;
+        let hasher = (size <= MAX_HASH_SIZE).then(|| {
+            let mut hasher = Sha256::new();
+            hasher.update(bytes);
+            hasher
+        });
+        self.summary(bytes, size, hasher)
+    }
+
+    /// Build the summary from the start of the content and the hash of all of it
+    fn summary(&self, head: &[u8], size: u64, hasher: Option<Sha256>) -> BinarySummary {
+        let (width, height) = dimensions(head).unzip();
+        let excerpt = match self.excerpt {
+            Some(format) if size <= self.excerpt_size => Some(format.encode(head)),
+            _ => None,
+        };
+
+        BinarySummary {
+            mime: mime_type(head).map(str::to_string),
+            width,
+            height,
+            sha256: hasher.map(|hasher| format!("{:x}", hasher.finalize())),
+            excerpt,
+        }
+    }
+}
+
+/// MIME type identified by the magic bytes at the start of the content
+pub fn mime_type(bytes: &[u8]) -> Option<&'static str> {
+    // Containers whose type depends on a second tag
+    if bytes.starts_with(b"RIFF") && bytes.len() >= 12 {
+        return match &bytes[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"WAVE" => Some("audio/wav"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        };
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return match &bytes[8..12] {
+            b"avif" | b"avis" => Some("image/avif"),
+            b"heic" | b"heix" | b"mif1" => Some("image/heic"),
+            b"qt  " => Some("video/quicktime"),
+            _ => Some("video/mp4"),
+        };
+    }
+
+    SIGNATURES
+        .iter()
+        .find(|(offset, magic, _)| bytes.get(*offset..*offset + magic.len()) == Some(*magic))
+        .map(|(_, _, mime)| *mime)
+}
+
+/// Width and height of a PNG, JPEG, GIF or WebP image
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match mime_type(bytes)? {
+        "image/png" if bytes.get(12..16) == Some(b"IHDR") => {
+            Some((be32(bytes, 16)?, be32(bytes, 20)?))
+        }
+        "image/gif" => Some((le16(bytes, 6)?, le16(bytes, 8)?)),
+        "image/jpeg" => jpeg_dimensions(bytes),
+        "image/webp" => webp_dimensions(bytes),
+        _ => None,
+    }
+}
+
+/// Dimensions from the first start-of-frame segment of a JPEG
+fn jpeg_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    while pos + 9 < bytes.len() {
+        if bytes[pos] != 0xFF {
+            return None;
+        }
+        let marker = bytes[pos + 1];
+        match marker {
+            // Fill byte before a marker
+            0xFF => pos += 1,
+            // Markers without a length
+            0x01 | 0xD0..=0xD9 => pos += 2,
+            // Start of frame, except DHT, JPG and DAC which share the range
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be16(bytes, pos + 7)?, be16(bytes, pos + 5)?));
+            }
+            _ => pos += 2 + be16(bytes, pos + 2)? as usize,
+        }
+    }
+    None
+}
+
+/// Dimensions from the first chunk of a lossy, lossless or extended WebP
+fn webp_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    match bytes.get(12..16)? {
+        b"VP8 " => Some((le16(bytes, 26)? & 0x3FFF, le16(bytes, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le24(bytes, 24)? + 1, le24(bytes, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// Lines of offsets, hex bytes and printable characters
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for (i, line) in bytes.chunks(HEX_LINE_BYTES).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let printable: String = line
+            .iter()
+            .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                true => b as char,
+                false => '.',
+            })
+            .collect();
+        text.push_str(&format!(
+            "{:08x}  {:<47}  |{}|\n",
+            i * HEX_LINE_BYTES,
+            hex.join(" "),
+            printable
+        ));
+    }
+    text
+}
+
+fn be16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le24(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal PNG header with the given dimensions
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        bytes.extend(width.to_be_bytes());
+        bytes.extend(height.to_be_bytes());
+        bytes.extend([8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type(&png(1, 1)), Some("image/png"));
+        assert_eq!(mime_type(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(mime_type(b"PK\x03\x04rest"), Some("application/zip"));
+        assert_eq!(mime_type(b"RIFF\0\0\0\0WAVEfmt "), Some("audio/wav"));
+        assert_eq!(mime_type(b"\0\0\0\x20ftypisom"), Some("video/mp4"));
+        assert_eq!(mime_type(&[0, 1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        assert_eq!(dimensions(&png(640, 480)), Some((640, 480)));
+        assert_eq!(
+            dimensions(b"GIF89a\x20\x00\x10\x00\x00\x00\x00"),
+            Some((32, 16))
+        );
+
+        // SOI, an APP0 segment to skip, then a baseline start of frame
+        let jpeg = b"\xFF\xD8\xFF\xE0\x00\x04\x00\x00\xFF\xC0\x00\x11\x08\x00\x64\x00\xC8\x03";
+        assert_eq!(dimensions(jpeg), Some((200, 100)));
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+        webp.extend([0x7F, 0x02, 0x00, 0xDF, 0x01, 0x00]);
+        assert_eq!(dimensions(&webp), Some((640, 480)));
+    }
+
+    #[test]
+    fn test_summary_and_excerpts() {
+        let summarizer = Summarizer::new(Some(BinaryExcerpt::Hex), Some(64));
+        let summary = summarizer.summarize(b"\x00\x01hello");
+        assert_eq!(summary.mime, None);
+        assert_eq!(summary.sha256.as_ref().map(String::len), Some(64));
+        assert_eq!(
+            summarizer.summarize(b"").sha256.as_deref(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            summary.excerpt.as_deref(),
+            Some("00000000  00 01 68 65 6c 6c 6f                             |..hello|\n")
+        );
+
+        // Files above the excerpt size only get the summary
+        assert_eq!(summarizer.summarize(&[0; 65]).excerpt, None);
+
+        let base64 = Summarizer::new(Some(BinaryExcerpt::Base64), None);
+        assert_eq!(
+            base64.summarize(b"\x00\x01hello").excerpt.as_deref(),
+            Some("AAFoZWxsbw==\n")
+        );
+    }
+}
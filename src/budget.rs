@@ -0,0 +1,446 @@
+/*!
+ * Token-budget-aware packing of scan results
+ *
+ * Ranks text files and keeps whole files until a token budget is reached.
+ * Files that do not fit keep their place in the tree with their content
+ * removed, so writers can still list them in the overview.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::config::Config;
+use crate::error::{DumpFsError, Result};
+use crate::pattern::PatternSet;
+use crate::split::{collect_leaves, empty_like, leaf_cost, render_len};
+use crate::tokenizer::Model;
+use crate::types::{DirectoryNode, FileNode, Node};
+use crate::writer::Writer;
+
+/// Token budget for the generated output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenBudget {
+    /// Fixed number of tokens
+    Tokens(usize),
+    /// Use the context window of the selected model
+    Auto,
+}
+
+impl TokenBudget {
+    /// Resolve the budget to a number of tokens
+    pub fn resolve(&self, model: Option<Model>) -> Result<usize> {
+        match (self, model) {
+            (TokenBudget::Tokens(tokens), _) => Ok(*tokens),
+            (TokenBudget::Auto, Some(model)) if model.context_window() > 0 => {
+                Ok(model.context_window())
+            }
+            (TokenBudget::Auto, Some(model)) => Err(DumpFsError::Config(format!(
+                "Unknown context window for model {}",
+                model
+            ))),
+            (TokenBudget::Auto, None) => Err(DumpFsError::Config(
+                "--token-budget auto requires --model".to_string(),
+            )),
+        }
+    }
+}
+
+impl FromStr for TokenBudget {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(TokenBudget::Auto);
+        }
+
+        s.parse()
+            .map(TokenBudget::Tokens)
+            .map_err(|_| format!("expected a number of tokens or `auto`, got `{}`", s))
+    }
+}
+
+impl fmt::Display for TokenBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenBudget::Tokens(tokens) => write!(f, "{}", tokens),
+            TokenBudget::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenBudget {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Tokens(usize),
+            Name(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Tokens(tokens) => Ok(TokenBudget::Tokens(tokens)),
+            Raw::Name(name) => name.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Order in which files are considered when packing within a budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BudgetOrder {
+    /// Files closest to the root first
+    #[default]
+    Depth,
+    /// Smallest files first, fitting as many files as possible
+    Size,
+    /// Most recently modified files first
+    Recency,
+}
+
+/// A file left out of the output because it did not fit the budget
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OmittedFile {
+    /// Relative path of the file
+    pub path: PathBuf,
+    /// Token count of the file
+    pub tokens: usize,
+}
+
+/// Outcome of packing a scan into a token budget
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BudgetSummary {
+    /// Budget in tokens
+    pub budget: usize,
+    /// Estimated tokens of the output, including the overview
+    pub used: usize,
+    /// Files whose content was omitted
+    pub omitted: Vec<OmittedFile>,
+}
+
+/// Estimated or counted token size of a file
+///
+/// Uses the tokenizer count when available and falls back to the same
+/// four-characters-per-token estimate as the report.
+pub fn file_tokens(file: &FileNode) -> usize {
+    file.tokens.unwrap_or_else(|| {
+        file.content
+            .as_ref()
+            .map(|content| content.chars().count() / 4)
+            .unwrap_or(0)
+    })
+}
+
+/// Share of the budget kept free for the error of the token estimates
+const MARGIN_PERCENT: usize = 5;
+
+/// Keep whole files in ranked order until the budget is exhausted
+///
+/// Each file is charged its rendered cost, including its wrapper, metadata and
+/// diff, as measured with the configured writer. The overview of a tree whose
+/// files are all omitted, and a margin, are reserved before packing. Files
+/// matching earlier priority patterns are considered first, then the remaining
+/// files in the budget order. Priority patterns follow gitignore rules, and a
+/// later negated pattern takes files out of the priority group. A file that
+/// does not fit is skipped so that
+/// smaller files further down the ranking can still be included.
+pub fn pack(config: &Config, root: &mut DirectoryNode, budget: usize) -> Result<BudgetSummary> {
+    let writer = config.format.writer(config.clone(), None);
+    let costs = measure_files(writer.as_ref(), root)?;
+
+    // The overview lists every omitted file, so reserve it as if all were
+    let mut stripped = root.clone();
+    strip_files(&mut stripped);
+    let overhead = render_len(writer.as_ref(), &stripped)? / 4;
+    let available = budget.saturating_sub(budget * MARGIN_PERCENT / 100);
+
+    // Patterns are checked by `Config::validate`; one set per pattern gives its rank
+    let priority = PatternSet::new(&config.priority_patterns).unwrap_or_default();
+    let ranks: Vec<PatternSet> = config
+        .priority_patterns
+        .iter()
+        .map(|pattern| PatternSet::new([pattern]).unwrap_or_default())
+        .collect();
+
+    let root_path = root.path.clone();
+    let mut files = Vec::new();
+    collect_files(root, &mut files);
+
+    files.sort_by_cached_key(|file| {
+        let rel_path = file.path.strip_prefix(&root_path).unwrap_or(&file.path);
+        let rank = match priority.matches(rel_path, false) {
+            true => ranks.iter().position(|set| set.matches(rel_path, false)),
+            false => None,
+        }
+        .unwrap_or(usize::MAX);
+        let order_key = match config.budget_order {
+            BudgetOrder::Depth => file.path.components().count() as u128,
+            BudgetOrder::Size => file_tokens(file) as u128,
+            BudgetOrder::Recency => file
+                .metadata
+                .modified
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| u128::MAX - since_epoch.as_nanos())
+                .unwrap_or(u128::MAX),
+        };
+        (rank, order_key, file.path.clone())
+    });
+
+    let mut summary = BudgetSummary {
+        budget,
+        used: overhead,
+        ..Default::default()
+    };
+
+    for file in files {
+        let cost = costs
+            .get(&file.path)
+            .copied()
+            .unwrap_or_else(|| file_tokens(file));
+        if summary.used + cost <= available {
+            summary.used += cost;
+        } else {
+            summary.omitted.push(OmittedFile {
+                path: file.path.clone(),
+                tokens: omit(file),
+            });
+        }
+    }
+
+    summary.omitted.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(summary)
+}
+
+/// Measure the rendered token cost of every file with content or a diff
+fn measure_files(writer: &dyn Writer, root: &DirectoryNode) -> io::Result<HashMap<PathBuf, usize>> {
+    let empty = empty_like(root);
+    let base_bytes = render_len(writer, &empty)?;
+
+    let mut leaves = Vec::new();
+    collect_leaves(root, &mut Vec::new(), &mut leaves);
+
+    let mut costs = HashMap::new();
+    for (ancestors, node) in leaves {
+        if let Node::File(file) = &node {
+            if file.content.is_some() || file.diff.is_some() {
+                let (_, tokens) = leaf_cost(writer, &empty, &ancestors, &node, base_bytes)?;
+                costs.insert(file.path.clone(), tokens);
+            }
+        }
+    }
+    Ok(costs)
+}
+
+/// Omit every file, so the tree renders as the largest possible overview
+fn strip_files(dir: &mut DirectoryNode) {
+    for node in &mut dir.contents {
+        match node {
+            Node::Directory(dir_node) => strip_files(dir_node),
+            Node::File(file_node) => {
+                omit(file_node);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Drop the content and diff of a file, keeping its token count for the overview
+fn omit(file: &mut FileNode) -> usize {
+    let tokens = file_tokens(file);
+    file.content = None;
+    file.diff = None;
+    file.tokens = Some(tokens);
+    tokens
+}
+
+/// Collect mutable references to every text file with content or a diff in the tree
+fn collect_files<'a>(dir: &'a mut DirectoryNode, files: &mut Vec<&'a mut FileNode>) {
+    for node in &mut dir.contents {
+        match node {
+            Node::Directory(dir_node) => collect_files(dir_node, files),
+            Node::File(file_node) if file_node.content.is_some() || file_node.diff.is_some() => {
+                files.push(file_node)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::types::Metadata;
+
+    fn file(path: &str, chars: usize, age_secs: u64) -> Node {
+        let path = PathBuf::from(path);
+        Node::File(FileNode {
+            name: path.file_name().unwrap().to_string_lossy().to_string(),
+            path,
+            metadata: Metadata {
+                size: chars as u64,
+                modified: SystemTime::now() - Duration::from_secs(age_secs),
+                permissions: "644".to_string(),
+                encoding: None,
+            },
+            content: Some("x".repeat(chars)),
+            tokens: None,
+            diff: None,
+            line_range: None,
+            truncated: false,
+            cells: None,
+        })
+    }
+
+    fn tree() -> DirectoryNode {
+        let metadata = Metadata {
+            size: 0,
+            modified: SystemTime::now(),
+            permissions: "755".to_string(),
+            encoding: None,
+        };
+        DirectoryNode {
+            name: "root".to_string(),
+            path: PathBuf::from("root"),
+            metadata: metadata.clone(),
+            contents: vec![
+                Node::Directory(DirectoryNode {
+                    name: "src".to_string(),
+                    path: PathBuf::from("root/src"),
+                    metadata,
+                    contents: vec![
+                        file("root/src/lib.rs", 4000, 10),
+                        file("root/src/big.rs", 8000, 5),
+                    ],
+                }),
+                file("root/README.md", 2000, 100),
+            ],
+        }
+    }
+
+    fn omitted(summary: &BudgetSummary) -> Vec<String> {
+        summary
+            .omitted
+            .iter()
+            .map(|f| f.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_token_budget() {
+        assert_eq!("auto".parse::<TokenBudget>(), Ok(TokenBudget::Auto));
+        assert_eq!("1000".parse::<TokenBudget>(), Ok(TokenBudget::Tokens(1000)));
+        assert!("lots".parse::<TokenBudget>().is_err());
+
+        assert_eq!(
+            TokenBudget::Auto.resolve(Some(Model::Sonnet37)).unwrap(),
+            200_000
+        );
+        assert!(TokenBudget::Auto.resolve(None).is_err());
+    }
+
+    fn config(order: BudgetOrder, priority: &[&str]) -> Config {
+        Config {
+            token_budget: Some(TokenBudget::Tokens(0)),
+            budget_order: order,
+            priority_patterns: priority.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Estimated tokens of the rendered output
+    fn rendered_tokens(config: &Config, root: &DirectoryNode) -> usize {
+        let mut output = Vec::new();
+        config
+            .format
+            .render(config.clone(), root, &mut output)
+            .unwrap();
+        output.len() / 4
+    }
+
+    #[test]
+    fn test_pack_by_depth() {
+        let config = config(BudgetOrder::Depth, &[]);
+        let mut root = tree();
+        // README (500) + lib.rs (1000) fit, big.rs (2000) does not
+        let summary = pack(&config, &mut root, 2000).unwrap();
+
+        assert_eq!(omitted(&summary), vec!["root/src/big.rs"]);
+        assert!(summary.used > 1500 && summary.used <= 2000);
+        assert!(rendered_tokens(&config, &root) <= summary.used);
+    }
+
+    #[test]
+    fn test_pack_with_priority_and_recency() {
+        let config = config(BudgetOrder::Recency, &["src/**"]);
+        let mut root = tree();
+        let summary = pack(&config, &mut root, 3000).unwrap();
+
+        // big.rs is the most recent src file, then lib.rs no longer fits but README does
+        assert_eq!(omitted(&summary), vec!["root/src/lib.rs"]);
+        assert!(rendered_tokens(&config, &root) <= summary.used);
+    }
+
+    #[test]
+    fn test_pack_with_gitignore_priority_patterns() {
+        let pack_with = |priority: &[&str]| {
+            let config = config(BudgetOrder::Depth, priority);
+            let mut root = tree();
+            omitted(&pack(&config, &mut root, 3000).unwrap())
+        };
+
+        // Without priority, README and big.rs come first and lib.rs no longer fits
+        assert_eq!(pack_with(&[]), vec!["root/src/lib.rs"]);
+        // A directory pattern with a later negation keeps only lib.rs first
+        assert_eq!(pack_with(&["src/", "!src/big.rs"]), vec!["root/src/big.rs"]);
+        // A bare name matches at any depth, an anchored one only at the root
+        assert_eq!(pack_with(&["lib.rs"]), vec!["root/src/big.rs"]);
+        assert_eq!(pack_with(&["/lib.rs"]), vec!["root/src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_pack_charges_rendered_cost() {
+        let config = config(BudgetOrder::Depth, &[]);
+        let metadata = Metadata {
+            size: 0,
+            modified: SystemTime::now(),
+            permissions: "755".to_string(),
+            encoding: None,
+        };
+        let mut root = DirectoryNode {
+            name: "root".to_string(),
+            path: PathBuf::from("root"),
+            metadata,
+            contents: (0..50)
+                .map(|i| file(&format!("root/file{:02}.rs", i), 80, 0))
+                .collect(),
+        };
+        // The contents alone take 1000 tokens, but their wrappers do not fit as well
+        let budget = 1500;
+        let summary = pack(&config, &mut root, budget).unwrap();
+
+        assert!(!summary.omitted.is_empty());
+        assert!(summary.used <= budget);
+        assert!(rendered_tokens(&config, &root) <= budget);
+    }
+
+    #[test]
+    fn test_pack_counts_diff() {
+        let config = config(BudgetOrder::Depth, &[]);
+        let mut root = tree();
+        if let Node::File(readme) = &mut root.contents[1] {
+            readme.diff = Some("+changed line\n".repeat(500));
+        }
+        // Without its diff README would fit alongside lib.rs
+        let summary = pack(&config, &mut root, 2000).unwrap();
+
+        assert!(omitted(&summary).contains(&"root/README.md".to_string()));
+        assert!(rendered_tokens(&config, &root) <= summary.used);
+    }
+}
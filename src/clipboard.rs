@@ -230,15 +230,11 @@ fn determine_clipboard_providers() -> Vec<ClipboardProvider> {
 
     // Add platform-specific providers
     match get_platform() {
-        "macos" => {
-            if command_exists("pbcopy") {
-                providers.push(ClipboardProvider::MacOS);
-            }
+        "macos" if command_exists("pbcopy") => {
+            providers.push(ClipboardProvider::MacOS);
         }
-        "windows" | "wsl" => {
-            if command_exists("clip.exe") {
-                providers.push(ClipboardProvider::Wsl);
-            }
+        "windows" | "wsl" if command_exists("clip.exe") => {
+            providers.push(ClipboardProvider::Wsl);
         }
         "linux" => {
             // Try Wayland first
@@ -255,10 +251,8 @@ fn determine_clipboard_providers() -> Vec<ClipboardProvider> {
                 providers.push(ClipboardProvider::Xclip);
             }
         }
-        "android" => {
-            if command_exists("termux-clipboard-set") {
-                providers.push(ClipboardProvider::Termux);
-            }
+        "android" if command_exists("termux-clipboard-set") => {
+            providers.push(ClipboardProvider::Termux);
         }
         _ => {}
     }
@@ -0,0 +1,642 @@
+/*!
+ * Configuration file discovery and parsing
+ *
+ * Settings can be stored in a user-level file under the XDG config directory
+ * (`~/.config/dumpfs/dumpfs.toml`) and in a project-level `.dumpfs.toml` in the
+ * target directory. Both may define named profiles under `[profile.<name>]`.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+
+use super::{Args, STDOUT_PATH};
+use crate::binary::BinaryExcerpt;
+use crate::budget::{BudgetOrder, TokenBudget};
+use crate::git::{DiffOutput, DiffSpec, GitCachePolicy};
+use crate::order::SortOrder;
+use crate::report::ReportFormat;
+use crate::split::SplitLimit;
+use crate::tokenizer::Model;
+use crate::truncate::Truncation;
+use crate::utils::parse_file_size;
+use crate::FsWriterFormatter;
+
+/// Name of the project-level configuration file
+pub const PROJECT_CONFIG_FILE: &str = ".dumpfs.toml";
+
+/// Name of the user-level configuration file inside the `dumpfs` config directory
+pub const USER_CONFIG_FILE: &str = "dumpfs.toml";
+
+/// A partial set of configuration values
+///
+/// Every field is optional so that settings from several sources (user file,
+/// project file, profile, command line) can be layered on top of each other.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Output file path
+    pub output_file: Option<PathBuf>,
+    /// Patterns to ignore
+    pub ignore_patterns: Option<Vec<String>>,
+    /// Patterns to include
+    pub include_patterns: Option<Vec<String>>,
+    /// Number of threads to use for processing
+    #[serde(alias = "threads")]
+    pub num_threads: Option<usize>,
+    /// Whether to respect .gitignore files
+    pub respect_gitignore: Option<bool>,
+    /// Path to custom .gitignore file
+    pub gitignore_path: Option<PathBuf>,
+    /// LLM model to use for tokenization
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub model: Option<Model>,
+    /// Policy for handling Git repository caching
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub git_cache_policy: Option<GitCachePolicy>,
+    /// Branch, tag or commit to check out for repository URLs
+    #[serde(alias = "ref")]
+    pub git_ref: Option<String>,
+    /// Include file and directory metadata
+    pub include_metadata: Option<bool>,
+    /// Copy output to clipboard
+    pub clip: Option<bool>,
+    /// Print output to stdout
+    pub stdout: Option<bool>,
+    /// Writer format
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub format: Option<FsWriterFormatter>,
+    /// Token budget for file contents
+    pub token_budget: Option<TokenBudget>,
+    /// Order in which files are kept within the token budget
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub budget_order: Option<BudgetOrder>,
+    /// Patterns for files to keep first within the token budget
+    pub priority_patterns: Option<Vec<String>>,
+    /// Maximum size of each output part
+    pub split: Option<SplitLimit>,
+    /// Changes to dump instead of the whole tree
+    pub diff: Option<DiffSpec>,
+    /// What to include for each changed file in diff mode
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub diff_output: Option<DiffOutput>,
+    /// Whether secrets are redacted from file contents
+    pub redact: Option<bool>,
+    /// Additional regular expressions for secrets to redact
+    pub redact_patterns: Option<Vec<String>>,
+    /// Whether unchanged files are taken from the scan index
+    pub index: Option<bool>,
+    /// Keep running and regenerate the output on changes
+    pub watch: Option<bool>,
+    /// Prefix each line of file content with its line number
+    pub line_numbers: Option<bool>,
+    /// Size above which text files are truncated or left out
+    #[serde(default, deserialize_with = "deserialize_file_size")]
+    pub max_file_size: Option<u64>,
+    /// Size above which files are treated as binary without checking their content
+    #[serde(default, deserialize_with = "deserialize_file_size")]
+    pub max_detect_size: Option<u64>,
+    /// Part of text files above the size limit to keep
+    pub truncate: Option<Truncation>,
+    /// Encoding of 8-bit text files that are not UTF-8
+    pub fallback_encoding: Option<String>,
+    /// Lines kept of each notebook cell output
+    pub notebook_output_lines: Option<usize>,
+    /// Whether binary files are summarized
+    pub binary_summary: Option<bool>,
+    /// Encoding of excerpts of small binary files
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub binary_excerpt: Option<BinaryExcerpt>,
+    /// Size up to which binary files get an excerpt
+    #[serde(default, deserialize_with = "deserialize_file_size")]
+    pub binary_excerpt_size: Option<u64>,
+    /// Whether archives are scanned as virtual directories
+    pub expand_archives: Option<bool>,
+    /// Whether files are written as they are scanned
+    pub stream: Option<bool>,
+    /// Order of the entries within each directory
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub sort: Option<SortOrder>,
+    /// Whether volatile fields are left out or pinned
+    pub reproducible: Option<bool>,
+    /// Levels of subdirectories shown in the report's directory table
+    pub report_depth: Option<usize>,
+    /// File the scan report is also written to
+    pub report: Option<PathBuf>,
+    /// Format of the scan report
+    #[serde(default, deserialize_with = "deserialize_value_enum")]
+    pub report_format: Option<ReportFormat>,
+}
+
+impl Settings {
+    /// Overlay `other` on top of these settings, keeping values `other` leaves unset
+    pub fn merge(&mut self, other: Settings) {
+        macro_rules! overlay {
+            ($($field:ident),* $(,)?) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+
+        overlay!(
+            output_file,
+            ignore_patterns,
+            include_patterns,
+            num_threads,
+            respect_gitignore,
+            gitignore_path,
+            model,
+            git_cache_policy,
+            git_ref,
+            include_metadata,
+            clip,
+            stdout,
+            format,
+            token_budget,
+            budget_order,
+            priority_patterns,
+            split,
+            diff,
+            diff_output,
+            redact,
+            redact_patterns,
+            index,
+            watch,
+            line_numbers,
+            max_file_size,
+            max_detect_size,
+            truncate,
+            fallback_encoding,
+            notebook_output_lines,
+            binary_summary,
+            binary_excerpt,
+            binary_excerpt_size,
+            expand_archives,
+            stream,
+            sort,
+            reproducible,
+            report_depth,
+            report,
+            report_format,
+        );
+    }
+
+    /// Resolve relative paths against the directory containing the config file
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            &mut self.output_file,
+            &mut self.gitignore_path,
+            &mut self.report,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() && *path != Path::new(STDOUT_PATH) {
+                *path = base.join(&*path);
+            }
+        }
+    }
+
+    /// Drop settings an untrusted file may not use, returning the rejected path keys
+    ///
+    /// Paths must already be resolved against `base`, the directory they have to stay in.
+    fn restrict(&mut self, base: &Path) -> Vec<&'static str> {
+        self.clip = None;
+        self.stdout = None;
+
+        let mut rejected = Vec::new();
+        for (key, path) in [
+            ("output_file", &mut self.output_file),
+            ("gitignore_path", &mut self.gitignore_path),
+            ("report", &mut self.report),
+        ] {
+            let escapes = path
+                .as_ref()
+                .is_some_and(|path| match path.strip_prefix(base) {
+                    Ok(relative) => relative
+                        .components()
+                        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)),
+                    Err(_) => path != Path::new(STDOUT_PATH),
+                });
+            if escapes {
+                *path = None;
+                rejected.push(key);
+            }
+        }
+        rejected
+    }
+}
+
+impl From<&Args> for Settings {
+    /// Settings explicitly given on the command line
+    fn from(args: &Args) -> Self {
+        let non_empty = |patterns: &Vec<String>| (!patterns.is_empty()).then(|| patterns.clone());
+        // A `--foo`/`--no-foo` pair, where clap keeps only the last one given
+        let toggle = |on: bool, off: bool| match (on, off) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+
+        Self {
+            output_file: args.output_file.as_ref().map(PathBuf::from),
+            ignore_patterns: non_empty(&args.ignore_patterns),
+            include_patterns: non_empty(&args.include_patterns),
+            num_threads: args.threads,
+            respect_gitignore: args.respect_gitignore,
+            gitignore_path: args.gitignore_path.as_ref().map(PathBuf::from),
+            model: args.model,
+            git_cache_policy: args.git_cache_policy,
+            git_ref: args.git_ref.clone(),
+            include_metadata: toggle(args.include_metadata, args.no_include_metadata),
+            clip: toggle(args.clip, args.no_clip),
+            stdout: toggle(args.stdout, args.no_stdout),
+            format: args.format.clone(),
+            token_budget: args.token_budget,
+            budget_order: args.budget_order,
+            priority_patterns: non_empty(&args.priority_patterns),
+            split: args.split,
+            diff: args
+                .diff
+                .clone()
+                .or_else(|| args.staged.then_some(DiffSpec::Staged))
+                .or_else(|| args.worktree.then_some(DiffSpec::Worktree)),
+            diff_output: args.diff_output,
+            redact: toggle(args.redact, args.no_redact),
+            redact_patterns: non_empty(&args.redact_patterns),
+            index: toggle(args.index, args.no_index),
+            watch: toggle(args.watch, args.no_watch),
+            line_numbers: toggle(args.line_numbers, args.no_line_numbers),
+            max_file_size: args.max_file_size,
+            max_detect_size: args.max_detect_size,
+            truncate: args.truncate,
+            fallback_encoding: args.fallback_encoding.clone(),
+            notebook_output_lines: args.notebook_output_lines,
+            binary_summary: toggle(args.binary_summary, args.no_binary_summary),
+            binary_excerpt: args.binary_excerpt,
+            binary_excerpt_size: args.binary_excerpt_size,
+            expand_archives: toggle(args.expand_archives, args.no_expand_archives),
+            stream: toggle(args.stream, args.no_stream),
+            sort: args.sort,
+            reproducible: toggle(args.reproducible, args.no_reproducible),
+            report_depth: args.report_depth,
+            report: args.report.as_ref().map(PathBuf::from),
+            report_format: args.report_format,
+        }
+    }
+}
+
+/// A problem found while reading a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// File the problem was found in
+    pub path: PathBuf,
+    /// 1-based line number of the offending key
+    pub line: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+/// A parsed configuration file
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    /// Location of the file
+    pub path: PathBuf,
+    /// Top-level settings
+    pub settings: Settings,
+    /// Named profiles defined under `[profile.<name>]`
+    pub profiles: BTreeMap<String, Settings>,
+    /// Keys that could not be applied
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ConfigFile {
+    /// Read and parse a configuration file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let source = fs::read_to_string(path)?;
+        Ok(Self::parse(path, &source))
+    }
+
+    /// Parse configuration from a string
+    ///
+    /// Invalid keys do not abort parsing; they are collected in `issues` so
+    /// that `Config::validate` can report all of them with their location.
+    pub fn parse(path: &Path, source: &str) -> Self {
+        let mut file = Self {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+
+        let table = match source.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                let line = e
+                    .span()
+                    .map(|span| line_of_offset(source, span.start))
+                    .unwrap_or(1);
+                file.push_issue(line, e.message().to_string());
+                return file;
+            }
+        };
+
+        for (key, value) in table {
+            if key != "profile" {
+                let settings = file.parse_key(source, None, key, value);
+                file.settings.merge(settings);
+                continue;
+            }
+
+            let toml::Value::Table(profiles) = value else {
+                let line = find_key_line(source, None, "profile");
+                file.push_issue(line, "`profile` must be a table of named profiles".into());
+                continue;
+            };
+
+            for (name, value) in profiles {
+                let toml::Value::Table(table) = value else {
+                    let line = find_key_line(source, Some("profile"), &name);
+                    file.push_issue(line, format!("profile `{}` must be a table", name));
+                    continue;
+                };
+
+                let section = format!("profile.{}", name);
+                let mut settings = Settings::default();
+                for (key, value) in table {
+                    settings.merge(file.parse_key(source, Some(&section), key, value));
+                }
+                file.profiles.insert(name, settings);
+            }
+        }
+
+        if let Some(base) = path.parent() {
+            file.settings.resolve_paths(base);
+            for settings in file.profiles.values_mut() {
+                settings.resolve_paths(base);
+            }
+        }
+
+        file
+    }
+
+    /// Deserialize a single key, recording an issue if it is unknown or malformed
+    fn parse_key(
+        &mut self,
+        source: &str,
+        section: Option<&str>,
+        key: String,
+        value: toml::Value,
+    ) -> Settings {
+        let line = find_key_line(source, section, &key);
+        let mut table = toml::Table::new();
+        table.insert(key, value);
+
+        match Settings::deserialize(toml::Value::Table(table)) {
+            Ok(settings) => settings,
+            Err(e) => {
+                self.push_issue(line, e.message().to_string());
+                Settings::default()
+            }
+        }
+    }
+
+    /// Read the project file of a repository cloned from a remote
+    ///
+    /// Whoever controls the repository controls this file, so it may only
+    /// write inside the clone: paths that are absolute or climb out of it
+    /// are rejected, and `clip` and `stdout` are ignored.
+    pub fn load_untrusted(path: &Path) -> io::Result<Self> {
+        let source = fs::read_to_string(path)?;
+        let mut file = Self::parse(path, &source);
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let mut rejected = Vec::new();
+        for key in file.settings.restrict(base) {
+            rejected.push((find_key_line(&source, None, key), key));
+        }
+        for (name, settings) in &mut file.profiles {
+            let section = format!("profile.{}", name);
+            for key in settings.restrict(base) {
+                rejected.push((find_key_line(&source, Some(&section), key), key));
+            }
+        }
+        for (line, key) in rejected {
+            file.push_issue(
+                line,
+                format!("`{}` of a cloned repository must stay inside it", key),
+            );
+        }
+
+        Ok(file)
+    }
+
+    fn push_issue(&mut self, line: usize, message: String) {
+        self.issues.push(ConfigIssue {
+            path: self.path.clone(),
+            line,
+            message,
+        });
+    }
+}
+
+/// Path of the user-level configuration file, if a config directory is known
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("dumpfs").join(USER_CONFIG_FILE))
+}
+
+/// Find configuration files that apply to `target_dir`, lowest precedence first
+pub fn discover(target_dir: &Path) -> io::Result<Vec<ConfigFile>> {
+    discover_with(target_dir, ConfigFile::load)
+}
+
+/// Find configuration files that apply to a repository cloned from a remote
+///
+/// The project file is read with `ConfigFile::load_untrusted`.
+pub fn discover_cloned(target_dir: &Path) -> io::Result<Vec<ConfigFile>> {
+    discover_with(target_dir, ConfigFile::load_untrusted)
+}
+
+/// Find the user-level file and the project file, reading the latter with `load`
+fn discover_with(
+    target_dir: &Path,
+    load: fn(&Path) -> io::Result<ConfigFile>,
+) -> io::Result<Vec<ConfigFile>> {
+    let mut files = discover_user()?;
+    let project = target_dir.join(PROJECT_CONFIG_FILE);
+    if project.is_file() {
+        files.push(load(&project)?);
+    }
+    Ok(files)
+}
+
+/// Find the user-level configuration file, which applies wherever dumpfs runs
+pub fn discover_user() -> io::Result<Vec<ConfigFile>> {
+    user_config_path()
+        .filter(|path| path.is_file())
+        .map(|path| ConfigFile::load(&path))
+        .into_iter()
+        .collect()
+}
+
+/// Deserialize an enum from the same names accepted on the command line
+fn deserialize_value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let name = String::deserialize(deserializer)?;
+    T::from_str(&name, true).map(Some).map_err(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        serde::de::Error::custom(format!(
+            "invalid value `{}`, expected one of: {}",
+            name,
+            expected.join(", ")
+        ))
+    })
+}
+
+/// Deserialize a file size given as a number of bytes or a string such as `2mb`
+fn deserialize_file_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Bytes(u64),
+        Spec(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Bytes(bytes) => Ok(Some(bytes)),
+        Raw::Spec(spec) => parse_file_size(&spec)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Convert a byte offset into a 1-based line number
+fn line_of_offset(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Locate the line a key is defined on within a table section
+///
+/// `section` is the dotted table name (e.g. `profile.review`), or `None` for
+/// the top level. Falls back to the section header or line 1 when the key
+/// cannot be found (e.g. for inline tables).
+fn find_key_line(source: &str, section: Option<&str>, key: &str) -> usize {
+    let mut current: Option<String> = None;
+    let mut header_line = 1;
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.trim_end_matches(']').trim();
+            current = Some(name.replace(' ', ""));
+            if current.as_deref() == section {
+                header_line = index + 1;
+            }
+            continue;
+        }
+
+        if current.as_deref() != section {
+            continue;
+        }
+
+        let name = line.split('=').next().unwrap_or_default().trim();
+        if name.trim_matches('"') == key {
+            return index + 1;
+        }
+    }
+
+    header_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+ignore_patterns = ["*.log"]
+format = "xml"
+threads = 8
+token_budget = "auto"
+
+[profile.review]
+include_patterns = ["src/**"]
+model = "gpt4o"
+include_metadata = true
+"#;
+
+    #[test]
+    fn test_parse_settings_and_profiles() {
+        let file = ConfigFile::parse(Path::new("/repo/.dumpfs.toml"), SAMPLE);
+
+        assert!(file.issues.is_empty(), "{:?}", file.issues);
+        assert_eq!(
+            file.settings.ignore_patterns,
+            Some(vec!["*.log".to_string()])
+        );
+        assert!(matches!(file.settings.format, Some(FsWriterFormatter::Xml)));
+        assert_eq!(file.settings.num_threads, Some(8));
+        assert_eq!(file.settings.token_budget, Some(TokenBudget::Auto));
+
+        let review = file.profiles.get("review").expect("profile should exist");
+        assert_eq!(review.model, Some(Model::Gpt4o));
+        assert_eq!(review.include_metadata, Some(true));
+        assert!(review.ignore_patterns.is_none());
+    }
+
+    #[test]
+    fn test_bad_keys_report_file_and_line() {
+        let source = "format = \"xml\"\nignore = [\"*.log\"]\n\n[profile.ci]\nmodel = \"gpt-5\"\n";
+        let file = ConfigFile::parse(Path::new("/repo/.dumpfs.toml"), source);
+
+        assert_eq!(file.issues.len(), 2);
+        assert_eq!(file.issues[0].line, 2);
+        assert!(file.issues[0].message.contains("unknown field `ignore`"));
+        assert_eq!(file.issues[1].line, 5);
+        assert!(file.issues[1]
+            .to_string()
+            .starts_with("/repo/.dumpfs.toml:5:"));
+
+        // Valid keys are still applied
+        assert!(matches!(file.settings.format, Some(FsWriterFormatter::Xml)));
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_file() {
+        let source = "output_file = \"out/context.md\"\ngitignore_path = \"/abs/ignore\"\n";
+        let file = ConfigFile::parse(Path::new("/repo/.dumpfs.toml"), source);
+
+        assert_eq!(
+            file.settings.output_file,
+            Some(PathBuf::from("/repo/out/context.md"))
+        );
+        assert_eq!(
+            file.settings.gitignore_path,
+            Some(PathBuf::from("/abs/ignore"))
+        );
+
+        // Stdout is not a path
+        let file = ConfigFile::parse(Path::new("/repo/.dumpfs.toml"), "output_file = \"-\"\n");
+        assert_eq!(file.settings.output_file, Some(PathBuf::from("-")));
+    }
+}
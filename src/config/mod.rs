@@ -0,0 +1,734 @@
+/*!
+ * Configuration handling for DumpFS
+ */
+
+mod file;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use regex::Regex;
+
+pub use file::{
+    discover, discover_cloned, discover_user, user_config_path, ConfigFile, ConfigIssue, Settings,
+    PROJECT_CONFIG_FILE, USER_CONFIG_FILE,
+};
+
+use crate::binary::BinaryExcerpt;
+use crate::budget::{BudgetOrder, TokenBudget};
+use crate::encoding;
+use crate::error::{DumpFsError, Result};
+use crate::git::{ChangeSet, DiffOutput, DiffSpec, GitCachePolicy, GitRepoInfo};
+use crate::order::SortOrder;
+use crate::pattern::{split_line_range, PatternSet};
+use crate::report::{ReportFormat, DEFAULT_TREE_DEPTH};
+use crate::split::SplitLimit;
+use crate::tokenizer::Model;
+use crate::truncate::Truncation;
+use crate::utils::parse_file_size;
+use crate::FsWriterFormatter;
+
+/// Command-line arguments for DumpFS
+#[derive(Parser, Debug, Clone)]
+#[clap(
+    name = "dumpfs",
+    version = env!("CARGO_PKG_VERSION"),
+    disable_help_subcommand = true,
+    about = "Generate XML representation of directory contents for LLM context",
+    long_about = "Creates an XML representation of a directory structure and its contents, designed for providing context to Large Language Models (LLMs)."
+)]
+pub struct Args {
+    /// Subcommand to run instead of a single scan
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Target directory or Git repository URL to process
+    #[clap(default_value = ".")]
+    pub directory_path: String,
+
+    /// Output file name, or `-` to write to stdout
+    pub output_file: Option<String>,
+
+    /// Comma-separated list of patterns to ignore
+    #[clap(long, value_delimiter = ',')]
+    pub ignore_patterns: Vec<String>,
+
+    /// Comma-separated list of patterns to include (if specified, only matching files are included)
+    #[clap(long, value_delimiter = ',')]
+    pub include_patterns: Vec<String>,
+
+    /// Number of threads to use for processing (default: 4)
+    #[clap(long)]
+    pub threads: Option<usize>,
+
+    /// Respect .gitignore files (default: true)
+    #[clap(long)]
+    pub respect_gitignore: Option<bool>,
+
+    /// Path to custom .gitignore file
+    #[clap(long)]
+    pub gitignore_path: Option<String>,
+
+    /// Include file and directory metadata (size, modified time, permissions)
+    #[clap(
+        long,
+        overrides_with = "no_include_metadata",
+        help = "Include file and directory metadata in the XML output"
+    )]
+    pub include_metadata: bool,
+
+    /// Leave out file and directory metadata, even if a configuration file includes it
+    #[clap(long, overrides_with = "include_metadata")]
+    pub no_include_metadata: bool,
+
+    /// LLM model to use for tokenization (enables token counting)
+    #[clap(long, value_enum)]
+    pub model: Option<Model>,
+
+    /// Generate shell completions
+    #[clap(long = "generate", value_enum)]
+    pub generate: Option<Shell>,
+
+    /// Clean Git repository cache (specify number of days, 0 for all)
+    #[clap(long, value_name = "DAYS")]
+    pub clean_cache: Option<u64>,
+
+    /// Policy for handling Git repository caching (default: always-pull)
+    #[clap(long, value_enum)]
+    pub git_cache_policy: Option<GitCachePolicy>,
+
+    /// Copy output to clipboard
+    #[clap(
+        long,
+        overrides_with = "no_clip",
+        help = "Copy output to system clipboard"
+    )]
+    pub clip: bool,
+    /// Do not copy output to the clipboard, even if a configuration file does
+    #[clap(long, overrides_with = "clip")]
+    pub no_clip: bool,
+    /// Print output to stdout, without writing a file unless one is named
+    #[clap(long, overrides_with = "no_stdout", help = "print to stdout")]
+    pub stdout: bool,
+    /// Do not print output to stdout, even if a configuration file does
+    #[clap(long, overrides_with = "stdout")]
+    pub no_stdout: bool,
+
+    /// Writer format
+    #[clap(long, short)]
+    pub format: Option<FsWriterFormatter>,
+
+    /// Named profile from .dumpfs.toml to apply
+    #[clap(long)]
+    pub profile: Option<String>,
+
+    /// Maximum tokens of file content to include (a number, or `auto` for the model's context window)
+    #[clap(long, value_name = "N|auto")]
+    pub token_budget: Option<TokenBudget>,
+
+    /// Order in which files are kept when packing within the token budget (default: depth)
+    #[clap(long, value_enum)]
+    pub budget_order: Option<BudgetOrder>,
+
+    /// Comma-separated list of patterns for files to keep first when packing within the token budget
+    #[clap(long, value_delimiter = ',')]
+    pub priority_patterns: Vec<String>,
+
+    /// Split output into numbered parts of at most this size (e.g. `32k` tokens or `500kb`)
+    #[clap(long, value_name = "LIMIT")]
+    pub split: Option<SplitLimit>,
+
+    /// Only dump files changed between two revisions (`base..head` or `base...head`)
+    #[clap(long, value_name = "BASE..HEAD")]
+    pub diff: Option<DiffSpec>,
+
+    /// Only dump changes staged in the index
+    #[clap(long, conflicts_with = "diff")]
+    pub staged: bool,
+
+    /// Only dump unstaged changes and untracked files in the working tree
+    #[clap(long, conflicts_with_all = ["diff", "staged"])]
+    pub worktree: bool,
+
+    /// What to include for each changed file in diff mode (default: both)
+    #[clap(long, value_enum)]
+    pub diff_output: Option<DiffOutput>,
+
+    /// Branch, tag or commit to check out when dumping a repository URL (also `url#ref`)
+    #[clap(long = "ref", value_name = "REF")]
+    pub git_ref: Option<String>,
+
+    /// Redact secrets from file contents, even if a configuration file turns it off
+    #[clap(long, overrides_with = "no_redact")]
+    pub redact: bool,
+
+    /// Do not redact secrets such as API keys and private keys from file contents
+    #[clap(long, overrides_with = "redact")]
+    pub no_redact: bool,
+
+    /// Regular expression for additional secrets to redact (can be repeated)
+    #[clap(long = "redact-pattern", value_name = "REGEX")]
+    pub redact_patterns: Vec<String>,
+
+    /// Reuse the scan index of unchanged files, even if a configuration file turns it off
+    #[clap(long, overrides_with = "no_index")]
+    pub index: bool,
+
+    /// Do not reuse or update the scan index of unchanged files
+    #[clap(long, overrides_with = "index")]
+    pub no_index: bool,
+
+    /// Prefix each line of file content with its line number
+    #[clap(long, overrides_with = "no_line_numbers")]
+    pub line_numbers: bool,
+
+    /// Do not number lines, even if a configuration file does
+    #[clap(long, overrides_with = "line_numbers")]
+    pub no_line_numbers: bool,
+
+    /// Size above which text files are truncated or left out (e.g. `500kb`, `4mb`; default: 1mb)
+    #[clap(long, value_name = "SIZE", value_parser = parse_file_size)]
+    pub max_file_size: Option<u64>,
+
+    /// Size above which files are treated as binary without checking their content (default: 8mb)
+    #[clap(long, value_name = "SIZE", value_parser = parse_file_size)]
+    pub max_detect_size: Option<u64>,
+
+    /// Keep part of text files above the size limit: `head:N`, `tail:N`, `head-tail:N` lines or `tokens:N`
+    #[clap(long, value_name = "STRATEGY")]
+    pub truncate: Option<Truncation>,
+
+    /// Encoding of 8-bit text files that are not UTF-8 (e.g. `latin1`, `shift_jis`; default: windows-1252)
+    #[clap(long, value_name = "ENCODING")]
+    pub fallback_encoding: Option<String>,
+
+    /// Keep at most N lines of each notebook cell output (0 to drop outputs)
+    #[clap(long, value_name = "N")]
+    pub notebook_output_lines: Option<usize>,
+
+    /// Summarize binary files, even if a configuration file turns it off
+    #[clap(long, overrides_with = "no_binary_summary")]
+    pub binary_summary: bool,
+
+    /// Do not summarize binary files (MIME type, image dimensions and SHA-256)
+    #[clap(long, overrides_with = "binary_summary")]
+    pub no_binary_summary: bool,
+
+    /// Include a hex dump or base64 excerpt of small binary files
+    #[clap(long, value_name = "FORMAT")]
+    pub binary_excerpt: Option<BinaryExcerpt>,
+
+    /// Size up to which binary files get an excerpt (default: 1kb)
+    #[clap(long, value_name = "SIZE", value_parser = parse_file_size)]
+    pub binary_excerpt_size: Option<u64>,
+
+    /// Scan inside zip, tar and tar.gz archives as if they were directories
+    #[clap(long, overrides_with = "no_expand_archives")]
+    pub expand_archives: bool,
+
+    /// Leave archives as single files, even if a configuration file expands them
+    #[clap(long, overrides_with = "expand_archives")]
+    pub no_expand_archives: bool,
+
+    /// Write files as they are scanned instead of holding the whole tree in memory
+    #[clap(long, overrides_with = "no_stream")]
+    pub stream: bool,
+
+    /// Hold the whole tree in memory, even if a configuration file streams
+    #[clap(long, overrides_with = "stream")]
+    pub no_stream: bool,
+
+    /// Order of the entries within each directory (default: as listed by the filesystem)
+    #[clap(long, value_enum, value_name = "ORDER")]
+    pub sort: Option<SortOrder>,
+
+    /// Produce identical output for identical trees: sort by name and pin timestamps
+    #[clap(long, overrides_with = "no_reproducible")]
+    pub reproducible: bool,
+
+    /// Keep volatile fields, even if a configuration file asks for reproducible output
+    #[clap(long, overrides_with = "reproducible")]
+    pub no_reproducible: bool,
+
+    /// Levels of subdirectories shown in the report's directory table (default: 2)
+    #[clap(long, value_name = "N")]
+    pub report_depth: Option<usize>,
+
+    /// Also write the scan report to this file (JSON unless --report-format is given)
+    #[clap(long, value_name = "PATH")]
+    pub report: Option<String>,
+
+    /// Format of the scan report (default: table, or json when written with --report)
+    #[clap(long, value_enum, value_name = "FORMAT")]
+    pub report_format: Option<ReportFormat>,
+
+    /// Keep running and regenerate the output whenever files change
+    #[clap(
+        long,
+        overrides_with = "no_watch",
+        conflicts_with_all = ["diff", "staged", "worktree"]
+    )]
+    pub watch: bool,
+
+    /// Write the output once, even if a configuration file watches
+    #[clap(long, overrides_with = "watch")]
+    pub no_watch: bool,
+}
+
+/// Subcommands of DumpFS
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Serve scans over HTTP: POST a JSON scan request to `/scan`
+    Serve(ServeArgs),
+    /// Serve the target directory to coding agents over MCP on stdin/stdout
+    Mcp,
+}
+
+/// Arguments for `dumpfs serve`
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// Port to listen on
+    #[clap(long, default_value_t = 7878)]
+    pub port: u16,
+
+    /// Address to bind to
+    #[clap(long, default_value = "127.0.0.1")]
+    pub host: String,
+}
+
+/// Default number of threads used for processing
+pub const DEFAULT_THREADS: usize = 4;
+
+/// Default size above which text files are truncated or left out
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1_048_576;
+
+/// Default size above which files are treated as binary without checking their content
+pub const DEFAULT_MAX_DETECT_SIZE: u64 = 8_000_000;
+
+/// Output file name that stands for stdout
+pub const STDOUT_PATH: &str = "-";
+
+/// Application configuration
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// Target directory to process
+    pub target_dir: PathBuf,
+
+    /// Output file path (`-` for stdout)
+    pub output_file: PathBuf,
+
+    /// Patterns to ignore
+    pub ignore_patterns: Vec<String>,
+
+    /// Patterns to include (if empty, include all)
+    pub include_patterns: Vec<String>,
+
+    /// Number of threads to use for processing
+    pub num_threads: usize,
+
+    /// Whether to respect .gitignore files
+    pub respect_gitignore: bool,
+
+    /// Path to custom .gitignore file
+    pub gitignore_path: Option<PathBuf>,
+
+    /// LLM model to use for tokenization
+    pub model: Option<Model>,
+
+    /// Original repository URL (if applicable)
+    pub repo_url: Option<String>,
+
+    /// Git repository information (if applicable)
+    pub git_repo: Option<GitRepoInfo>,
+
+    /// Policy for handling Git repository caching
+    pub git_cache_policy: GitCachePolicy,
+
+    /// Branch, tag or commit to check out for repository URLs
+    pub git_ref: Option<String>,
+
+    /// Include file and directory metadata
+    pub include_metadata: bool,
+
+    /// Copy output to clipboard
+    pub clip: bool,
+
+    /// Print output to stdout
+    pub stdout: bool,
+
+    /// Writer format
+    pub format: FsWriterFormatter,
+
+    /// Token budget for file contents (if packing is enabled)
+    pub token_budget: Option<TokenBudget>,
+
+    /// Order in which files are kept when packing within the token budget
+    pub budget_order: BudgetOrder,
+
+    /// Patterns for files to keep first when packing within the token budget
+    pub priority_patterns: Vec<String>,
+
+    /// Maximum size of each output part (if splitting is enabled)
+    pub split: Option<SplitLimit>,
+
+    /// Changes to dump instead of the whole tree (if diff mode is enabled)
+    pub diff: Option<DiffSpec>,
+
+    /// What to include for each changed file in diff mode
+    pub diff_output: DiffOutput,
+
+    /// Files changed according to `diff`, collected before scanning
+    pub changes: Option<Arc<ChangeSet>>,
+
+    /// Whether secrets are redacted from file contents
+    pub redact: bool,
+
+    /// Additional regular expressions for secrets to redact
+    pub redact_patterns: Vec<String>,
+
+    /// Whether unchanged files are taken from the scan index
+    pub index: bool,
+
+    /// Keep running and regenerate the output on changes
+    pub watch: bool,
+
+    /// Prefix each line of file content with its line number
+    pub line_numbers: bool,
+
+    /// Size above which text files are truncated or left out (default: 1 MiB)
+    pub max_file_size: Option<u64>,
+
+    /// Size above which files are treated as binary without checking their content (default: 8 MB)
+    pub max_detect_size: Option<u64>,
+
+    /// Part of text files above the size limit to keep (if truncation is enabled)
+    pub truncate: Option<Truncation>,
+
+    /// Encoding of 8-bit text files that are not UTF-8 (default: windows-1252)
+    pub fallback_encoding: Option<String>,
+
+    /// Lines kept of each notebook cell output (None for all)
+    pub notebook_output_lines: Option<usize>,
+
+    /// Whether binary files are summarized
+    pub binary_summary: bool,
+
+    /// Encoding of excerpts of small binary files (None for no excerpts)
+    pub binary_excerpt: Option<BinaryExcerpt>,
+
+    /// Size up to which binary files get an excerpt (default: 1 KiB)
+    pub binary_excerpt_size: Option<u64>,
+
+    /// Whether archives are scanned as virtual directories
+    pub expand_archives: bool,
+
+    /// Whether files are written as they are scanned
+    pub stream: bool,
+
+    /// Order of the entries within each directory (None for the filesystem order)
+    pub sort: Option<SortOrder>,
+
+    /// Whether volatile fields (scan time, hostname, modification times) are left out or pinned
+    pub reproducible: bool,
+
+    /// Levels of subdirectories shown in the report's directory table
+    pub report_depth: usize,
+
+    /// File the scan report is also written to
+    pub report: Option<PathBuf>,
+
+    /// Format of the scan report (None for the default of where it goes)
+    pub report_format: Option<ReportFormat>,
+
+    /// Configuration files that were applied, lowest precedence first
+    pub config_files: Vec<PathBuf>,
+
+    /// Problems found while reading configuration files
+    pub config_issues: Vec<ConfigIssue>,
+}
+
+impl Config {
+    /// Create configuration from command-line arguments only
+    pub fn from_args(args: Args) -> Self {
+        let target_dir = PathBuf::from(&args.directory_path);
+        Self::from_settings(target_dir, Settings::from(&args))
+    }
+
+    /// Create configuration from command-line arguments layered over configuration files
+    ///
+    /// Settings are applied in increasing order of precedence: the user-level
+    /// file, the project `.dumpfs.toml` in `target_dir`, the selected profile
+    /// (if any) and finally flags given on the command line.
+    pub fn load(args: Args, target_dir: &Path) -> Result<Self> {
+        Self::layer(args, target_dir, discover(target_dir)?, true)
+    }
+
+    /// Create configuration for a repository cloned from a remote
+    ///
+    /// Like `load`, but the clone's project file may not write outside the clone.
+    pub fn load_cloned(args: Args, target_dir: &Path) -> Result<Self> {
+        Self::layer(args, target_dir, discover_cloned(target_dir)?, true)
+    }
+
+    /// Create configuration from command-line arguments layered over the user-level file
+    ///
+    /// Used for Git repositories before they are cloned, when their project
+    /// file cannot be read yet. A profile missing from the user-level file is
+    /// not an error, as it may be defined by the project file.
+    pub fn load_user(args: Args, target_dir: &Path) -> Result<Self> {
+        Self::layer(args, target_dir, discover_user()?, false)
+    }
+
+    /// Layer the settings of `files`, the selected profile and command-line flags
+    fn layer(
+        args: Args,
+        target_dir: &Path,
+        files: Vec<ConfigFile>,
+        require_profile: bool,
+    ) -> Result<Self> {
+        let mut settings = Settings::default();
+        for file in &files {
+            settings.merge(file.settings.clone());
+        }
+
+        if let Some(name) = &args.profile {
+            let profiles: Vec<&Settings> =
+                files.iter().filter_map(|f| f.profiles.get(name)).collect();
+            if profiles.is_empty() && require_profile {
+                return Err(DumpFsError::Config(format!(
+                    "Profile `{}` not found in any configuration file",
+                    name
+                )));
+            }
+            for profile in profiles {
+                settings.merge(profile.clone());
+            }
+        }
+
+        settings.merge(Settings::from(&args));
+
+        let mut config = Self::from_settings(target_dir.to_path_buf(), settings);
+        config.config_files = files.iter().map(|f| f.path.clone()).collect();
+        config.config_issues = files.into_iter().flat_map(|f| f.issues).collect();
+        Ok(config)
+    }
+
+    /// Build a configuration from resolved settings, filling in defaults
+    pub(crate) fn from_settings(target_dir: PathBuf, settings: Settings) -> Self {
+        let format = settings.format.unwrap_or_default();
+        // Printing to stdout only writes a file when one is named, or when
+        // split parts need a file name to be numbered
+        let output_file = settings
+            .output_file
+            .unwrap_or_else(|| match settings.stdout {
+                Some(true) if settings.split.is_none() => PathBuf::from(STDOUT_PATH),
+                _ => target_dir.join(format.default_file_name()),
+            });
+
+        Self {
+            target_dir,
+            output_file,
+            ignore_patterns: settings.ignore_patterns.unwrap_or_default(),
+            include_patterns: settings.include_patterns.unwrap_or_default(),
+            num_threads: settings.num_threads.unwrap_or(DEFAULT_THREADS),
+            respect_gitignore: settings.respect_gitignore.unwrap_or(true),
+            gitignore_path: settings.gitignore_path,
+            model: settings.model,
+            repo_url: None,
+            git_repo: None,
+            git_cache_policy: settings.git_cache_policy.unwrap_or_default(),
+            git_ref: settings.git_ref,
+            include_metadata: settings.include_metadata.unwrap_or(false),
+            stdout: settings.stdout.unwrap_or(false),
+            clip: settings.clip.unwrap_or(false),
+            format,
+            token_budget: settings.token_budget,
+            budget_order: settings.budget_order.unwrap_or_default(),
+            priority_patterns: settings.priority_patterns.unwrap_or_default(),
+            split: settings.split,
+            diff: settings.diff,
+            diff_output: settings.diff_output.unwrap_or_default(),
+            changes: None,
+            redact: settings.redact.unwrap_or(true),
+            redact_patterns: settings.redact_patterns.unwrap_or_default(),
+            index: settings.index.unwrap_or(true),
+            watch: settings.watch.unwrap_or(false),
+            line_numbers: settings.line_numbers.unwrap_or(false),
+            max_file_size: settings.max_file_size,
+            max_detect_size: settings.max_detect_size,
+            truncate: settings.truncate,
+            fallback_encoding: settings.fallback_encoding,
+            notebook_output_lines: settings.notebook_output_lines,
+            binary_summary: settings.binary_summary.unwrap_or(true),
+            binary_excerpt: settings.binary_excerpt,
+            binary_excerpt_size: settings.binary_excerpt_size,
+            expand_archives: settings.expand_archives.unwrap_or(false),
+            stream: settings.stream.unwrap_or(false),
+            // Reproducible output needs a stable order
+            sort: settings.sort.or_else(|| {
+                settings
+                    .reproducible
+                    .unwrap_or(false)
+                    .then_some(SortOrder::Name)
+            }),
+            reproducible: settings.reproducible.unwrap_or(false),
+            report_depth: settings.report_depth.unwrap_or(DEFAULT_TREE_DEPTH),
+            report: settings.report,
+            report_format: settings.report_format,
+            config_files: Vec::new(),
+            config_issues: Vec::new(),
+        }
+    }
+
+    /// Whether the output goes to stdout instead of a file
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output_file == Path::new(STDOUT_PATH)
+    }
+
+    /// Validate the configuration
+    pub fn validate(&self) -> io::Result<()> {
+        // Report invalid keys from configuration files with their location
+        if !self.config_issues.is_empty() {
+            let issues: Vec<String> = self.config_issues.iter().map(|i| i.to_string()).collect();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid configuration:\n  {}", issues.join("\n  ")),
+            ));
+        }
+
+        // For Git repositories, we've already validated during cloning
+        if self.repo_url.is_some() && self.git_repo.is_some() {
+            // Check if the cloned directory exists and is readable
+            // Blob URLs point at a single file rather than a directory
+            if !self.target_dir.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "Path not found in cloned repository: {}",
+                        self.target_dir.display()
+                    ),
+                ));
+            }
+        } else {
+            // For local directories, check if target directory exists and is readable
+            if !self.target_dir.exists() || !self.target_dir.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Target directory not found: {}", self.target_dir.display()),
+                ));
+            }
+        }
+
+        // Check if output file directory exists and is writable
+        if let Some(parent) = self.output_file.parent() {
+            if !parent.exists() && parent != Path::new("") {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Output directory not found: {}", parent.display()),
+                ));
+            }
+        }
+
+        // Check that the report file can be created
+        if let Some(parent) = self.report.as_deref().and_then(Path::parent) {
+            if !parent.exists() && parent != Path::new("") {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Report directory not found: {}", parent.display()),
+                ));
+            }
+        }
+
+        // Check that an automatic token budget can be resolved
+        if let Some(budget) = &self.token_budget {
+            budget.resolve(self.model)?;
+        }
+
+        // Changes in diff mode are collected once, so they cannot be watched
+        if self.watch && self.diff.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Watch mode cannot be combined with diff mode",
+            ));
+        }
+
+        // Parts of a split output are written next to the output file
+        if self.split.is_some() && self.writes_to_stdout() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Split output needs an output file name to number its parts",
+            ));
+        }
+
+        // Streaming writes files before the rest of the tree is known
+        if self.stream {
+            let conflict = if self.token_budget.is_some() {
+                Some("a token budget")
+            } else if self.split.is_some() {
+                Some("split output")
+            } else if self.watch {
+                Some("watch mode")
+            } else {
+                None
+            };
+            if let Some(conflict) = conflict {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Streaming output cannot be combined with {}", conflict),
+                ));
+            }
+        }
+
+        // Check that include, ignore and priority patterns compile, and that line ranges are valid
+        let include_patterns = self
+            .include_patterns
+            .iter()
+            .map(|spec| split_line_range(spec).map(|(pattern, _)| pattern))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let ignore_patterns = self.ignore_patterns.iter().map(String::as_str);
+        let priority_patterns = self.priority_patterns.iter().map(String::as_str);
+        for pattern in ignore_patterns
+            .chain(include_patterns)
+            .chain(priority_patterns)
+        {
+            if let Err(e) = PatternSet::new([pattern]) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid pattern `{}`: {}", pattern, e),
+                ));
+            }
+        }
+
+        // Check that the fallback encoding is known
+        if let Some(label) = &self.fallback_encoding {
+            encoding::for_label(label)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+
+        // Check that custom redaction patterns compile
+        for pattern in &self.redact_patterns {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid redaction pattern `{}`: {}", pattern, e),
+                ));
+            }
+        }
+
+        // Check if custom gitignore file exists
+        if let Some(path) = &self.gitignore_path {
+            if !path.exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Custom .gitignore file not found: {}", path.display()),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+/*!
+ * Detection and decoding of text encodings
+ *
+ * Text files are decoded to UTF-8 before anything else looks at them. The
+ * encoding is taken from a byte order mark if there is one, then UTF-16
+ * without a BOM is recognized by its zero bytes, then UTF-8 is tried. Other
+ * 8-bit text is decoded with a fallback legacy encoding (Windows-1252 unless
+ * configured otherwise). Content that does not look like text in any of them
+ * is treated as binary.
+ */
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Number of leading bytes sampled to detect whether a file is text
+pub const SAMPLE_SIZE: usize = 8192;
+
+/// Legacy encoding assumed for 8-bit text that is not UTF-8
+pub const DEFAULT_FALLBACK: &Encoding = WINDOWS_1252;
+
+/// Share of control characters above which content is treated as binary
+const MAX_CONTROL_RATIO: f32 = 0.1;
+
+/// Encoding for a label such as `latin1`, `shift_jis` or `windows-1251`
+pub fn for_label(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("unknown encoding `{}`", label))
+}
+
+/// Encoding of text content, or None if it looks binary
+///
+/// `complete` tells whether `bytes` is the whole content rather than a sample,
+/// in which case a multi-byte character cut off at the end is an error.
+pub fn detect(
+    bytes: &[u8],
+    complete: bool,
+    fallback: &'static Encoding,
+) -> Option<&'static Encoding> {
+    if bytes.is_empty() {
+        return Some(UTF_8);
+    }
+
+    let encoding = if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        encoding
+    } else if let Some(encoding) = detect_utf16(bytes) {
+        encoding
+    } else if is_utf8(bytes, complete) {
+        UTF_8
+    } else if !bytes.contains(&0) {
+        fallback
+    } else {
+        return None;
+    };
+
+    // Text has few control characters once decoded
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    let chars = text.chars().count().max(1);
+    let controls = text
+        .chars()
+        .filter(|&c| c < '\u{9}' || ('\u{e}'..'\u{20}').contains(&c))
+        .count();
+    (controls as f32 / (chars as f32) < MAX_CONTROL_RATIO).then_some(encoding)
+}
+
+/// Detect the encoding of a file from its leading bytes, or None if it looks binary
+pub fn detect_file(
+    path: &Path,
+    fallback: &'static Encoding,
+) -> io::Result<Option<&'static Encoding>> {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    let file = File::open(path)?;
+    file.take(SAMPLE_SIZE as u64).read_to_end(&mut sample)?;
+    let complete = sample.len() < SAMPLE_SIZE;
+    Ok(detect(&sample, complete, fallback))
+}
+
+/// Decode text to UTF-8, dropping any byte order mark
+///
+/// Returns the text and the encoding it was decoded from. Content that turns
+/// out not to be valid in the detected encoding is decoded with the fallback,
+/// which accepts any bytes for the default Windows-1252.
+pub fn decode(bytes: &[u8], fallback: &'static Encoding) -> (String, &'static Encoding) {
+    let encoding = detect(bytes, true, fallback).unwrap_or(fallback);
+    let (text, encoding, _) = encoding.decode(bytes);
+    (text.into_owned(), encoding)
+}
+
+/// Name to record in metadata, or None for UTF-8
+pub fn label(encoding: &'static Encoding) -> Option<String> {
+    (encoding != UTF_8).then(|| encoding.name().to_string())
+}
+
+/// Whether bytes are UTF-8, allowing a sample to end in the middle of a character
+fn is_utf8(bytes: &[u8], complete: bool) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        // `error_len` is None when the input ends inside a valid sequence
+        Err(e) => !complete && e.error_len().is_none(),
+    }
+}
+
+/// Recognize UTF-16 without a BOM by zero high bytes of ASCII characters
+fn detect_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let zeros_at = |offset: usize| {
+        bytes
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .take(pairs)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+
+    // Mostly ASCII text leaves one byte of nearly every unit zero, and the other never
+    match (even * 10 >= pairs * 7, odd * 10 >= pairs * 7) {
+        (false, true) if even == 0 => Some(UTF_16LE),
+        (true, false) if odd == 0 => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+        let mut bytes = if bom { vec![0xFF, 0xFE] } else { Vec::new() };
+        bytes.extend(text.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        bytes
+    }
+
+    #[test]
+    fn test_detect_encodings() {
+        assert_eq!(
+            detect(b"fn main() {}\n", true, DEFAULT_FALLBACK),
+            Some(UTF_8)
+        );
+        assert_eq!(
+            detect(b"\xEF\xBB\xBFhello\n", true, DEFAULT_FALLBACK),
+            Some(UTF_8)
+        );
+        assert_eq!(
+            detect(&utf16le("int main();\r\n", true), true, DEFAULT_FALLBACK),
+            Some(UTF_16LE)
+        );
+        assert_eq!(
+            detect(&utf16le("int main();\r\n", false), true, DEFAULT_FALLBACK),
+            Some(UTF_16LE)
+        );
+        assert_eq!(
+            detect(b"caf\xE9 cr\xE8me\n", true, DEFAULT_FALLBACK),
+            Some(WINDOWS_1252)
+        );
+        assert_eq!(
+            detect(&[0, 1, 2, 3, 0, 0, 7, 8], true, DEFAULT_FALLBACK),
+            None
+        );
+    }
+
+    #[test]
+    fn test_sample_split_inside_character() {
+        // `é` is two bytes in UTF-8; the sample ends after the first
+        let sample = b"caf\xC3";
+        assert_eq!(detect(sample, false, DEFAULT_FALLBACK), Some(UTF_8));
+        assert_eq!(detect(sample, true, DEFAULT_FALLBACK), Some(WINDOWS_1252));
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(&utf16le("line 1\nline 2\n", true), DEFAULT_FALLBACK),
+            ("line 1\nline 2\n".to_string(), UTF_16LE)
+        );
+        assert_eq!(
+            decode(b"caf\xE9\n", DEFAULT_FALLBACK),
+            ("café\n".to_string(), WINDOWS_1252)
+        );
+        let shift_jis = for_label("shift_jis").unwrap();
+        assert_eq!(
+            decode(b"\x93\xFA\x96\x7B\n", shift_jis),
+            ("日本\n".to_string(), shift_jis)
+        );
+        assert_eq!(label(UTF_8), None);
+        assert_eq!(label(UTF_16LE).as_deref(), Some("UTF-16LE"));
+        assert!(for_label("no-such-encoding").is_err());
+    }
+}
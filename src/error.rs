@@ -40,6 +40,10 @@ pub enum DumpFsError {
     #[error("Regex error: {0}")]
     Regex(#[from] regex::Error),
 
+    /// Filesystem watcher errors
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
+
     /// Scanner errors
     #[error("Scanner error: {0}")]
     Scanner(String),
@@ -115,6 +119,6 @@ impl<T, E: std::error::Error + 'static> ResultExt<T, E> for std::result::Result<
 // Allow converting DumpFsError to io::Error for backward compatibility with tests
 impl From<DumpFsError> for io::Error {
     fn from(err: DumpFsError) -> Self {
-        io::Error::new(io::ErrorKind::Other, err.to_string())
+        io::Error::other(err.to_string())
     }
 }
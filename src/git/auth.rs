@@ -0,0 +1,240 @@
+/*!
+ * Credentials for private repositories
+ *
+ * libgit2 calls the credentials callback again after every rejected attempt,
+ * so the chain hands out each source once: ssh-agent, keys in `~/.ssh`, an
+ * access token from the environment, git credential helpers, and finally the
+ * platform default (e.g. Negotiate/NTLM).
+ */
+
+use std::env;
+use std::path::PathBuf;
+
+use git2::{Cred, CredentialType, ErrorClass, ErrorCode, RemoteCallbacks};
+
+use super::error::GitError;
+use super::url::GitHost;
+
+/// Message of the error returned once every credential source was tried
+const EXHAUSTED: &str = "no more credentials to try";
+
+/// Private keys tried when ssh-agent does not authenticate, in order
+const SSH_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Source of credentials for one fetch or clone
+pub(crate) struct CredentialChain {
+    /// Hosting platform, used to pick the token environment variables
+    host: GitHost,
+    tried_username: bool,
+    tried_agent: bool,
+    /// Private keys not tried yet
+    keys: Vec<PathBuf>,
+    tried_token: bool,
+    tried_helper: bool,
+    tried_default: bool,
+}
+
+impl CredentialChain {
+    pub fn new(host: GitHost) -> Self {
+        let keys = dirs::home_dir()
+            .map(|home| {
+                SSH_KEYS
+                    .iter()
+                    .map(|name| home.join(".ssh").join(name))
+                    .filter(|path| path.exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            host,
+            tried_username: false,
+            tried_agent: false,
+            keys,
+            tried_token: false,
+            tried_helper: false,
+            tried_default: false,
+        }
+    }
+
+    /// Next credential to offer for `url`, or an error when all were tried
+    pub fn next(
+        &mut self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        // SSH asks for a user name first when the URL has none
+        if allowed.contains(CredentialType::USERNAME) && !self.tried_username {
+            self.tried_username = true;
+            return Cred::username(username.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            let user = username.unwrap_or("git");
+            if !self.tried_agent {
+                self.tried_agent = true;
+                if let Ok(cred) = Cred::ssh_key_from_agent(user) {
+                    return Ok(cred);
+                }
+            }
+            while !self.keys.is_empty() {
+                let key = self.keys.remove(0);
+                let public_key = key.with_extension("pub");
+                let public_key = public_key.exists().then_some(public_key.as_path());
+                if let Ok(cred) = Cred::ssh_key(user, public_key, &key, None) {
+                    return Ok(cred);
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if !self.tried_token {
+                self.tried_token = true;
+                if let Some((user, token)) = token_for(&self.host, |name| env::var(name).ok()) {
+                    return Cred::userpass_plaintext(
+                        user.unwrap_or(username.unwrap_or("git")),
+                        &token,
+                    );
+                }
+            }
+            if !self.tried_helper {
+                self.tried_helper = true;
+                if let Ok(config) = git2::Config::open_default() {
+                    if let Ok(cred) = Cred::credential_helper(&config, url, username) {
+                        return Ok(cred);
+                    }
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) && !self.tried_default {
+            self.tried_default = true;
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str(EXHAUSTED))
+    }
+}
+
+/// Access token from the environment for a hosting platform
+///
+/// Returns the user name the platform expects with the token (None to use
+/// the one from the URL) and the token itself. `DUMPFS_GIT_TOKEN` applies to
+/// any host.
+fn token_for(
+    host: &GitHost,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Option<(Option<&'static str>, String)> {
+    let candidates: &[(&str, Option<&'static str>)] = match host {
+        GitHost::GitHub => &[
+            ("GITHUB_TOKEN", Some("x-access-token")),
+            ("GH_TOKEN", Some("x-access-token")),
+        ],
+        GitHost::GitLab => &[
+            ("GITLAB_TOKEN", Some("oauth2")),
+            ("CI_JOB_TOKEN", Some("gitlab-ci-token")),
+        ],
+        GitHost::Bitbucket => &[("BITBUCKET_TOKEN", Some("x-token-auth"))],
+        GitHost::Other(_) => &[],
+    };
+
+    candidates
+        .iter()
+        .chain(&[("DUMPFS_GIT_TOKEN", None)])
+        .find_map(|(name, user)| {
+            lookup(name)
+                .filter(|token| !token.is_empty())
+                .map(|token| (*user, token))
+        })
+}
+
+/// Remote callbacks that authenticate through the credential chain
+pub(crate) fn remote_callbacks<'a>(host: &GitHost) -> RemoteCallbacks<'a> {
+    let mut chain = CredentialChain::new(host.clone());
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| chain.next(url, username, allowed));
+    callbacks
+}
+
+/// Turn authentication failures into `GitError::AuthError`, and anything else with `other`
+pub(crate) fn map_error(
+    error: git2::Error,
+    url: &str,
+    host: &GitHost,
+    other: fn(git2::Error) -> GitError,
+) -> GitError {
+    let is_auth = error.code() == ErrorCode::Auth
+        || error.message().contains(EXHAUSTED)
+        || (error.class() == ErrorClass::Ssh && error.message().contains("authentication"))
+        || (error.class() == ErrorClass::Http && error.message().contains("401"));
+
+    if !is_auth {
+        return other(error);
+    }
+
+    let hint = match host {
+        GitHost::GitHub => "set GITHUB_TOKEN",
+        GitHost::GitLab => "set GITLAB_TOKEN",
+        GitHost::Bitbucket => "set BITBUCKET_TOKEN",
+        GitHost::Other(_) => "set DUMPFS_GIT_TOKEN",
+    };
+    GitError::AuthError(format!(
+        "{} ({}). To authenticate, {}, add a key to ssh-agent or ~/.ssh, or configure a git credential helper",
+        url,
+        error.message(),
+        hint
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_for_host() {
+        let env = |name: &str| match name {
+            "GH_TOKEN" => Some("gh".to_string()),
+            "GITLAB_TOKEN" => Some(String::new()),
+            "CI_JOB_TOKEN" => Some("job".to_string()),
+            "DUMPFS_GIT_TOKEN" => Some("any".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            token_for(&GitHost::GitHub, env),
+            Some((Some("x-access-token"), "gh".to_string()))
+        );
+        // Empty variables are skipped
+        assert_eq!(
+            token_for(&GitHost::GitLab, env),
+            Some((Some("gitlab-ci-token"), "job".to_string()))
+        );
+        assert_eq!(
+            token_for(&GitHost::Other("git.example.com".to_string()), env),
+            Some((None, "any".to_string()))
+        );
+        assert_eq!(token_for(&GitHost::Bitbucket, |_| None), None);
+    }
+
+    #[test]
+    fn test_chain_is_exhausted() {
+        let mut chain = CredentialChain::new(GitHost::Other("example.com".to_string()));
+        chain.keys.clear();
+        chain.tried_agent = true;
+        chain.tried_token = true;
+        chain.tried_helper = true;
+
+        let err = match chain.next("https://example.com/o/r", None, CredentialType::SSH_KEY) {
+            Ok(_) => panic!("expected the chain to be exhausted"),
+            Err(err) => err,
+        };
+        let mapped = map_error(
+            err,
+            "https://example.com/o/r",
+            &GitHost::Other("example.com".to_string()),
+            GitError::CloneError,
+        );
+        assert!(matches!(mapped, GitError::AuthError(_)));
+    }
+}
@@ -0,0 +1,444 @@
+/*!
+ * Changed-file discovery for diff mode
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use git2::{
+    Delta, Diff, DiffOptions, FetchOptions, FileMode, Object, Repository as Git2Repository,
+};
+use serde::{Deserialize, Deserializer};
+
+use super::auth;
+use super::error::{GitError, GitResult};
+use super::url::{is_abbreviated_id, is_object_id, parse_git_url, GitHost};
+
+/// Which changes to dump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpec {
+    /// Changes between two revisions (`base..head`, or `base...head` to diff from their merge base)
+    Range {
+        base: String,
+        head: String,
+        merge_base: bool,
+    },
+    /// Changes staged in the index
+    Staged,
+    /// Unstaged changes in the working tree, including untracked files
+    Worktree,
+}
+
+impl FromStr for DiffSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "staged" => return Ok(DiffSpec::Staged),
+            "worktree" => return Ok(DiffSpec::Worktree),
+            _ => {}
+        }
+
+        let (base, head, merge_base) = match s.split_once("...") {
+            Some((base, head)) => (base, head, true),
+            None => match s.split_once("..") {
+                Some((base, head)) => (base, head, false),
+                None => return Err(format!("expected `<base>..<head>`, got `{}`", s)),
+            },
+        };
+
+        if base.is_empty() {
+            return Err(format!("missing base revision in `{}`", s));
+        }
+
+        Ok(DiffSpec::Range {
+            base: base.to_string(),
+            head: if head.is_empty() { "HEAD" } else { head }.to_string(),
+            merge_base,
+        })
+    }
+}
+
+impl fmt::Display for DiffSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffSpec::Range {
+                base,
+                head,
+                merge_base,
+            } => write!(
+                f,
+                "{}{}{}",
+                base,
+                if *merge_base { "..." } else { ".." },
+                head
+            ),
+            DiffSpec::Staged => write!(f, "staged"),
+            DiffSpec::Worktree => write!(f, "worktree"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DiffSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// What to include for each changed file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiffOutput {
+    /// Full file content only
+    Content,
+    /// Unified diff only
+    Diff,
+    /// Full file content followed by its unified diff
+    #[default]
+    Both,
+}
+
+impl DiffOutput {
+    /// Whether the full file content is included
+    pub fn content(&self) -> bool {
+        matches!(self, DiffOutput::Content | DiffOutput::Both)
+    }
+
+    /// Whether the unified diff is included
+    pub fn diff(&self) -> bool {
+        matches!(self, DiffOutput::Diff | DiffOutput::Both)
+    }
+}
+
+/// How a file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+/// Where the changed version of a file is read from
+#[derive(Debug, Clone)]
+pub enum FileVersion {
+    /// The file on disk
+    Worktree,
+    /// A blob from the head revision or the index
+    Blob { data: Vec<u8>, executable: bool },
+    /// The file no longer exists
+    Deleted,
+}
+
+/// A single changed file
+#[derive(Debug, Clone)]
+pub struct FileChange {
+    /// Absolute path of the file in the working tree
+    pub path: PathBuf,
+    /// How the file changed
+    pub status: ChangeStatus,
+    /// Unified diff of the change
+    pub patch: String,
+    /// Changed version of the file
+    pub version: FileVersion,
+}
+
+/// Files changed in a diff, keyed by absolute path
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    /// Changed files in path order
+    pub changes: BTreeMap<PathBuf, FileChange>,
+    /// Modification time reported for blobs (the head commit time)
+    pub modified: SystemTime,
+}
+
+impl ChangeSet {
+    /// Whether `path` is a changed file or a directory containing one
+    pub fn touches(&self, path: &Path) -> bool {
+        self.changes
+            .range(path.to_path_buf()..)
+            .next()
+            .is_some_and(|(changed, _)| changed.starts_with(path))
+    }
+}
+
+/// Collect the files changed according to `spec` in the repository containing `path`
+pub fn changed_files(path: &Path, spec: &DiffSpec) -> GitResult<ChangeSet> {
+    let repo = Git2Repository::discover(path).map_err(GitError::OpenError)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| GitError::NotFound(format!("working tree for {}", path.display())))?
+        .canonicalize()?;
+
+    let mut options = DiffOptions::new();
+    let mut modified = SystemTime::now();
+
+    let (mut diff, from_worktree) = match spec {
+        DiffSpec::Range {
+            base,
+            head,
+            merge_base,
+        } => {
+            let head_commit = resolve(&repo, head, !merge_base)?.peel_to_commit()?;
+            let mut base_commit = resolve(&repo, base, !merge_base)?.peel_to_commit()?;
+            if *merge_base {
+                let oid = repo.merge_base(base_commit.id(), head_commit.id())?;
+                base_commit = repo.find_commit(oid)?;
+            }
+            modified = UNIX_EPOCH + Duration::from_secs(head_commit.time().seconds().max(0) as u64);
+
+            let diff = repo.diff_tree_to_tree(
+                Some(&base_commit.tree()?),
+                Some(&head_commit.tree()?),
+                Some(&mut options),
+            )?;
+            (diff, false)
+        }
+        DiffSpec::Staged => {
+            let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+            let index = repo.index()?;
+            let diff =
+                repo.diff_tree_to_index(head_tree.as_ref(), Some(&index), Some(&mut options))?;
+            (diff, false)
+        }
+        DiffSpec::Worktree => {
+            options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+            (repo.diff_index_to_workdir(None, Some(&mut options))?, true)
+        }
+    };
+    diff.find_similar(None)?;
+
+    let mut changes = BTreeMap::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        let status = match delta.status() {
+            Delta::Added | Delta::Untracked | Delta::Copied => ChangeStatus::Added,
+            Delta::Modified | Delta::Typechange => ChangeStatus::Modified,
+            Delta::Deleted => ChangeStatus::Deleted,
+            Delta::Renamed => ChangeStatus::Renamed,
+            _ => continue,
+        };
+
+        let file = match status {
+            ChangeStatus::Deleted => delta.old_file(),
+            _ => delta.new_file(),
+        };
+        // Submodules have no content to dump
+        if file.mode() == FileMode::Commit {
+            continue;
+        }
+        let Some(rel_path) = file.path() else {
+            continue;
+        };
+
+        let version = match status {
+            ChangeStatus::Deleted => FileVersion::Deleted,
+            _ if from_worktree => FileVersion::Worktree,
+            _ => FileVersion::Blob {
+                data: repo.find_blob(file.id())?.content().to_vec(),
+                executable: file.mode() == FileMode::BlobExecutable,
+            },
+        };
+
+        let path = workdir.join(rel_path);
+        changes.insert(
+            path.clone(),
+            FileChange {
+                path,
+                status,
+                patch: patch_text(&diff, idx)?,
+                version,
+            },
+        );
+    }
+
+    Ok(ChangeSet { changes, modified })
+}
+
+/// Render the unified diff of one delta
+fn patch_text(diff: &Diff, idx: usize) -> GitResult<String> {
+    let mut text = Vec::new();
+    if let Some(mut patch) = git2::Patch::from_diff(diff, idx)? {
+        patch.print(&mut |_, _, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                text.push(line.origin() as u8);
+            }
+            text.extend_from_slice(line.content());
+            true
+        })?;
+    } else {
+        // Binary deltas have no patch; describe them like `git diff` does
+        text.extend_from_slice(b"Binary files differ\n");
+    }
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+/// Resolve a revision, fetching it from `origin` if it is not available locally
+///
+/// Cached clones are shallow and only know their default branch, so other
+/// branches, tags and commits have to be fetched before they can be diffed.
+fn resolve<'r>(repo: &'r Git2Repository, rev: &str, shallow: bool) -> GitResult<Object<'r>> {
+    let lookup = |repo: &'r Git2Repository| {
+        repo.revparse_single(rev)
+            .or_else(|_| repo.revparse_single(&format!("origin/{}", rev)))
+    };
+
+    if let Ok(object) = lookup(repo) {
+        return Ok(object);
+    }
+
+    let mut remote = repo
+        .find_remote("origin")
+        .map_err(|_| GitError::NotFound(format!("revision `{}`", rev)))?;
+
+    let mut refspecs = vec![
+        format!("+refs/heads/{0}:refs/remotes/origin/{0}", rev),
+        format!("+refs/tags/{0}:refs/tags/{0}", rev),
+    ];
+    if is_object_id(rev) {
+        refspecs.push(rev.to_string());
+    }
+
+    // Authenticate like the clone did, based on the remote's host
+    let url = remote.url().unwrap_or_default().to_string();
+    let host = parse_git_url(&url)
+        .map(|info| info.host)
+        .unwrap_or_else(|_| GitHost::Other(String::new()));
+
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(auth::remote_callbacks(&host));
+    fetch_options.download_tags(git2::AutotagOption::None);
+    if shallow {
+        fetch_options.depth(1);
+    }
+    let fetched = remote.fetch(&refspecs, Some(&mut fetch_options), None);
+
+    match lookup(repo) {
+        Ok(object) => Ok(object),
+        // A short commit id that is not a branch or tag cannot be fetched
+        Err(_) if is_abbreviated_id(rev) => Err(GitError::AbbreviatedCommit(rev.to_string())),
+        Err(_) => {
+            fetched.map_err(|e| auth::map_error(e, &url, &host, GitError::FetchError))?;
+            Err(GitError::NotFound(format!("revision `{}`", rev)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::Signature;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn commit_all(repo: &Git2Repository, message: &str) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let parents: Vec<_> = repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_parse_diff_spec() {
+        assert_eq!(
+            "main..feature".parse(),
+            Ok(DiffSpec::Range {
+                base: "main".to_string(),
+                head: "feature".to_string(),
+                merge_base: false,
+            })
+        );
+        assert_eq!(
+            "v1.0...".parse::<DiffSpec>().unwrap().to_string(),
+            "v1.0...HEAD"
+        );
+        assert_eq!("staged".parse(), Ok(DiffSpec::Staged));
+        assert!("main".parse::<DiffSpec>().is_err());
+        assert!("..main".parse::<DiffSpec>().is_err());
+    }
+
+    #[test]
+    fn test_changed_files() {
+        let dir = tempdir().unwrap();
+        let repo = Git2Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("kept.txt"), "unchanged\n").unwrap();
+        fs::write(dir.path().join("edited.txt"), "one\n").unwrap();
+        fs::write(dir.path().join("removed.txt"), "bye\n").unwrap();
+        commit_all(&repo, "base");
+        repo.tag_lightweight("base", &repo.revparse_single("HEAD").unwrap(), false)
+            .unwrap();
+
+        fs::write(dir.path().join("edited.txt"), "one\ntwo\n").unwrap();
+        fs::remove_file(dir.path().join("removed.txt")).unwrap();
+        commit_all(&repo, "head");
+
+        let root = dir.path().canonicalize().unwrap();
+        let spec = "base..HEAD".parse().unwrap();
+        let set = changed_files(dir.path(), &spec).unwrap();
+
+        let paths: Vec<_> = set.changes.keys().cloned().collect();
+        assert_eq!(
+            paths,
+            vec![root.join("edited.txt"), root.join("removed.txt")]
+        );
+
+        let edited = &set.changes[&root.join("edited.txt")];
+        assert_eq!(edited.status, ChangeStatus::Modified);
+        assert!(edited.patch.contains("+two"));
+        assert!(matches!(&edited.version, FileVersion::Blob { data, .. } if data == b"one\ntwo\n"));
+        assert!(matches!(
+            set.changes[&root.join("removed.txt")].version,
+            FileVersion::Deleted
+        ));
+        assert!(set.touches(&root));
+        assert!(!set.touches(&root.join("kept.txt")));
+
+        // Local changes: one staged edit, one untracked file
+        fs::write(dir.path().join("kept.txt"), "changed\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("kept.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+
+        let staged = changed_files(dir.path(), &DiffSpec::Staged).unwrap();
+        assert_eq!(
+            staged.changes.keys().cloned().collect::<Vec<_>>(),
+            vec![root.join("kept.txt")]
+        );
+
+        let worktree = changed_files(dir.path(), &DiffSpec::Worktree).unwrap();
+        assert_eq!(
+            worktree.changes.keys().cloned().collect::<Vec<_>>(),
+            vec![root.join("new.txt")]
+        );
+    }
+}
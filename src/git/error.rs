@@ -20,10 +20,18 @@ pub enum GitError {
     #[error("Failed to clone repository: {0}")]
     CloneError(git2::Error),
 
+    /// Credentials were missing or rejected by the remote
+    #[error("Authentication failed for {0}")]
+    AuthError(String),
+
     /// Error fetching from remote
     #[error("Failed to fetch from remote: {0}")]
     FetchError(git2::Error),
 
+    /// Abbreviated commit id that is not available locally
+    #[error("Cannot fetch abbreviated commit `{0}`: remotes only serve commits by their full 40- or 64-character id")]
+    AbbreviatedCommit(String),
+
     /// Git2 error (generic)
     #[error("Git error: {0}")]
     Git2Error(#[from] git2::Error),
@@ -2,7 +2,9 @@
  * Git repository handling functionality
  */
 
+mod auth;
 mod cache;
+mod diff;
 mod error;
 mod progress;
 mod repository;
@@ -11,6 +13,9 @@ mod url;
 // Re-export public items
 pub use cache::clean_cache;
 use clap::ValueEnum;
+pub use diff::{
+    changed_files, ChangeSet, ChangeStatus, DiffOutput, DiffSpec, FileChange, FileVersion,
+};
 pub use error::{GitError, GitResult};
 use indicatif::{ProgressBar, ProgressStyle};
 use progress::ProgressBarAdapter;
@@ -22,9 +27,10 @@ use std::io;
 use std::path::PathBuf;
 
 /// Policy for handling Git repository caching
-#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
 pub enum GitCachePolicy {
     /// Always pull latest changes for existing repositories (default)
+    #[default]
     AlwaysPull,
     /// Delete and re-clone existing repositories
     ForceClone,
@@ -32,12 +38,6 @@ pub enum GitCachePolicy {
     UseCache,
 }
 
-impl Default for GitCachePolicy {
-    fn default() -> Self {
-        Self::AlwaysPull
-    }
-}
-
 /// Clone or update a Git repository
 ///
 /// This function maintains compatibility with the original API
@@ -58,24 +58,28 @@ pub fn clone_repository<P: ProgressReporter>(
         match Repository::open(info.clone()) {
             Ok(mut repo) => {
                 if let Err(e) = repo.pull(progress_fn) {
-                    return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+                    return Err(io::Error::other(e.to_string()));
                 }
                 Ok(repo.path().clone())
             }
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+            Err(e) => Err(io::Error::other(e.to_string())),
         }
     } else {
         // Clone the repository
         match Repository::clone(info.clone(), progress_fn) {
             Ok(repo) => Ok(repo.path().clone()),
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, e.to_string())),
+            Err(e) => Err(io::Error::other(e.to_string())),
         }
     }
 }
 
 // Create a progress reporter adapter
+///
+/// `git_ref` selects a branch, tag or commit and takes precedence over a
+/// `#ref` suffix on the URL.
 pub fn process_path(
     path: &str,
+    git_ref: Option<&str>,
     git_cache_policy: GitCachePolicy,
     progress: Option<&ProgressBar>,
 ) -> GitResult<(PathBuf, Option<String>, Option<GitRepoInfo>)> {
@@ -85,7 +89,10 @@ pub fn process_path(
     }
 
     // Parse the Git URL
-    let repo_info = parse_git_url(path)?;
+    let mut repo_info = parse_git_url(path)?;
+    if let Some(git_ref) = git_ref {
+        repo_info = repo_info.with_explicit_ref(git_ref);
+    }
 
     // Use the provided progress bar or create a new one
     let progress_bar = match progress {
@@ -106,7 +113,7 @@ pub fn process_path(
     let repo_exists = Repository::exists(&repo_info);
 
     // Handle based on policy
-    match (git_cache_policy, repo_exists) {
+    let repo_path = match (git_cache_policy, repo_exists) {
         // Repository doesn't exist, always clone
         (_, false) => {
             progress_bar.set_prefix("🔄 Cloning");
@@ -132,7 +139,7 @@ pub fn process_path(
                     progress_bar.abandon_with_message(format!("Failed to clone repository: {}", e));
                 })?;
 
-            Ok((repo.path().clone(), Some(path.to_string()), Some(repo_info)))
+            repo.path().clone()
         }
 
         // Force clone even if exists
@@ -174,7 +181,7 @@ pub fn process_path(
                     ))
                 })?;
 
-            Ok((repo.path().clone(), Some(path.to_string()), Some(repo_info)))
+            repo.path().clone()
         }
 
         // Pull if exists
@@ -204,7 +211,7 @@ pub fn process_path(
                 repo_info.owner, repo_info.name
             ));
 
-            Ok((repo.path().clone(), Some(path.to_string()), Some(repo_info)))
+            repo.path().clone()
         }
 
         // Use cache without pulling
@@ -215,18 +222,30 @@ pub fn process_path(
                 repo_info.owner, repo_info.name
             ));
 
+            // A sparse checkout of another path is widened before it is scanned
+            Repository::open(repo_info.clone())
+                .and_then(|repo| repo.ensure_checkout())
+                .inspect_err(|e| {
+                    progress_bar
+                        .abandon_with_message(format!("Failed to use cached repository: {}", e));
+                })?;
+
             progress_bar.finish_with_message(format!(
                 "Using cached repository: {}/{}",
                 repo_info.owner, repo_info.name
             ));
 
-            Ok((
-                repo_info.cache_path.clone(),
-                Some(path.to_string()),
-                Some(repo_info),
-            ))
+            repo_info.cache_path.clone()
         }
-    }
+    };
+
+    // Scan only the subdirectory or file a tree/blob URL points at
+    let scan_path = match &repo_info.subpath {
+        Some(subpath) => repo_path.join(subpath),
+        None => repo_path,
+    };
+
+    Ok((scan_path, Some(path.to_string()), Some(repo_info)))
 }
 
 #[cfg(test)]
@@ -252,9 +271,15 @@ mod tests {
             host: GitHost::GitHub,
             owner: "username".to_string(),
             name: "repo".to_string(),
+            git_ref: None,
+            subpath: None,
             cache_path: PathBuf::from("/tmp/cache/github/username/repo"),
         };
 
         assert_eq!(info.to_string(), "GitHub/username/repo");
+        assert_eq!(
+            info.with_ref(Some("v1.0".to_string())).to_string(),
+            "GitHub/username/repo#v1.0"
+        );
     }
 }
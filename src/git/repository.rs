@@ -3,13 +3,19 @@
  */
 
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use git2::build::CheckoutBuilder;
 use git2::{FetchOptions, RemoteCallbacks, Repository as Git2Repository};
 
+use super::auth;
 use super::error::{GitError, GitResult};
 use super::progress::{GitProgress, ProgressReporter};
-use super::url::GitRepoInfo;
+use super::url::{is_abbreviated_id, is_object_id, GitHost, GitRepoInfo};
+
+/// File in the `.git` directory recording the only path a sparse checkout wrote
+const SPARSE_MARKER: &str = "dumpfs-sparse";
 
 /// Git repository with associated information
 pub struct Repository {
@@ -37,68 +43,46 @@ impl Repository {
         // Create cache directory if it doesn't exist
         fs::create_dir_all(&info.cache_path).map_err(GitError::IoError)?;
 
-        // Setup builder with progress reporting
+        // A specific ref is fetched on its own rather than through a branch clone
+        if info.git_ref.is_some() {
+            let repo = Git2Repository::init(&info.cache_path).map_err(GitError::CloneError)?;
+            repo.remote("origin", &info.url)
+                .map_err(GitError::CloneError)?;
+            let mut repo = Self { inner: repo, info };
+            repo.checkout_ref(progress)?;
+            return Ok(repo);
+        }
+
+        // Setup builder with credentials and progress reporting
         let mut builder = git2::build::RepoBuilder::new();
 
         // Configure fetch options with zero depth for shallow clone
         let mut fetch_options = FetchOptions::new();
         fetch_options.download_tags(git2::AutotagOption::None);
         fetch_options.depth(1);
-
-        if let Some(reporter) = progress {
-            let mut callbacks = RemoteCallbacks::new();
-            callbacks.transfer_progress(|stats| {
-                let progress = GitProgress {
-                    total_objects: stats.total_objects(),
-                    received_objects: stats.received_objects(),
-                    indexed_objects: stats.indexed_objects(),
-                    local_objects: stats.local_objects(),
-                    total_deltas: stats.total_deltas(),
-                    indexed_deltas: stats.indexed_deltas(),
-                    received_bytes: stats.received_bytes(),
-                };
-                reporter.report(&progress);
-                true
-            });
-
-            fetch_options.remote_callbacks(callbacks);
-        }
+        fetch_options.remote_callbacks(remote_callbacks(&info.host, progress));
 
         builder.fetch_options(fetch_options);
 
         // Clone the repository
         let repo = builder
             .clone(&info.url, &info.cache_path)
-            .map_err(GitError::CloneError)?;
+            .map_err(|e| auth::map_error(e, &info.url, &info.host, GitError::CloneError))?;
 
         Ok(Self { inner: repo, info })
     }
 
     /// Pull latest changes for an existing repository
     pub fn pull<P: ProgressReporter>(&mut self, progress: Option<&P>) -> GitResult<()> {
+        if self.info.git_ref.is_some() {
+            return self.checkout_ref(progress);
+        }
+
         // Set up fetch options with progress reporting
         let mut fetch_options = FetchOptions::new();
         fetch_options.download_tags(git2::AutotagOption::None);
         fetch_options.depth(0); // Set fetch depth to zero for minimal history
-
-        if let Some(reporter) = progress {
-            let mut callbacks = RemoteCallbacks::new();
-            callbacks.transfer_progress(|stats| {
-                let progress = GitProgress {
-                    total_objects: stats.total_objects(),
-                    received_objects: stats.received_objects(),
-                    indexed_objects: stats.indexed_objects(),
-                    local_objects: stats.local_objects(),
-                    total_deltas: stats.total_deltas(),
-                    indexed_deltas: stats.indexed_deltas(),
-                    received_bytes: stats.received_bytes(),
-                };
-                reporter.report(&progress);
-                true
-            });
-
-            fetch_options.remote_callbacks(callbacks);
-        }
+        fetch_options.remote_callbacks(remote_callbacks(&self.info.host, progress));
 
         // Fetch from remote
         let mut remote = self
@@ -108,7 +92,9 @@ impl Repository {
 
         remote
             .fetch(&["main", "master"], Some(&mut fetch_options), None)
-            .map_err(GitError::FetchError)?;
+            .map_err(|e| {
+                auth::map_error(e, &self.info.url, &self.info.host, GitError::FetchError)
+            })?;
 
         // Find remote branch to reset to
         let remote_branch = self
@@ -135,6 +121,118 @@ impl Repository {
         Ok(())
     }
 
+    /// Shallow-fetch the configured ref from `origin` and check it out (detached)
+    ///
+    /// The ref may name a branch, a tag or a commit, so every form is requested
+    /// and the first one that resolves wins.
+    fn checkout_ref<P: ProgressReporter>(&mut self, progress: Option<&P>) -> GitResult<()> {
+        let git_ref = self
+            .info
+            .git_ref
+            .clone()
+            .ok_or_else(|| GitError::NotFound("ref to check out".to_string()))?;
+
+        let mut refspecs = vec![
+            format!("+refs/heads/{0}:refs/remotes/origin/{0}", git_ref),
+            format!("+refs/tags/{0}:refs/tags/{0}", git_ref),
+        ];
+        if is_object_id(&git_ref) {
+            refspecs.push(git_ref.clone());
+        }
+
+        let mut remote = self
+            .inner
+            .find_remote("origin")
+            .map_err(GitError::FetchError)?;
+
+        // Fetch each form separately, since a refspec the remote lacks fails the whole fetch
+        let mut last_error = None;
+        for refspec in &refspecs {
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.download_tags(git2::AutotagOption::None);
+            fetch_options.depth(1);
+            fetch_options.remote_callbacks(remote_callbacks(&self.info.host, progress));
+
+            if let Err(e) = remote.fetch(&[refspec], Some(&mut fetch_options), None) {
+                last_error = Some(e);
+            }
+        }
+
+        // Resolve as a branch, then a tag, then a commit id
+        let commit = [
+            format!("refs/remotes/origin/{}", git_ref),
+            format!("refs/tags/{}", git_ref),
+            git_ref.clone(),
+        ]
+        .iter()
+        .find_map(|spec| {
+            self.inner
+                .revparse_single(spec)
+                .and_then(|obj| obj.peel_to_commit())
+                .ok()
+        })
+        .ok_or_else(|| match last_error {
+            _ if is_abbreviated_id(&git_ref) => GitError::AbbreviatedCommit(git_ref.clone()),
+            Some(e) => auth::map_error(e, &self.info.url, &self.info.host, GitError::FetchError),
+            None => GitError::NotFound(format!("ref `{}` in {}", git_ref, self.info.url)),
+        })?;
+
+        self.inner
+            .set_head_detached(commit.id())
+            .map_err(GitError::Git2Error)?;
+        // Only write the requested subpath to disk (a sparse checkout)
+        let mut checkout = CheckoutBuilder::new();
+        if let Some(subpath) = &self.info.subpath {
+            checkout.path(subpath);
+        }
+        self.inner
+            .reset(
+                commit.as_object(),
+                git2::ResetType::Hard,
+                Some(&mut checkout),
+            )
+            .map_err(GitError::Git2Error)?;
+
+        // Record a sparse checkout, so it is widened before being reused for other paths
+        let marker = self.inner.path().join(SPARSE_MARKER);
+        match &self.info.subpath {
+            Some(subpath) => fs::write(&marker, subpath.to_string_lossy().as_bytes()),
+            None => fs::remove_file(&marker).or_else(|e| match e.kind() {
+                io::ErrorKind::NotFound => Ok(()),
+                _ => Err(e),
+            }),
+        }
+        .map_err(GitError::IoError)?;
+
+        Ok(())
+    }
+
+    /// Make sure a cached checkout holds everything that is going to be scanned
+    ///
+    /// A sparse checkout is reused as it is for paths below the one it wrote,
+    /// and widened to the whole commit otherwise.
+    pub fn ensure_checkout(&self) -> GitResult<()> {
+        let marker = self.inner.path().join(SPARSE_MARKER);
+        let Ok(written) = fs::read_to_string(&marker) else {
+            return Ok(());
+        };
+        if let Some(subpath) = &self.info.subpath {
+            if subpath.starts_with(Path::new(&written)) {
+                return Ok(());
+            }
+        }
+
+        let head = self
+            .inner
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(GitError::Git2Error)?;
+        self.inner
+            .reset(head.as_object(), git2::ResetType::Hard, None)
+            .map_err(GitError::Git2Error)?;
+        fs::remove_file(marker).map_err(GitError::IoError)
+    }
+
     /// Get repository information
     pub fn info(&self) -> &GitRepoInfo {
         &self.info
@@ -146,6 +244,32 @@ impl Repository {
     }
 }
 
+/// Remote callbacks with credentials and optional transfer progress reporting
+fn remote_callbacks<'a, P: ProgressReporter>(
+    host: &GitHost,
+    progress: Option<&'a P>,
+) -> RemoteCallbacks<'a> {
+    let mut callbacks = auth::remote_callbacks(host);
+
+    if let Some(reporter) = progress {
+        callbacks.transfer_progress(move |stats| {
+            let progress = GitProgress {
+                total_objects: stats.total_objects(),
+                received_objects: stats.received_objects(),
+                indexed_objects: stats.indexed_objects(),
+                local_objects: stats.local_objects(),
+                total_deltas: stats.total_deltas(),
+                indexed_deltas: stats.indexed_deltas(),
+                received_bytes: stats.received_bytes(),
+            };
+            reporter.report(&progress);
+            true
+        });
+    }
+
+    callbacks
+}
+
 /// Repository operation builder for more flexible configuration
 pub struct RepositoryBuilder {
     /// Repository information
@@ -165,7 +289,7 @@ impl RepositoryBuilder {
 
     /// Configure with progress reporting
     pub fn with_progress<P: ProgressReporter + 'static>(mut self, reporter: P) -> Self {
-        let mut callbacks = RemoteCallbacks::new();
+        let mut callbacks = auth::remote_callbacks(&self.info.host);
         callbacks.transfer_progress(move |stats| {
             let progress = GitProgress {
                 total_objects: stats.total_objects(),
@@ -197,14 +321,19 @@ impl RepositoryBuilder {
         // Setup builder
         let mut builder = git2::build::RepoBuilder::new();
 
-        if let Some(fetch_options) = self.fetch_options {
-            builder.fetch_options(fetch_options);
-        }
+        let fetch_options = self.fetch_options.unwrap_or_else(|| {
+            let mut fetch_options = FetchOptions::new();
+            fetch_options.remote_callbacks(auth::remote_callbacks(&self.info.host));
+            fetch_options
+        });
+        builder.fetch_options(fetch_options);
 
         // Clone the repository
         let repo = builder
             .clone(&self.info.url, &self.info.cache_path)
-            .map_err(GitError::CloneError)?;
+            .map_err(|e| {
+                auth::map_error(e, &self.info.url, &self.info.host, GitError::CloneError)
+            })?;
 
         Ok(Repository {
             inner: repo,
@@ -217,3 +346,106 @@ impl RepositoryBuilder {
         Repository::open(self.info)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::git::GitHost;
+
+    struct NoProgress;
+
+    impl ProgressReporter for NoProgress {
+        fn report(&self, _progress: &GitProgress) {}
+    }
+
+    fn commit_file(repo: &Git2Repository, content: &str) -> git2::Oid {
+        let workdir = repo.workdir().unwrap();
+        fs::write(workdir.join("version.txt"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            content,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_clone_at_ref() {
+        let upstream_dir = tempdir().unwrap();
+        let upstream = Git2Repository::init(upstream_dir.path()).unwrap();
+        fs::write(upstream_dir.path().join("README.md"), "readme").unwrap();
+        let first = commit_file(&upstream, "one");
+        upstream
+            .tag_lightweight("v1", &upstream.find_object(first, None).unwrap(), false)
+            .unwrap();
+        commit_file(&upstream, "two");
+
+        let cache_dir = tempdir().unwrap();
+        let info = |git_ref: &str| GitRepoInfo {
+            url: format!("file://{}", upstream_dir.path().display()),
+            host: GitHost::Other("local".to_string()),
+            owner: "owner".to_string(),
+            name: "repo".to_string(),
+            git_ref: Some(git_ref.to_string()),
+            subpath: None,
+            cache_path: cache_dir.path().join(git_ref),
+        };
+
+        let tag = Repository::clone(info("v1"), None::<&NoProgress>).unwrap();
+        assert_eq!(
+            fs::read_to_string(tag.path().join("version.txt")).unwrap(),
+            "one"
+        );
+
+        // Sparse checkout of a single path
+        let mut sparse_info = info("v1");
+        sparse_info.subpath = Some(PathBuf::from("version.txt"));
+        sparse_info.cache_path = cache_dir.path().join("sparse");
+        let sparse = Repository::clone(sparse_info.clone(), None::<&NoProgress>).unwrap();
+        assert!(sparse.path().join("version.txt").exists());
+        assert!(!sparse.path().join("README.md").exists());
+
+        // Reusing it for the same path keeps it sparse, for the whole tree widens it
+        Repository::open(sparse_info.clone())
+            .unwrap()
+            .ensure_checkout()
+            .unwrap();
+        assert!(!sparse.path().join("README.md").exists());
+        sparse_info.subpath = None;
+        Repository::open(sparse_info)
+            .unwrap()
+            .ensure_checkout()
+            .unwrap();
+        assert!(sparse.path().join("README.md").exists());
+
+        let sha = first.to_string();
+        let commit = Repository::clone(info(&sha), None::<&NoProgress>).unwrap();
+        assert_eq!(
+            fs::read_to_string(commit.path().join("version.txt")).unwrap(),
+            "one"
+        );
+
+        let branch_name = upstream.head().unwrap().shorthand().unwrap().to_string();
+        let mut branch = Repository::clone(info(&branch_name), None::<&NoProgress>).unwrap();
+        branch.pull(None::<&NoProgress>).unwrap();
+        assert_eq!(
+            fs::read_to_string(branch.path().join("version.txt")).unwrap(),
+            "two"
+        );
+    }
+}
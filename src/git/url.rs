@@ -2,7 +2,7 @@
  * Git URL parsing and handling
  */
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use once_cell::sync::Lazy;
@@ -14,7 +14,7 @@ use super::error::{GitError, GitResult};
 // Statically compiled regexes for better performance
 static HTTP_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(
-        r"^https?://(?:www\.)?(?:github\.com|gitlab\.com|bitbucket\.org|.*)/[^/]+/[^/]+(?:\.git)?$",
+        r"^https?://(?:www\.)?(?:github\.com|gitlab\.com|bitbucket\.org|.*)/[^/]+/[^/]+(?:\.git)?(?:/.*)?$",
     )
     .expect("HTTP URL regex pattern should be valid")
 });
@@ -64,13 +64,44 @@ pub struct GitRepoInfo {
     pub owner: String,
     /// Repository name
     pub name: String,
+    /// Branch, tag or commit to check out (default branch if None)
+    pub git_ref: Option<String>,
+    /// Subdirectory or file within the repository to scan (whole repository if None)
+    pub subpath: Option<PathBuf>,
     /// Local cache path
     pub cache_path: PathBuf,
 }
 
+impl GitRepoInfo {
+    /// Target a specific branch, tag or commit, with its own cache directory
+    pub fn with_ref(mut self, git_ref: Option<String>) -> Self {
+        self.cache_path =
+            get_ref_cache_path(&self.host, &self.owner, &self.name, git_ref.as_deref());
+        self.git_ref = git_ref;
+        self
+    }
+
+    /// Apply a ref given separately from the URL (`--ref`)
+    ///
+    /// Tree and blob links read their ref as one path segment, so a branch
+    /// like `feature/login` claims its remaining segments back from the subpath.
+    pub fn with_explicit_ref(mut self, git_ref: &str) -> Self {
+        if let (Some(url_ref), Some(subpath)) = (&self.git_ref, &self.subpath) {
+            if let Ok(rest) = Path::new(url_ref).join(subpath).strip_prefix(git_ref) {
+                self.subpath = (rest != Path::new("")).then(|| rest.to_path_buf());
+            }
+        }
+        self.with_ref(Some(git_ref.to_string()))
+    }
+}
+
 impl std::fmt::Display for GitRepoInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}/{}/{}", self.host, self.owner, self.name)
+        write!(f, "{}/{}/{}", self.host, self.owner, self.name)?;
+        if let Some(git_ref) = &self.git_ref {
+            write!(f, "#{}", git_ref)?;
+        }
+        Ok(())
     }
 }
 
@@ -78,6 +109,13 @@ impl FromStr for GitRepoInfo {
     type Err = GitError;
 
     fn from_str(url: &str) -> Result<Self, Self::Err> {
+        // Split off a `#ref` suffix selecting a branch, tag or commit
+        let (url, git_ref) = match url.split_once('#') {
+            Some((_, "")) => return Err(GitError::InvalidUrl(url.to_string())),
+            Some((url, git_ref)) => (url, Some(git_ref.to_string())),
+            None => (url, None),
+        };
+
         // Check if the URL is valid
         if !HTTP_REGEX.is_match(url) && !SSH_REGEX.is_match(url) {
             return Err(GitError::InvalidUrl(url.to_string()));
@@ -105,6 +143,7 @@ impl FromStr for GitRepoInfo {
 
                 let owner = path_segments[0].to_string();
                 let mut name = path_segments[1].to_string();
+                let (tree_ref, subpath) = parse_web_path(url, &path_segments[2..])?;
 
                 // Remove .git suffix if present
                 if name.ends_with(".git") {
@@ -118,15 +157,25 @@ impl FromStr for GitRepoInfo {
                     _ => GitHost::Other(host_str.to_string()),
                 };
 
-                let cache_path = get_cache_path(&host, &owner, &name);
+                // Clone from the repository root, not the web page URL
+                let mut clone_url = parsed_url.clone();
+                clone_url.set_path(&format!("{}/{}", owner, path_segments[1]));
+                clone_url.set_query(None);
 
                 return Ok(GitRepoInfo {
-                    url: url.to_string(),
+                    url: if subpath.is_some() || tree_ref.is_some() {
+                        clone_url.to_string()
+                    } else {
+                        url.to_string()
+                    },
                     host,
                     owner,
                     name,
-                    cache_path,
-                });
+                    git_ref: None,
+                    subpath,
+                    cache_path: PathBuf::new(),
+                }
+                .with_ref(git_ref.or(tree_ref)));
             }
         }
 
@@ -152,15 +201,16 @@ impl FromStr for GitRepoInfo {
                         _ => GitHost::Other(host_str.to_string()),
                     };
 
-                    let cache_path = get_cache_path(&host, &owner, &name);
-
                     return Ok(GitRepoInfo {
                         url: url.to_string(),
                         host,
                         owner,
                         name,
-                        cache_path,
-                    });
+                        git_ref: None,
+                        subpath: None,
+                        cache_path: PathBuf::new(),
+                    }
+                    .with_ref(git_ref));
                 }
             }
         }
@@ -169,6 +219,31 @@ impl FromStr for GitRepoInfo {
     }
 }
 
+/// Parse the part of a web URL after `owner/repo` into a ref and a subpath
+///
+/// Understands GitHub's `tree/<ref>/<path>` and `blob/<ref>/<path>`, GitLab's
+/// `-/tree/<ref>/<path>` and `-/blob/<ref>/<path>`, and Bitbucket's
+/// `src/<ref>/<path>`. The ref is taken to be a single path segment.
+fn parse_web_path(url: &str, segments: &[&str]) -> GitResult<(Option<String>, Option<PathBuf>)> {
+    let segments: Vec<&str> = segments.iter().copied().filter(|s| !s.is_empty()).collect();
+
+    let (git_ref, path) = match segments.as_slice() {
+        [] => return Ok((None, None)),
+        ["-", "tree" | "blob", git_ref, path @ ..]
+        | ["tree" | "blob", git_ref, path @ ..]
+        | ["src", git_ref, path @ ..] => (git_ref, path),
+        _ => {
+            return Err(GitError::InvalidUrl(format!(
+                "Unsupported repository page in URL: {}",
+                url
+            )))
+        }
+    };
+
+    let subpath = (!path.is_empty()).then(|| path.iter().collect::<PathBuf>());
+    Ok((Some(git_ref.to_string()), subpath))
+}
+
 /// Check if a path is a Git repository URL
 pub fn is_git_url(path: &str) -> bool {
     path.parse::<GitRepoInfo>().is_ok()
@@ -179,6 +254,20 @@ pub fn parse_git_url(url: &str) -> GitResult<GitRepoInfo> {
     url.parse()
 }
 
+/// Check if a ref is a full SHA-1 or SHA-256 object id
+///
+/// Remotes only serve commits by their full id, so only these can be fetched directly.
+pub fn is_object_id(git_ref: &str) -> bool {
+    matches!(git_ref.len(), 40 | 64) && git_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Check if a ref looks like an abbreviated commit id, which can only be resolved locally
+pub fn is_abbreviated_id(git_ref: &str) -> bool {
+    (7..64).contains(&git_ref.len())
+        && !is_object_id(git_ref)
+        && git_ref.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 /// Get the cache directory path for a repository
 pub fn get_cache_path(host: &GitHost, owner: &str, name: &str) -> PathBuf {
     let mut cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("~/.cache")); // Safe unwrap with fallback
@@ -192,6 +281,26 @@ pub fn get_cache_path(host: &GitHost, owner: &str, name: &str) -> PathBuf {
     }
 }
 
+/// Get the cache directory path for a repository checked out at a ref
+///
+/// Each ref gets its own checkout next to the default one (`repo@v1.2`), so
+/// switching between refs does not clobber other cached checkouts.
+pub fn get_ref_cache_path(
+    host: &GitHost,
+    owner: &str,
+    name: &str,
+    git_ref: Option<&str>,
+) -> PathBuf {
+    match git_ref {
+        Some(git_ref) => {
+            let encoded: String =
+                url::form_urlencoded::byte_serialize(git_ref.as_bytes()).collect();
+            get_cache_path(host, owner, &format!("{}@{}", name, encoded))
+        }
+        None => get_cache_path(host, owner, name),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -199,64 +308,98 @@ mod tests {
     #[test]
     fn test_is_git_url() {
         // Test GitHub URLs
-        assert!(is_git_url(&"https://github.com/username/repo".to_string()));
-        assert!(is_git_url(
-            &"https://github.com/username/repo.git".to_string()
-        ));
-        assert!(is_git_url(&"git@github.com:username/repo.git".to_string()));
+        assert!(is_git_url("https://github.com/username/repo"));
+        assert!(is_git_url("https://github.com/username/repo.git"));
+        assert!(is_git_url("git@github.com:username/repo.git"));
 
         // Test GitLab URLs
-        assert!(is_git_url(&"https://gitlab.com/username/repo".to_string()));
-        assert!(is_git_url(
-            &"https://gitlab.com/username/repo.git".to_string()
-        ));
-        assert!(is_git_url(&"git@gitlab.com:username/repo.git".to_string()));
+        assert!(is_git_url("https://gitlab.com/username/repo"));
+        assert!(is_git_url("https://gitlab.com/username/repo.git"));
+        assert!(is_git_url("git@gitlab.com:username/repo.git"));
 
         // Test Bitbucket URLs
-        assert!(is_git_url(
-            &"https://bitbucket.org/username/repo".to_string()
-        ));
-        assert!(is_git_url(
-            &"https://bitbucket.org/username/repo.git".to_string()
-        ));
-        assert!(is_git_url(
-            &"git@bitbucket.org:username/repo.git".to_string()
-        ));
+        assert!(is_git_url("https://bitbucket.org/username/repo"));
+        assert!(is_git_url("https://bitbucket.org/username/repo.git"));
+        assert!(is_git_url("git@bitbucket.org:username/repo.git"));
 
         // Test custom Git host URLs
-        assert!(is_git_url(
-            &"https://git.example.com/username/repo".to_string()
-        ));
-        assert!(is_git_url(
-            &"https://git.example.com/username/repo.git".to_string()
-        ));
-        assert!(is_git_url(
-            &"git@git.example.com:username/repo.git".to_string()
-        ));
+        assert!(is_git_url("https://git.example.com/username/repo"));
+        assert!(is_git_url("https://git.example.com/username/repo.git"));
+        assert!(is_git_url("git@git.example.com:username/repo.git"));
 
         // Test invalid URLs
-        assert!(!is_git_url(&"https://github.com".to_string()));
-        assert!(!is_git_url(&"https://github.com/username".to_string()));
-        assert!(!is_git_url(&"git@github.com".to_string()));
-        assert!(!is_git_url(&"/path/to/local/directory".to_string()));
-        assert!(!is_git_url(&"username/repo".to_string()));
+        assert!(!is_git_url("https://github.com"));
+        assert!(!is_git_url("https://github.com/username"));
+        assert!(!is_git_url("git@github.com"));
+        assert!(!is_git_url("/path/to/local/directory"));
+        assert!(!is_git_url("username/repo"));
     }
 
     #[test]
     fn test_parse_git_url() {
         // Test GitHub HTTPS URL
-        let repo = parse_git_url(&"https://github.com/username/repo".to_string()).unwrap();
+        let repo = parse_git_url("https://github.com/username/repo").unwrap();
         assert_eq!(repo.url, "https://github.com/username/repo");
         assert!(matches!(repo.host, GitHost::GitHub));
         assert_eq!(repo.owner, "username");
         assert_eq!(repo.name, "repo");
 
         // Test GitHub SSH URL
-        let repo = parse_git_url(&"git@github.com:username/repo.git".to_string()).unwrap();
+        let repo = parse_git_url("git@github.com:username/repo.git").unwrap();
         assert_eq!(repo.url, "git@github.com:username/repo.git");
         assert!(matches!(repo.host, GitHost::GitHub));
         assert_eq!(repo.owner, "username");
         assert_eq!(repo.name, "repo");
+        assert_eq!(repo.git_ref, None);
+
+        // Test URLs with a ref suffix
+        let repo = parse_git_url("https://github.com/username/repo#release/1.2").unwrap();
+        assert_eq!(repo.url, "https://github.com/username/repo");
+        assert_eq!(repo.name, "repo");
+        assert_eq!(repo.git_ref.as_deref(), Some("release/1.2"));
+        assert!(repo
+            .cache_path
+            .ends_with("github/username/repo@release%2F1.2"));
+
+        let repo = parse_git_url("git@github.com:username/repo.git#v1.0").unwrap();
+        assert_eq!(repo.url, "git@github.com:username/repo.git");
+        assert_eq!(repo.name, "repo");
+        assert_eq!(repo.git_ref.as_deref(), Some("v1.0"));
+        assert!(!is_git_url("https://github.com/username/repo#"));
+
+        // Test tree and blob URLs pointing into the repository
+        let repo = parse_git_url("https://github.com/username/repo/tree/v1.2/crates/core").unwrap();
+        assert_eq!(repo.url, "https://github.com/username/repo");
+        assert_eq!(repo.git_ref.as_deref(), Some("v1.2"));
+        assert_eq!(repo.subpath, Some(PathBuf::from("crates/core")));
+
+        let repo = parse_git_url("https://github.com/username/repo/blob/main/src/lib.rs").unwrap();
+        assert_eq!(repo.git_ref.as_deref(), Some("main"));
+        assert_eq!(repo.subpath, Some(PathBuf::from("src/lib.rs")));
+
+        let repo = parse_git_url("https://gitlab.com/username/repo/-/tree/develop/docs/").unwrap();
+        assert_eq!(repo.url, "https://gitlab.com/username/repo");
+        assert_eq!(repo.git_ref.as_deref(), Some("develop"));
+        assert_eq!(repo.subpath, Some(PathBuf::from("docs")));
+
+        let repo = parse_git_url("https://bitbucket.org/username/repo/src/abc1234/lib").unwrap();
+        assert_eq!(repo.git_ref.as_deref(), Some("abc1234"));
+        assert_eq!(repo.subpath, Some(PathBuf::from("lib")));
+
+        // A tree URL without a path targets the whole repository at that ref
+        let repo = parse_git_url("https://github.com/username/repo/tree/v2").unwrap();
+        assert_eq!(repo.git_ref.as_deref(), Some("v2"));
+        assert_eq!(repo.subpath, None);
+
+        // An explicit ref takes back the segments of a branch name containing `/`
+        let repo = parse_git_url("https://github.com/username/repo/tree/feature/login/src")
+            .unwrap()
+            .with_explicit_ref("feature/login");
+        assert_eq!(repo.git_ref.as_deref(), Some("feature/login"));
+        assert_eq!(repo.subpath, Some(PathBuf::from("src")));
+
+        // Other repository pages are rejected
+        assert!(!is_git_url("https://github.com/username/repo/issues/1"));
 
         // Test custom host cache path
         let host = GitHost::Other("example.com".to_string());
@@ -264,10 +407,28 @@ mod tests {
         let name = "repo";
         let cache_path = get_cache_path(&host, owner, name);
         assert!(cache_path.ends_with(
-            &std::path::Path::new("git")
+            std::path::Path::new("git")
                 .join("example.com")
                 .join("username")
                 .join("repo")
         ));
     }
+
+    #[test]
+    fn test_object_ids() {
+        let sha1 = "3f2a9c1d7e5b4a8f0c6d2e9b1a7f3c5d8e0b2a4f";
+        assert!(is_object_id(sha1));
+        assert!(is_object_id(&sha1.repeat(2)[..64]));
+        assert!(!is_abbreviated_id(sha1));
+
+        // Short ids can only be resolved locally
+        assert!(!is_object_id("3f2a9c1"));
+        assert!(is_abbreviated_id("3f2a9c1"));
+        assert!(is_abbreviated_id(&sha1[..12]));
+
+        // Branch names and ids too short to be unique are neither
+        assert!(!is_abbreviated_id("main"));
+        assert!(!is_abbreviated_id("abc"));
+        assert!(!is_object_id("feature/login"));
+    }
 }
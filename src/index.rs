@@ -0,0 +1,317 @@
+/*!
+ * Persistent scan index for incremental scans
+ *
+ * The index remembers, for every file of the last scan, its detected type,
+ * encoding and statistics, and the summary of binary files. Entries are keyed
+ * by absolute path and only reused when the file's size and modification time
+ * are unchanged, so the type of unchanged files is not detected, their content
+ * is not tokenized again and binary files are not hashed again.
+ * Content itself is never stored: it is read from the file on every scan, so
+ * no copy of it (or of the secrets in it) is left behind in the cache.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::pattern::split_line_range;
+use crate::report::FileReportInfo;
+use crate::types::{BinarySummary, FileType, Metadata};
+
+/// Version of the on-disk format; bump when entries change shape or meaning
+const INDEX_VERSION: u32 = 5;
+
+/// Cached result of processing one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Size in bytes when the file was processed
+    pub size: u64,
+    /// Modification time when the file was processed
+    pub modified: SystemTime,
+    /// Detected file type
+    pub file_type: FileType,
+    /// Line, character, token and redaction counts (text files only)
+    pub info: FileReportInfo,
+    /// Encoding the content was converted from (None for UTF-8)
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Summary of a binary file (None for text files or if summaries are disabled)
+    #[serde(default)]
+    pub summary: Option<BinarySummary>,
+}
+
+impl IndexEntry {
+    /// Whether the entry still describes a file with this metadata
+    fn matches(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.size && self.modified == metadata.modified
+    }
+}
+
+/// On-disk representation of the index
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    fingerprint: String,
+    entries: HashMap<PathBuf, IndexEntry>,
+}
+
+/// Index of previously scanned files
+#[derive(Debug)]
+pub struct ScanIndex {
+    /// Location of the index file
+    path: PathBuf,
+    /// Hash of the settings that affect entries
+    fingerprint: String,
+    /// Entries loaded from the previous scan
+    previous: HashMap<PathBuf, IndexEntry>,
+    /// Entries seen during this scan, saved for the next one
+    current: Mutex<HashMap<PathBuf, IndexEntry>>,
+    /// Whether an entry was added or replaced during this scan
+    changed: AtomicBool,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ScanIndex {
+    /// Open the index at `path`, discarding it if it was built with different settings
+    pub fn open(path: PathBuf, fingerprint: String) -> Self {
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<IndexFile>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION && index.fingerprint == fingerprint)
+            .map(|index| index.entries)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            fingerprint,
+            previous,
+            current: Mutex::new(HashMap::new()),
+            changed: AtomicBool::new(false),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Open the index for the project in `config.target_dir`
+    pub fn for_config(config: &Config) -> Option<Self> {
+        let path = index_path(&config.target_dir)?;
+        Some(Self::open(path, fingerprint(config)))
+    }
+
+    /// Look up the entry for a file, if it is unchanged since the last scan
+    pub fn get(&self, path: &Path, metadata: &Metadata) -> Option<IndexEntry> {
+        let entry = self
+            .previous
+            .get(path)
+            .filter(|entry| entry.matches(metadata))
+            .cloned();
+
+        match &entry {
+            Some(entry) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                if let Ok(mut current) = self.current.lock() {
+                    current.insert(path.to_path_buf(), entry.clone());
+                }
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        entry
+    }
+
+    /// Record the result of processing a file
+    pub fn insert(&self, path: &Path, entry: IndexEntry) {
+        self.changed.store(true, Ordering::Relaxed);
+        if let Ok(mut current) = self.current.lock() {
+            current.insert(path.to_path_buf(), entry);
+        }
+    }
+
+    /// Number of files reused from and missing in the index
+    pub fn stats(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Write the entries seen during this scan to disk
+    ///
+    /// Files that were not seen (deleted or ignored) are dropped from the index.
+    /// Nothing is written if every file was unchanged and none were dropped.
+    pub fn save(&self) -> io::Result<()> {
+        let entries = self
+            .current
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire lock on scan index"))?
+            .clone();
+        // Entries not inserted were all taken from the previous scan
+        if !self.changed.load(Ordering::Relaxed) && entries.len() == self.previous.len() {
+            return Ok(());
+        }
+        let index = IndexFile {
+            version: INDEX_VERSION,
+            fingerprint: self.fingerprint.clone(),
+            entries,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string(&index)?)
+    }
+}
+
+/// Settings that change what is stored for a file
+#[derive(Serialize)]
+struct FingerprintKey<'a> {
+    model: Option<String>,
+    redact: bool,
+    redact_patterns: &'a [String],
+    max_file_size: Option<u64>,
+    max_detect_size: Option<u64>,
+    truncate: Option<String>,
+    fallback_encoding: Option<&'a str>,
+    notebook_output_lines: Option<usize>,
+    binary_summary: bool,
+    binary_excerpt: Option<&'static str>,
+    binary_excerpt_size: Option<u64>,
+    line_ranges: Vec<&'a str>,
+}
+
+/// Hash of the settings that change what is stored for a file
+///
+/// SHA-256 over the serialized settings, so the hash is the same across
+/// builds and toolchains.
+pub fn fingerprint(config: &Config) -> String {
+    let key = FingerprintKey {
+        model: config.model.map(|model| format!("{:?}", model)),
+        redact: config.redact,
+        redact_patterns: &config.redact_patterns,
+        max_file_size: config.max_file_size,
+        max_detect_size: config.max_detect_size,
+        truncate: config.truncate.map(|t| t.to_string()),
+        fallback_encoding: config.fallback_encoding.as_deref(),
+        notebook_output_lines: config.notebook_output_lines,
+        binary_summary: config.binary_summary,
+        binary_excerpt: config.binary_excerpt.map(|e| e.name()),
+        binary_excerpt_size: config.binary_excerpt_size,
+        line_ranges: config
+            .include_patterns
+            .iter()
+            .filter(|spec| matches!(split_line_range(spec), Ok((_, Some(_)))))
+            .map(String::as_str)
+            .collect(),
+    };
+    let serialized = serde_json::to_vec(&key).unwrap_or_default();
+    format!("{:x}", Sha256::digest(serialized))
+}
+
+/// Path of the scan index for a project directory (`~/.cache/dumpfs/<path>.scan_index.json`)
+pub fn index_path(project_dir: &Path) -> Option<PathBuf> {
+    let cache_dir = dirs::home_dir()?.join(".cache").join("dumpfs");
+    let canonical_path = fs::canonicalize(project_dir).ok()?;
+
+    let sanitized_path = canonical_path.to_string_lossy().replace(
+        |c: char| !c.is_alphanumeric() && c != '_' && c != '-' && c != '.',
+        "_",
+    );
+
+    Some(cache_dir.join(format!("{}.scan_index.json", sanitized_path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn metadata(size: u64, modified: SystemTime) -> Metadata {
+        Metadata {
+            size,
+            modified,
+            permissions: "644".to_string(),
+            encoding: None,
+        }
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("index.json");
+        let file = dir.path().join("a.txt");
+        let modified = SystemTime::now();
+
+        let index = ScanIndex::open(path.clone(), "1".to_string());
+        assert!(index.get(&file, &metadata(5, modified)).is_none());
+        index.insert(
+            &file,
+            IndexEntry {
+                size: 5,
+                modified,
+                file_type: FileType::TextFile,
+                info: FileReportInfo {
+                    lines: 1,
+                    chars: 6,
+                    ..Default::default()
+                },
+                encoding: Some("UTF-16LE".to_string()),
+                summary: None,
+            },
+        );
+        index.save().unwrap();
+
+        let index = ScanIndex::open(path.clone(), "1".to_string());
+        let entry = index.get(&file, &metadata(5, modified)).unwrap();
+        assert_eq!(entry.encoding.as_deref(), Some("UTF-16LE"));
+        assert_eq!(entry.info.lines, 1);
+
+        // Saving an unchanged index leaves the file alone
+        fs::write(&path, "untouched").unwrap();
+        index.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "untouched");
+        fs::remove_file(&path).unwrap();
+        index.insert(&file, entry);
+        index.save().unwrap();
+
+        // A changed size or modification time is a miss
+        assert!(index.get(&file, &metadata(6, modified)).is_none());
+        assert!(index
+            .get(&file, &metadata(5, modified + Duration::from_secs(1)))
+            .is_none());
+        assert_eq!(index.stats(), (1, 2));
+
+        // Different settings discard the index
+        let index = ScanIndex::open(path, "2".to_string());
+        assert!(index.get(&file, &metadata(5, modified)).is_none());
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        let config = Config::default();
+        // A fixed hash of the serialized settings, unlike `DefaultHasher`
+        assert_eq!(
+            fingerprint(&config),
+            "b90c8eb09ec6479cdf6a4377c9bcfada47eac7bb4f3890fe840180b0eafba3f3"
+        );
+
+        let redacted = Config {
+            redact: true,
+            ..Default::default()
+        };
+        assert_ne!(fingerprint(&config), fingerprint(&redacted));
+    }
+}
@@ -5,29 +5,56 @@
  * for use as context for Large Language Models.
  */
 
+pub mod archive;
+pub mod binary;
+pub mod budget;
 pub mod clipboard;
 pub mod config;
+pub mod encoding;
 pub mod error;
 pub mod git;
+pub mod index;
+pub mod mcp;
+pub mod notebook;
+pub mod order;
+pub mod pattern;
+pub mod redact;
 pub mod report;
 pub mod scanner;
+pub mod serve;
+pub mod split;
+pub mod stream;
 pub mod tokenizer;
+pub mod truncate;
 pub mod types;
 pub mod utils;
+pub mod watch;
 pub mod writer;
 
 #[cfg(test)]
 mod tests;
 
 // Re-export main components for easier access
+pub use archive::ArchiveKind;
+pub use binary::{BinaryExcerpt, Summarizer};
+pub use budget::{BudgetOrder, BudgetSummary, TokenBudget};
 pub use clipboard::{copy_to_clipboard, ClipboardError};
 pub use config::Config;
 pub use error::{DumpFsError, Result, ResultExt};
+pub use index::ScanIndex;
+pub use order::SortOrder;
+pub use pattern::PatternSet;
+pub use redact::Redactor;
 pub use report::{FileReportInfo, ReportFormat, Reporter, ScanReport};
 pub use scanner::Scanner;
-pub use types::{BinaryNode, DirectoryNode, FileNode, FileType, Metadata, Node, SymlinkNode};
+pub use split::{OutputPart, SplitLimit};
+pub use stream::{NodeVisitor, TreeBuilder};
+pub use truncate::Truncation;
+pub use types::{
+    BinaryNode, BinarySummary, DirectoryNode, FileNode, FileType, Metadata, Node, SymlinkNode,
+};
 pub use utils::{count_files, format_file_size};
-pub use writer::FsWriterFormatter;
+pub use writer::{FsWriterFormatter, StreamWriter};
 
 // No process_path export needed
 
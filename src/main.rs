@@ -2,20 +2,27 @@
  * Command-line interface for DumpFS
  */
 
-use std::io::{self, Write};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 
+use dumpfs::budget::{self, BudgetSummary};
 use dumpfs::clipboard;
 use dumpfs::error::{DumpFsError, Result};
+use dumpfs::mcp::McpServer;
+use dumpfs::serve::Server;
+use dumpfs::split::{self, OutputPart};
+use dumpfs::types::DirectoryNode;
+use dumpfs::watch;
 
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, CompleteEnv, Shell};
 use indicatif::{ProgressBar, ProgressStyle};
 use rayon::ThreadPoolBuilder;
 
-use dumpfs::config::{Args, Config};
+use dumpfs::config::{Args, Command, Config};
 use dumpfs::git;
 use dumpfs::report::{ReportFormat, Reporter, ScanReport};
 use dumpfs::scanner::Scanner;
@@ -64,6 +71,17 @@ fn main() -> Result<()> {
         }
     }
 
+    // Serve scans over HTTP instead of running a single scan
+    if let Some(Command::Serve(serve)) = &args.command {
+        let server = Server::bind(&format!("{}:{}", serve.host, serve.port))?;
+        match server.local_addr() {
+            Some(addr) => eprintln!("🌐 Listening for scan requests on http://{}", addr),
+            None => eprintln!("🌐 Listening for scan requests"),
+        }
+        server.run();
+        return Ok(());
+    }
+
     // Create progress bar with advanced Unicode styling
     let progress = ProgressBar::new(0);
     progress.set_style(
@@ -74,13 +92,24 @@ fn main() -> Result<()> {
     progress.enable_steady_tick(std::time::Duration::from_millis(100));
     progress.set_prefix("📊 Setup");
 
-    // Create initial configuration
-    let mut config = Config::from_args(args.clone());
+    // Create initial configuration, layering CLI flags over .dumpfs.toml files.
+    // The project file of a Git repository can only be read once it is cloned.
+    let load_config = |loaded: Result<Config>| {
+        loaded.inspect_err(|e| {
+            progress.abandon_with_message(format!("Error loading configuration: {}", e));
+        })
+    };
+    let target_dir = Path::new(&args.directory_path);
+    let mut config = load_config(match git::is_git_url(&args.directory_path) {
+        true => Config::load_user(args.clone(), target_dir),
+        false => Config::load(args.clone(), target_dir),
+    })?;
 
     // Process path (either local directory or git repository URL)
     progress.set_message(format!("Processing path: {}", args.directory_path));
     let (processed_path, repo_url, git_repo) = match git::process_path(
         &args.directory_path,
+        config.git_ref.as_deref(),
         config.git_cache_policy,
         Some(&progress),
     ) {
@@ -92,6 +121,11 @@ fn main() -> Result<()> {
         }
     };
 
+    // Add the settings of the cloned repository's own .dumpfs.toml
+    if git_repo.is_some() {
+        config = load_config(Config::load_cloned(args.clone(), &processed_path))?;
+    }
+
     // Update config with processed path and repo info
     config.target_dir = processed_path;
     config.repo_url = repo_url;
@@ -100,7 +134,8 @@ fn main() -> Result<()> {
     // Adjust output file location for git repositories
     if let Some(repo) = &config.git_repo {
         // Check if output file is a relative path with no directory component
-        if !config.output_file.is_absolute()
+        if !config.writes_to_stdout()
+            && !config.output_file.is_absolute()
             && (config.output_file.parent().is_none()
                 || config
                     .output_file
@@ -116,6 +151,15 @@ fn main() -> Result<()> {
     // Validate configuration
     config.validate()?;
 
+    // Collect changed files in diff mode
+    if let Some(spec) = &config.diff {
+        progress.set_message(format!("🔀 Collecting changes: {}", spec));
+        let changes = git::changed_files(&config.target_dir, spec).inspect_err(|e| {
+            progress.abandon_with_message(format!("Error collecting changes: {}", e));
+        })?;
+        config.changes = Some(Arc::new(changes));
+    }
+
     // Configure thread pool
     if let Err(e) = ThreadPoolBuilder::new()
         .num_threads(config.num_threads)
@@ -124,6 +168,17 @@ fn main() -> Result<()> {
         eprintln!("Warning: Failed to set thread pool size: {}", e);
     }
 
+    // Answer MCP requests on stdin/stdout instead of writing a dump
+    if let Some(Command::Mcp) = &args.command {
+        progress.finish_and_clear();
+        eprintln!(
+            "🤖 Serving {} over MCP on stdio",
+            config.target_dir.display()
+        );
+        McpServer::new(config).run(io::stdin().lock(), io::stdout().lock())?;
+        return Ok(());
+    }
+
     progress.set_message(format!(
         "📂 Scanning directory: {}",
         config.target_dir.display()
@@ -159,11 +214,16 @@ fn main() -> Result<()> {
     // Start timing both scan and write operations
     let start_time = Instant::now();
 
-    // Scan directory
-    let root_node = scanner.scan()?;
-
-    // Write XML output
-    config.format.write(config.clone(), &root_node)?;
+    // Scan directory and write output, packed into the token budget and split into
+    // parts if requested, or written as files are scanned in streaming mode
+    let (root_node, budget_summary, parts, clipboard) = if config.stream {
+        let clipboard = stream_output(&config, &scanner)?;
+        (None, None, Vec::new(), clipboard)
+    } else {
+        let root_node = scanner.scan()?;
+        let (budget_summary, parts, clipboard) = write_output(&config, &root_node)?;
+        (Some(root_node), budget_summary, parts, clipboard)
+    };
 
     // Calculate total duration (scan + write)
     let total_duration = start_time.elapsed();
@@ -175,40 +235,198 @@ fn main() -> Result<()> {
     let scanner_stats = scanner.get_statistics()?;
 
     // Prepare the scan report
-    let scan_report = ScanReport {
-        output_file: config.output_file.display().to_string(),
-        duration: total_duration,
-        files_processed: scanner_stats.files_processed,
-        total_lines: scanner_stats.total_lines,
-        total_chars: scanner_stats.total_chars,
-        total_tokens: scanner_stats.total_tokens,
-        file_details: scanner_stats.file_details,
-        token_cache_hits: scanner_stats.token_cache_hits,
-        token_cache_misses: scanner_stats.token_cache_misses,
+    let scan_report = ScanReport::new(
+        config.output_file.display().to_string(),
+        total_duration,
+        scanner_stats,
+        budget_summary,
+        parts.clone(),
+    )
+    .with_context_window(config.model.map(|model| model.context_window()));
+
+    // Print the report, as tables when it also goes to a report file
+    let console_format = match config.report {
+        Some(_) => ReportFormat::ConsoleTable,
+        None => config.report_format.unwrap_or_default(),
     };
-
-    // Create a reporter and print the report
-    let reporter = Reporter::new(ReportFormat::ConsoleTable);
+    let reporter = Reporter::new(console_format).with_tree_depth(config.report_depth);
     reporter.print_report(&scan_report);
 
+    // Write the report file, as JSON unless another format was asked for
+    if let Some(path) = &config.report {
+        Reporter::new(config.report_format.unwrap_or(ReportFormat::Json))
+            .with_tree_depth(config.report_depth)
+            .write_report(&scan_report, path)?;
+    }
+
     // Handle clipboard functionality if --clip is specified
-    if config.clip || config.stdout {
-        // Get the output file content
-        let output_content = std::fs::read_to_string(&config.output_file)?;
+    deliver_output(&config, &parts, clipboard)?;
+
+    // Keep regenerating the output as files change (streaming mode excludes watching)
+    if let Some(root_node) = root_node.filter(|_| config.watch) {
+        eprintln!(
+            "👀 Watching {} for changes (press Ctrl+C to stop)",
+            config.target_dir.display()
+        );
+        let mut scanner = scanner;
+        scanner.progress = Arc::new(ProgressBar::hidden());
+        watch::watch(&config, &scanner, root_node, |root, changed| {
+            let (_, parts, clipboard) = write_output(&config, root)?;
+            eprintln!(
+                "🔄 {} path(s) changed, regenerated {}",
+                changed.len(),
+                config.output_file.display()
+            );
+            deliver_output(&config, &parts, clipboard)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Write the output for a scanned tree, packed into the token budget and split into parts if requested
+///
+/// Also returns the output for the clipboard when it was written in one piece.
+fn write_output(
+    config: &Config,
+    root_node: &DirectoryNode,
+) -> Result<(Option<BudgetSummary>, Vec<OutputPart>, Option<String>)> {
+    // Pack file contents into the token budget if one was requested
+    let packed;
+    let (root_node, budget_summary) = match &config.token_budget {
+        Some(budget) => {
+            let mut tree = root_node.clone();
+            let summary = budget::pack(config, &mut tree, budget.resolve(config.model)?)?;
+            packed = tree;
+            (&packed, Some(summary))
+        }
+        None => (root_node, None),
+    };
+
+    // Write output, either in one piece or as numbered parts
+    let (parts, clipboard) = match config.split {
+        Some(limit) => (split::write_parts(config, root_node, limit)?, None),
+        None => {
+            let mut sinks = OutputSinks::open(config)?;
+            config
+                .format
+                .render(config.clone(), root_node, &mut sinks)?;
+            (Vec::new(), sinks.finish()?)
+        }
+    };
+
+    Ok((budget_summary, parts, clipboard))
+}
+
+/// Scan and write the output in one pass, without holding the whole tree in memory
+///
+/// Also returns the output for the clipboard if it was requested.
+fn stream_output(config: &Config, scanner: &Scanner) -> Result<Option<String>> {
+    let mut sinks = OutputSinks::open(config)?;
+    let mut writer = config.format.stream(config.clone(), &mut sinks)?;
+    scanner.scan_into(&mut *writer)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(sinks.finish()?)
+}
+
+/// Destinations an output in one piece is written to as it is rendered
+struct OutputSinks {
+    /// Output file, unless writing to stdout
+    file: Option<BufWriter<File>>,
+    /// Stdout, when printing the output
+    stdout: Option<BufWriter<io::StdoutLock<'static>>>,
+    /// Copy of the output for the clipboard
+    clipboard: Option<Vec<u8>>,
+}
+
+impl OutputSinks {
+    fn open(config: &Config) -> io::Result<Self> {
+        let file = match config.writes_to_stdout() {
+            true => None,
+            false => Some(BufWriter::new(File::create(&config.output_file)?)),
+        };
+        Ok(Self {
+            file,
+            stdout: (config.stdout || config.writes_to_stdout())
+                .then(|| BufWriter::new(io::stdout().lock())),
+            clipboard: config.clip.then(Vec::new),
+        })
+    }
+
+    /// Flush the file and stdout, returning the output for the clipboard
+    fn finish(mut self) -> io::Result<Option<String>> {
+        self.flush()?;
+        Ok(self
+            .clipboard
+            .map(|output| String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+impl Write for OutputSinks {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(file) = &mut self.file {
+            file.write_all(buf)?;
+        }
+        if let Some(stdout) = &mut self.stdout {
+            stdout.write_all(buf)?;
+        }
+        if let Some(clipboard) = &mut self.clipboard {
+            clipboard.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        if let Some(stdout) = &mut self.stdout {
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Print split parts to stdout and copy the output to the clipboard if requested
+///
+/// An output in one piece was printed as it was written, and `clipboard` holds it.
+fn deliver_output(config: &Config, parts: &[OutputPart], clipboard: Option<String>) -> Result<()> {
+    if !config.clip && !config.stdout {
+        return Ok(());
+    }
+
+    // Read every part in order when split
+    let output_contents = if parts.is_empty() {
+        clipboard.into_iter().collect()
+    } else {
+        let output_contents = parts
+            .iter()
+            .map(|part| std::fs::read_to_string(&part.path))
+            .collect::<io::Result<Vec<_>>>()?;
         if config.stdout {
-            std::io::stdout().write_all(output_content.as_bytes())?;
+            for output_content in &output_contents {
+                std::io::stdout().write_all(output_content.as_bytes())?;
+            }
         }
+        output_contents
+    };
 
-        if config.clip {
-            // Copy to clipboard
-            match clipboard::copy_to_clipboard(&output_content) {
-                Ok(_) => {
-                    eprintln!("✅ Output copied to clipboard successfully");
-                }
-                Err(e) => {
-                    eprintln!("❌ Failed to copy to clipboard: {}", e);
-                    // Don't return error as the main functionality (file generation) succeeded
-                }
+    if config.clip && !output_contents.is_empty() {
+        // Copy to clipboard (only the first part fits in a single paste)
+        match clipboard::copy_to_clipboard(&output_contents[0]) {
+            Ok(_) if output_contents.len() > 1 => {
+                eprintln!(
+                    "✅ Part 1 of {} copied to clipboard successfully",
+                    output_contents.len()
+                );
+            }
+            Ok(_) => {
+                eprintln!("✅ Output copied to clipboard successfully");
+            }
+            Err(e) => {
+                eprintln!("❌ Failed to copy to clipboard: {}", e);
+                // Don't return error as the main functionality (file generation) succeeded
             }
         }
     }
@@ -0,0 +1,564 @@
+/*!
+ * Model Context Protocol server over stdio
+ *
+ * `dumpfs mcp` reads JSON-RPC messages line by line from stdin and answers on
+ * stdout, exposing the target directory to coding agents as tools. Every tool
+ * call scans the target with the configured `Scanner`, so ignore and include
+ * rules, redaction and diff mode apply exactly as in a normal dump: files that
+ * would not appear in the output cannot be listed, read or searched.
+ */
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use indicatif::ProgressBar;
+use regex::Regex;
+use serde_json::{json, Value};
+
+use crate::budget::file_tokens;
+use crate::config::Config;
+use crate::scanner::Scanner;
+use crate::tokenizer::{create_tokenizer, Tokenizer};
+use crate::types::{DirectoryNode, Node};
+
+/// Protocol version answered when the client does not request one
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Default number of matches returned by `search`
+const DEFAULT_MAX_RESULTS: usize = 100;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// MCP server exposing one scanned directory
+pub struct McpServer {
+    config: Config,
+    /// Tokenizer shared by all scans (if a model is configured)
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+}
+
+impl McpServer {
+    /// Create a server for the target directory in `config`
+    pub fn new(config: Config) -> Self {
+        let tokenizer = config.model.and_then(|model| {
+            let project_dir = config.target_dir.to_string_lossy().to_string();
+            match create_tokenizer(model, &project_dir) {
+                Ok(tokenizer) => Some(Arc::from(tokenizer)),
+                Err(e) => {
+                    eprintln!("Error creating tokenizer: {}", e);
+                    None
+                }
+            }
+        });
+
+        Self { config, tokenizer }
+    }
+
+    /// Answer messages from `input` on `output` until `input` is closed
+    pub fn run<R: BufRead, W: Write>(&self, input: R, mut output: W) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle one JSON-RPC message, returning the response for requests
+    pub fn handle_message(&self, message: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(message) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+
+        // Notifications have no id and get no response
+        let id = request.get("id")?.clone();
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = &request["params"];
+
+        let result = match method {
+            "initialize" => json!({
+                "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "dumpfs", "version": crate::VERSION },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tool_definitions() }),
+            "tools/call" => {
+                let Some(name) = params["name"].as_str() else {
+                    return Some(error_response(id, INVALID_PARAMS, "Missing tool name"));
+                };
+                match self.call_tool(name, &params["arguments"]) {
+                    Ok(text) => json!({
+                        "content": [{ "type": "text", "text": text }],
+                        "isError": false,
+                    }),
+                    Err(message) => json!({
+                        "content": [{ "type": "text", "text": message }],
+                        "isError": true,
+                    }),
+                }
+            }
+            _ => {
+                return Some(error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("Unknown method: {}", method),
+                ))
+            }
+        };
+
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Run a tool, returning its text output or an error message for the agent
+    fn call_tool(&self, name: &str, arguments: &Value) -> std::result::Result<String, String> {
+        let root = self.scan()?;
+        match name {
+            "list_tree" => {
+                let dir = match arguments["path"].as_str() {
+                    Some(path) => find_directory(&root, &clean_path(path))
+                        .ok_or_else(|| format!("No such directory: {}", path))?,
+                    None => &root,
+                };
+                let mut out = String::new();
+                write_tree(dir, 0, &mut out);
+                Ok(out)
+            }
+            "read_files" => {
+                let paths = string_array(&arguments["paths"])
+                    .ok_or("`paths` must be an array of strings")?;
+                self.read_files(root, &paths)
+            }
+            "search" => {
+                let pattern = arguments["pattern"]
+                    .as_str()
+                    .ok_or("`pattern` must be a string")?;
+                let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                let max_results = arguments["max_results"]
+                    .as_u64()
+                    .map_or(DEFAULT_MAX_RESULTS, |n| n as usize);
+                Ok(search(&root, &regex, max_results))
+            }
+            "count_tokens" => {
+                let paths = match &arguments["paths"] {
+                    Value::Null => None,
+                    value => {
+                        Some(string_array(value).ok_or("`paths` must be an array of strings")?)
+                    }
+                };
+                Ok(self.count_tokens(&root, paths.as_deref()))
+            }
+            _ => Err(format!("Unknown tool: {}", name)),
+        }
+    }
+
+    /// Scan the target directory with a fresh scanner
+    fn scan(&self) -> std::result::Result<DirectoryNode, String> {
+        let scanner = Scanner::with_tokenizer(
+            self.config.clone(),
+            Arc::new(ProgressBar::hidden()),
+            self.tokenizer.clone(),
+        );
+        scanner.scan().map_err(|e| e.to_string())
+    }
+
+    /// Render the selected files (or directories) in the configured output format
+    fn read_files(
+        &self,
+        mut root: DirectoryNode,
+        paths: &[String],
+    ) -> std::result::Result<String, String> {
+        let wanted: Vec<PathBuf> = paths.iter().map(|path| clean_path(path)).collect();
+        let mut found = HashSet::new();
+        let root_path = root.path.clone();
+        retain_paths(&mut root, &root_path, &wanted, &mut found);
+
+        let missing: Vec<&String> = paths
+            .iter()
+            .zip(&wanted)
+            .filter(|(_, path)| !found.contains(*path))
+            .map(|(path, _)| path)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Not found or excluded from the dump: {}",
+                missing
+                    .iter()
+                    .map(|path| path.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        let mut out = Vec::new();
+        self.config
+            .format
+            .render(self.config.clone(), &root, &mut out)
+            .map_err(|e| e.to_string())?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    /// Token counts of the selected files (all files by default) and their total
+    fn count_tokens(&self, root: &DirectoryNode, paths: Option<&[String]>) -> String {
+        let wanted: Option<Vec<PathBuf>> =
+            paths.map(|paths| paths.iter().map(|path| clean_path(path)).collect());
+
+        let mut out = String::new();
+        let mut total = 0;
+        visit_files(root, &root.path, &mut |rel_path, file| {
+            let selected = wanted.as_ref().is_none_or(|wanted| {
+                wanted
+                    .iter()
+                    .any(|path| path.as_os_str().is_empty() || rel_path.starts_with(path))
+            });
+            if selected && file.content.is_some() {
+                let tokens = file_tokens(file);
+                total += tokens;
+                out.push_str(&format!("{}\t{}\n", rel_path.display(), tokens));
+            }
+        });
+
+        let kind = match self.tokenizer {
+            Some(_) => "counted",
+            None => "estimated",
+        };
+        out.push_str(&format!("total\t{} ({})\n", total, kind));
+        out
+    }
+}
+
+/// Definitions of the tools for `tools/list`
+fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "list_tree",
+            "description": "List the directories and files that a dump of the project includes, with the token size of each text file.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory to list, relative to the project root (default: the whole project)" }
+                }
+            }
+        },
+        {
+            "name": "read_files",
+            "description": "Read files or directories from the project, rendered in the configured dump format.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "paths": { "type": "array", "items": { "type": "string" }, "description": "Paths relative to the project root" }
+                },
+                "required": ["paths"]
+            }
+        },
+        {
+            "name": "search",
+            "description": "Search the content of the project's text files with a regular expression. Returns `path:line: text` for each matching line.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "description": "Regular expression (Rust syntax)" },
+                    "max_results": { "type": "integer", "description": "Maximum number of matching lines (default: 100)" }
+                },
+                "required": ["pattern"]
+            }
+        },
+        {
+            "name": "count_tokens",
+            "description": "Count the tokens of files or directories in the project, and their total.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "paths": { "type": "array", "items": { "type": "string" }, "description": "Paths relative to the project root (default: every file)" }
+                }
+            }
+        }
+    ])
+}
+
+/// JSON-RPC error response
+fn error_response(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+/// Parse an array of strings
+fn string_array(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|item| item.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Normalize a path from the agent (`./src/`, `src`) to a relative path
+fn clean_path(path: &str) -> PathBuf {
+    Path::new(path.trim())
+        .components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
+}
+
+/// Path of a node relative to the scan root
+fn relative<'a>(path: &'a Path, root_path: &Path) -> &'a Path {
+    path.strip_prefix(root_path).unwrap_or(path)
+}
+
+/// Find a directory by its path relative to the scan root
+fn find_directory<'a>(root: &'a DirectoryNode, rel_path: &Path) -> Option<&'a DirectoryNode> {
+    if rel_path.as_os_str().is_empty() {
+        return Some(root);
+    }
+    let mut stack = vec![root];
+    while let Some(dir) = stack.pop() {
+        for node in &dir.contents {
+            if let Node::Directory(child) = node {
+                if relative(&child.path, &root.path) == rel_path {
+                    return Some(child);
+                }
+                stack.push(child);
+            }
+        }
+    }
+    None
+}
+
+/// Write an indented listing of a directory
+fn write_tree(dir: &DirectoryNode, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!("{}{}/\n", indent, dir.name));
+    let indent = "  ".repeat(depth + 1);
+    for node in &dir.contents {
+        match node {
+            Node::Directory(child) => write_tree(child, depth + 1, out),
+            Node::File(file) => {
+                out.push_str(&format!(
+                    "{}{} ({} tokens)\n",
+                    indent,
+                    file.name,
+                    file_tokens(file)
+                ));
+            }
+            Node::Binary(binary) => {
+                let mime = binary.summary.as_ref().and_then(|s| s.mime.as_deref());
+                match mime {
+                    Some(mime) => {
+                        out.push_str(&format!("{}{} (binary, {})\n", indent, binary.name, mime))
+                    }
+                    None => out.push_str(&format!("{}{} (binary)\n", indent, binary.name)),
+                }
+            }
+            Node::Symlink(symlink) => {
+                out.push_str(&format!(
+                    "{}{} -> {}\n",
+                    indent, symlink.name, symlink.target
+                ));
+            }
+        }
+    }
+}
+
+/// Keep only nodes at or below the wanted paths, recording which paths were found
+///
+/// Returns whether anything in `dir` was kept.
+fn retain_paths(
+    dir: &mut DirectoryNode,
+    root_path: &Path,
+    wanted: &[PathBuf],
+    found: &mut HashSet<PathBuf>,
+) -> bool {
+    let mark = |path: &Path, found: &mut HashSet<PathBuf>| {
+        let rel_path = relative(path, root_path);
+        let mut selected = false;
+        for wanted_path in wanted {
+            if rel_path.starts_with(wanted_path) {
+                found.insert(wanted_path.clone());
+                selected = true;
+            }
+        }
+        selected
+    };
+
+    dir.contents.retain_mut(|node| match node {
+        Node::Directory(child) => {
+            // A selected directory keeps everything below it
+            mark(&child.path, found) || retain_paths(child, root_path, wanted, found)
+        }
+        Node::File(file) => mark(&file.path, found),
+        Node::Binary(binary) => mark(&binary.path, found),
+        Node::Symlink(symlink) => mark(&symlink.path, found),
+    });
+    !dir.contents.is_empty()
+}
+
+/// Call `f` with the relative path of every text file in the tree
+fn visit_files<F>(dir: &DirectoryNode, root_path: &Path, f: &mut F)
+where
+    F: FnMut(&Path, &crate::types::FileNode),
+{
+    for node in &dir.contents {
+        match node {
+            Node::Directory(child) => visit_files(child, root_path, f),
+            Node::File(file) => f(relative(&file.path, root_path), file),
+            _ => {}
+        }
+    }
+}
+
+/// Lines of text files matching `regex`, as `path:line: text`
+fn search(root: &DirectoryNode, regex: &Regex, max_results: usize) -> String {
+    let mut out = String::new();
+    let mut matches = 0;
+    visit_files(root, &root.path, &mut |rel_path, file| {
+        let Some(content) = &file.content else {
+            return;
+        };
+        for (number, line) in content.lines().enumerate() {
+            if matches >= max_results {
+                return;
+            }
+            if regex.is_match(line) {
+                matches += 1;
+                out.push_str(&format!(
+                    "{}:{}: {}\n",
+                    rel_path.display(),
+                    number + 1,
+                    line
+                ));
+            }
+        }
+    });
+
+    match matches {
+        0 => "No matches".to_string(),
+        n if n >= max_results => {
+            out.push_str(&format!("(stopped after {} matches)\n", max_results));
+            out
+        }
+        _ => out,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::writer::FsWriterFormatter;
+
+    fn call(server: &McpServer, name: &str, arguments: Value) -> (String, bool) {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": name, "arguments": arguments },
+        });
+        let response = server.handle_message(&request.to_string()).unwrap();
+        let result = &response["result"];
+        (
+            result["content"][0]["text"].as_str().unwrap().to_string(),
+            result["isError"].as_bool().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_mcp_tools() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src").join("main.rs"),
+            "fn main() {\n    run();\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src").join("lib.rs"), "pub fn run() {}\n").unwrap();
+        fs::write(dir.path().join("debug.log"), "fn run() in log\n").unwrap();
+
+        let server = McpServer::new(Config {
+            target_dir: dir.path().to_path_buf(),
+            output_file: dir.path().join(".dumpfs.context.md"),
+            ignore_patterns: vec!["*.log".to_string()],
+            format: FsWriterFormatter::Txt,
+            ..Default::default()
+        });
+
+        let (tree, _) = call(&server, "list_tree", json!({}));
+        assert!(tree.contains("  src/\n"));
+        assert!(tree.contains("    main.rs ("));
+        assert!(!tree.contains("debug.log"));
+
+        let (output, is_error) = call(&server, "read_files", json!({ "paths": ["./src/lib.rs"] }));
+        assert!(!is_error);
+        assert!(output.contains("pub fn run() {}"));
+        assert!(!output.contains("fn main()"));
+
+        // Ignored files cannot be read
+        let (message, is_error) = call(&server, "read_files", json!({ "paths": ["debug.log"] }));
+        assert!(is_error);
+        assert!(message.contains("debug.log"));
+
+        let (matches, _) = call(&server, "search", json!({ "pattern": r"run\(\)" }));
+        assert!(matches.contains(&format!(
+            "{}:2:     run();",
+            Path::new("src").join("main.rs").display()
+        )));
+        assert!(matches.contains("lib.rs:1: pub fn run() {}"));
+        assert!(!matches.contains("log"));
+
+        let (counts, _) = call(&server, "count_tokens", json!({ "paths": ["src/lib.rs"] }));
+        assert!(counts.contains("lib.rs\t4\n"));
+        assert!(counts.contains("total\t4 (estimated)"));
+
+        let (_, is_error) = call(&server, "search", json!({ "pattern": "(" }));
+        assert!(is_error);
+    }
+
+    #[test]
+    fn test_mcp_protocol() {
+        let server = McpServer::new(Config::default());
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"resources/list"}"#,
+            "\n",
+        );
+
+        let mut output = Vec::new();
+        server.run(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "dumpfs");
+        let tools: Vec<&str> = responses[1]["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(tools, ["list_tree", "read_files", "search", "count_tokens"]);
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
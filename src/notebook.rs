@@ -0,0 +1,319 @@
+/*!
+ * Flattening of Jupyter notebooks
+ *
+ * Notebooks are stored as JSON in which outputs, base64 images and execution
+ * metadata usually outweigh the code. Before a notebook is counted and
+ * written, its cells are rendered in order: markdown cells as markdown, code
+ * cells as fenced code blocks followed by their text outputs. Binary outputs,
+ * execution counts and metadata are dropped.
+ *
+ * Each cell starts with a `<!-- cell N: kind -->` header line, where N is the
+ * index of the cell in the notebook, so writers can find the cells again.
+ */
+
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::types::{CellKind, NotebookCell};
+use crate::utils::code_fence;
+
+/// Extension of notebook files
+const NOTEBOOK_EXTENSION: &str = "ipynb";
+
+/// Language of code cells when the notebook does not name one
+const DEFAULT_LANGUAGE: &str = "python";
+
+/// Notebook document, keeping only what is rendered
+#[derive(Debug, Deserialize)]
+struct Document {
+    #[serde(default)]
+    cells: Vec<Cell>,
+    #[serde(default)]
+    metadata: Value,
+}
+
+/// A single cell of a notebook
+#[derive(Debug, Deserialize)]
+struct Cell {
+    cell_type: CellKind,
+    #[serde(default)]
+    source: Text,
+    #[serde(default)]
+    outputs: Vec<Output>,
+}
+
+/// Multi-line text, stored either as one string or as a list of lines
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    #[default]
+    Empty,
+    Lines(Vec<String>),
+    String(String),
+}
+
+impl Text {
+    fn join(&self) -> String {
+        match self {
+            Text::Empty => String::new(),
+            Text::Lines(lines) => lines.concat(),
+            Text::String(text) => text.clone(),
+        }
+    }
+}
+
+/// Output of a code cell
+#[derive(Debug, Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+enum Output {
+    Stream {
+        #[serde(default)]
+        text: Text,
+    },
+    ExecuteResult {
+        #[serde(default)]
+        data: Value,
+    },
+    DisplayData {
+        #[serde(default)]
+        data: Value,
+    },
+    Error {
+        #[serde(default)]
+        ename: String,
+        #[serde(default)]
+        evalue: String,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+impl Output {
+    /// Text of the output, or None for outputs without a plain text form
+    fn text(&self) -> Option<String> {
+        let text = match self {
+            Output::Stream { text } => text.join(),
+            Output::ExecuteResult { data } | Output::DisplayData { data } => {
+                let plain = data.get("text/plain")?;
+                serde_json::from_value::<Text>(plain.clone()).ok()?.join()
+            }
+            Output::Error { ename, evalue } => format!("{}: {}", ename, evalue),
+            Output::Unknown => return None,
+        };
+        (!text.trim().is_empty()).then_some(text)
+    }
+}
+
+/// Whether a file is a Jupyter notebook
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(NOTEBOOK_EXTENSION))
+}
+
+/// Render a notebook as markdown with code blocks
+///
+/// Text outputs are kept up to `output_lines` lines each (all of them if
+/// None, none if zero). Fails if the content is not notebook JSON.
+pub fn flatten(json: &str, output_lines: Option<usize>) -> Result<String, serde_json::Error> {
+    let document: Document = serde_json::from_str(json)?;
+    let language = document
+        .metadata
+        .pointer("/kernelspec/language")
+        .or_else(|| document.metadata.pointer("/language_info/name"))
+        .and_then(Value::as_str)
+        .unwrap_or(DEFAULT_LANGUAGE);
+
+    let mut rendered = Vec::new();
+    for (index, cell) in document.cells.iter().enumerate() {
+        let source = cell.source.join();
+        let source = source.trim_end();
+        if source.trim().is_empty() {
+            continue;
+        }
+
+        let mut text = format!("<!-- cell {}: {} -->\n", index, cell.cell_type.name());
+        match cell.cell_type {
+            CellKind::Code => {
+                let fence = code_fence(source);
+                text.push_str(&format!("{}{}\n{}\n{}\n", fence, language, source, fence));
+                for output in &cell.outputs {
+                    if let Some(output) = output.text() {
+                        let output = truncate_lines(output.trim_end(), output_lines);
+                        if output.is_empty() {
+                            continue;
+                        }
+                        let fence = code_fence(&output);
+                        text.push_str(&format!("{}output\n{}\n{}\n", fence, output, fence));
+                    }
+                }
+            }
+            CellKind::Markdown | CellKind::Raw => {
+                text.push_str(source);
+                text.push('\n');
+            }
+        }
+        rendered.push(text);
+    }
+
+    Ok(rendered.join("\n"))
+}
+
+/// Cells of flattened notebook content, found from their header lines
+///
+/// Lines are numbered from 1 and exclude the header. Returns None if the
+/// content has no cell headers, e.g. because it could not be flattened.
+pub fn cells(content: &str) -> Option<Vec<NotebookCell>> {
+    let lines: Vec<&str> = content.lines().collect();
+    let headers: Vec<(usize, usize, CellKind)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| parse_header(line).map(|(index, kind)| (i, index, kind)))
+        .collect();
+    if headers.is_empty() {
+        return None;
+    }
+
+    let cells = headers
+        .iter()
+        .enumerate()
+        .map(|(n, &(line, index, cell_type))| {
+            // The cell runs to the next header, without the blank line before it
+            let next = headers.get(n + 1).map_or(lines.len(), |&(line, _, _)| line);
+            let mut end = next;
+            while end > line + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            NotebookCell {
+                index,
+                cell_type,
+                start_line: line + 2,
+                end_line: end.max(line + 1),
+            }
+        })
+        .collect();
+    Some(cells)
+}
+
+/// Index and kind from a `<!-- cell N: kind -->` header line
+fn parse_header(line: &str) -> Option<(usize, CellKind)> {
+    let (index, kind) = line
+        .strip_prefix("<!-- cell ")?
+        .strip_suffix(" -->")?
+        .split_once(": ")?;
+    let kind = match kind {
+        "code" => CellKind::Code,
+        "markdown" => CellKind::Markdown,
+        "raw" => CellKind::Raw,
+        _ => return None,
+    };
+    Some((index.parse().ok()?, kind))
+}
+
+/// Keep at most `limit` lines, noting how many were left out
+fn truncate_lines(text: &str, limit: Option<usize>) -> String {
+    let total = text.lines().count();
+    match limit {
+        Some(limit) if total > limit => {
+            if limit == 0 {
+                return String::new();
+            }
+            let mut kept: Vec<&str> = text.lines().take(limit).collect();
+            let marker = format!("[... {} more lines]", total - limit);
+            kept.push(&marker);
+            kept.join("\n")
+        }
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+        "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+        "nbformat": 4,
+        "cells": [
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "Load the data."]},
+            {"cell_type": "code", "execution_count": 1, "metadata": {"scrolled": true},
+             "source": "import pandas as pd\ndf = pd.read_csv('data.csv')\ndf.head()",
+             "outputs": [
+                {"output_type": "stream", "name": "stdout", "text": ["line 1\n", "line 2\n", "line 3\n"]},
+                {"output_type": "display_data", "metadata": {},
+                 "data": {"image/png": "iVBORw0KGgoAAAANSUhEUgAA"}},
+                {"output_type": "execute_result", "execution_count": 1, "metadata": {},
+                 "data": {"text/plain": ["   a  b\n", "0  1  2"], "text/html": ["<table></table>"]}}
+             ]},
+            {"cell_type": "code", "execution_count": null, "metadata": {}, "source": [], "outputs": []},
+            {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": "1 / 0",
+             "outputs": [{"output_type": "error", "ename": "ZeroDivisionError",
+                          "evalue": "division by zero", "traceback": ["\u001b[0;31m..."]}]}
+        ]
+    }"##;
+
+    #[test]
+    fn test_flatten() {
+        let text = flatten(NOTEBOOK, None).unwrap();
+        assert_eq!(
+            text,
+            "<!-- cell 0: markdown -->\n\
+             # Analysis\n\
+             Load the data.\n\
+             \n\
+             <!-- cell 1: code -->\n\
+             ```python\n\
+             import pandas as pd\n\
+             df = pd.read_csv('data.csv')\n\
+             df.head()\n\
+             ```\n\
+             ```output\n\
+             line 1\n\
+             line 2\n\
+             line 3\n\
+             ```\n\
+             ```output\n   a  b\n0  1  2\n```\n\
+             \n\
+             <!-- cell 3: code -->\n\
+             ```python\n\
+             1 / 0\n\
+             ```\n\
+             ```output\n\
+             ZeroDivisionError: division by zero\n\
+             ```\n"
+        );
+        assert!(!text.contains("iVBORw0KGgo"));
+        assert!(!text.contains("execution_count"));
+    }
+
+    #[test]
+    fn test_output_lines() {
+        let text = flatten(NOTEBOOK, Some(1)).unwrap();
+        assert!(text.contains("```output\nline 1\n[... 2 more lines]\n```"));
+
+        let text = flatten(NOTEBOOK, Some(0)).unwrap();
+        assert!(!text.contains("```output"));
+
+        assert!(flatten("not json", None).is_err());
+    }
+
+    #[test]
+    fn test_cells() {
+        let text = flatten(NOTEBOOK, None).unwrap();
+        let cells = cells(&text).unwrap();
+        let spans: Vec<_> = cells
+            .iter()
+            .map(|c| (c.index, c.cell_type, c.start_line, c.end_line))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, CellKind::Markdown, 2, 3),
+                (1, CellKind::Code, 6, 19),
+                (3, CellKind::Code, 22, 27),
+            ]
+        );
+        assert_eq!(super::cells("plain text\n"), None);
+    }
+}
@@ -0,0 +1,242 @@
+/*!
+ * Ordering of directory entries
+ *
+ * By default entries appear in the order the filesystem lists them, which
+ * can differ between machines and runs. With `--sort`, the entries of each
+ * directory are sorted instead, directories still coming before files.
+ */
+
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use clap::ValueEnum;
+
+use crate::types::{DirectoryNode, Node};
+
+/// Order of the entries within each directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortOrder {
+    /// By name, comparing bytes
+    Name,
+    /// By name, comparing runs of digits as numbers (`file2` before `file10`)
+    Natural,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// What an entry is sorted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    /// File or directory name
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Last modification time
+    pub modified: SystemTime,
+}
+
+impl SortKey {
+    /// Key of a scanned node
+    pub fn of_node(node: &Node) -> Self {
+        Self {
+            name: node.name().to_string(),
+            size: node.metadata().size,
+            modified: node.metadata().modified,
+        }
+    }
+
+    /// Key of a path on disk, reading its metadata only if `order` needs it
+    pub fn of_path(path: &Path, order: SortOrder) -> Self {
+        let metadata = match order {
+            SortOrder::Size | SortOrder::Mtime => fs::metadata(path).ok(),
+            SortOrder::Name | SortOrder::Natural => None,
+        };
+        Self {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size: metadata.as_ref().map_or(0, fs::Metadata::len),
+            modified: metadata
+                .and_then(|m| m.modified().ok())
+                .unwrap_or(SystemTime::UNIX_EPOCH),
+        }
+    }
+}
+
+impl SortOrder {
+    /// Compare two entries, falling back to their names on ties
+    pub fn compare(&self, a: &SortKey, b: &SortKey) -> Ordering {
+        let by_name = || match self {
+            SortOrder::Natural => natural_cmp(&a.name, &b.name),
+            _ => a.name.cmp(&b.name),
+        };
+        match self {
+            SortOrder::Name | SortOrder::Natural => by_name(),
+            SortOrder::Size => b.size.cmp(&a.size).then_with(by_name),
+            SortOrder::Mtime => b.modified.cmp(&a.modified).then_with(by_name),
+        }
+    }
+
+    /// Sort items by their keys
+    pub fn sort<T>(&self, items: &mut Vec<T>, key: impl Fn(&T) -> SortKey) {
+        let mut keyed: Vec<(SortKey, T)> = items.drain(..).map(|item| (key(&item), item)).collect();
+        keyed.sort_by(|a, b| self.compare(&a.0, &b.0));
+        items.extend(keyed.into_iter().map(|(_, item)| item));
+    }
+
+    /// Sort the contents of a directory and of all directories below it
+    pub fn sort_tree(&self, dir: &mut DirectoryNode) {
+        for node in &mut dir.contents {
+            if let Node::Directory(child) = node {
+                self.sort_tree(child);
+            }
+        }
+        let (mut dirs, mut others): (Vec<Node>, Vec<Node>) = dir
+            .contents
+            .drain(..)
+            .partition(|node| matches!(node, Node::Directory(_)));
+        self.sort(&mut dirs, SortKey::of_node);
+        self.sort(&mut others, SortKey::of_node);
+        dir.contents = dirs;
+        dir.contents.append(&mut others);
+    }
+}
+
+/// Compare names, treating runs of ASCII digits as numbers
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (num_a, rest_a) = split_digits(a);
+            let (num_b, rest_b) = split_digits(b);
+            // Without leading zeros, a longer run is a larger number
+            let (trim_a, trim_b) = (num_a.trim_start_matches('0'), num_b.trim_start_matches('0'));
+            let ordering = trim_a
+                .len()
+                .cmp(&trim_b.len())
+                .then_with(|| trim_a.cmp(trim_b))
+                .then_with(|| num_a.len().cmp(&num_b.len()));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            (a, b) = (rest_a, rest_b);
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+/// Leading run of ASCII digits and the rest of the text
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::types::{FileNode, Metadata};
+
+    fn file(name: &str, size: u64, secs: u64) -> Node {
+        Node::File(FileNode {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            metadata: Metadata {
+                size,
+                modified: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+                permissions: "644".to_string(),
+                encoding: None,
+            },
+            content: None,
+            tokens: None,
+            diff: None,
+            line_range: None,
+            truncated: false,
+            cells: None,
+        })
+    }
+
+    fn names(dir: &DirectoryNode) -> Vec<&str> {
+        dir.contents.iter().map(Node::name).collect()
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec![
+            "file10.txt",
+            "file2.txt",
+            "file02.txt",
+            "File1.txt",
+            "file1a",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "File1.txt",
+                "file1a",
+                "file2.txt",
+                "file02.txt",
+                "file10.txt"
+            ]
+        );
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_sort_tree() {
+        let sub = DirectoryNode {
+            name: "sub".to_string(),
+            path: PathBuf::from("sub"),
+            metadata: file("sub", 0, 0).metadata().clone(),
+            contents: vec![file("b10", 1, 1), file("b9", 1, 1)],
+        };
+        let mut root = DirectoryNode {
+            name: "root".to_string(),
+            path: PathBuf::from("root"),
+            metadata: sub.metadata.clone(),
+            contents: vec![
+                file("small", 10, 300),
+                Node::Directory(sub),
+                file("large", 300, 100),
+                file("medium", 100, 200),
+            ],
+        };
+
+        SortOrder::Size.sort_tree(&mut root);
+        assert_eq!(names(&root), vec!["sub", "large", "medium", "small"]);
+
+        SortOrder::Mtime.sort_tree(&mut root);
+        assert_eq!(names(&root), vec!["sub", "small", "medium", "large"]);
+
+        SortOrder::Name.sort_tree(&mut root);
+        assert_eq!(names(&root), vec!["sub", "large", "medium", "small"]);
+        let Node::Directory(sub) = &root.contents[0] else {
+            panic!("sub should come first");
+        };
+        assert_eq!(names(sub), vec!["b10", "b9"]);
+
+        SortOrder::Natural.sort_tree(&mut root);
+        let Node::Directory(sub) = &root.contents[0] else {
+            panic!("sub should come first");
+        };
+        assert_eq!(names(sub), vec!["b9", "b10"]);
+    }
+}
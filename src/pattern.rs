@@ -0,0 +1,157 @@
+/*!
+ * Gitignore-style path patterns
+ *
+ * Include and ignore patterns are matched against paths relative to the scan
+ * root with the same rules as `.gitignore` files:
+ *
+ * - `*.log` or `target`: a pattern without a slash matches at any depth
+ * - `/build` or `docs/internal`: a pattern with a slash is anchored at the root
+ * - `Pods/`: a trailing slash only matches directories
+ * - `**` between slashes matches any number of directories
+ * - `!keep.log`: a negated pattern re-includes paths matched by earlier patterns
+ *
+ * A path also matches when one of its parent directories does, so `tests/fixtures`
+ * covers every file below that directory.
+ *
+ * Include patterns may end with a line range, as in `src/scanner.rs:150-320`,
+ * to select only those lines of the matching files.
+ */
+
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::types::LineRange;
+
+/// Compiled list of gitignore-style patterns
+#[derive(Debug, Clone)]
+pub struct PatternSet {
+    matcher: Gitignore,
+}
+
+impl Default for PatternSet {
+    fn default() -> Self {
+        Self {
+            matcher: Gitignore::empty(),
+        }
+    }
+}
+
+impl PatternSet {
+    /// Compile patterns, later ones taking precedence over earlier ones
+    pub fn new<I, S>(patterns: I) -> Result<Self, ignore::Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            builder.add_line(None, pattern.as_ref())?;
+        }
+        Ok(Self {
+            matcher: builder.build()?,
+        })
+    }
+
+    /// Whether the set has no patterns
+    pub fn is_empty(&self) -> bool {
+        self.matcher.is_empty()
+    }
+
+    /// Whether a root-relative path, or one of its parent directories, matches
+    pub fn matches(&self, rel_path: &Path, is_dir: bool) -> bool {
+        if rel_path.as_os_str().is_empty() || rel_path.has_root() {
+            return false;
+        }
+        self.matcher
+            .matched_path_or_any_parents(rel_path, is_dir)
+            .is_ignore()
+    }
+}
+
+/// Split a `path:start-end` spec into the pattern and its line range
+///
+/// Specs without a numeric suffix are returned unchanged, so patterns that
+/// contain a colon keep working.
+pub fn split_line_range(spec: &str) -> Result<(&str, Option<LineRange>), String> {
+    match spec.rsplit_once(':') {
+        Some((pattern, range))
+            if !pattern.is_empty()
+                && !range.is_empty()
+                && range.chars().all(|c| c.is_ascii_digit() || c == '-') =>
+        {
+            Ok((pattern, Some(range.parse()?)))
+        }
+        _ => Ok((spec, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(patterns: &[&str]) -> PatternSet {
+        PatternSet::new(patterns).unwrap()
+    }
+
+    #[test]
+    fn test_bare_names_match_at_any_depth() {
+        let patterns = set(&["*.log", "node_modules"]);
+        assert!(patterns.matches(Path::new("debug.log"), false));
+        assert!(patterns.matches(Path::new("a/b/debug.log"), false));
+        assert!(patterns.matches(Path::new("web/node_modules"), true));
+        assert!(patterns.matches(Path::new("web/node_modules/react/index.js"), false));
+        assert!(!patterns.matches(Path::new("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_path_patterns() {
+        let patterns = set(&["/build", "docs/internal/*", "Pods/", "src/**/*.rs"]);
+
+        // Anchored at the root
+        assert!(patterns.matches(Path::new("build"), true));
+        assert!(!patterns.matches(Path::new("tools/build"), true));
+
+        assert!(patterns.matches(Path::new("docs/internal/notes.md"), false));
+        assert!(!patterns.matches(Path::new("docs/public/notes.md"), false));
+
+        // Directories only
+        assert!(patterns.matches(Path::new("ios/Pods"), true));
+        assert!(!patterns.matches(Path::new("ios/Pods"), false));
+
+        assert!(patterns.matches(Path::new("src/main.rs"), false));
+        assert!(patterns.matches(Path::new("src/a/b/lib.rs"), false));
+        assert!(!patterns.matches(Path::new("tests/main.rs"), false));
+    }
+
+    #[test]
+    fn test_negation() {
+        let patterns = set(&["*.log", "!keep.log"]);
+        assert!(patterns.matches(Path::new("debug.log"), false));
+        assert!(!patterns.matches(Path::new("logs/keep.log"), false));
+
+        assert!(PatternSet::new(["src/[a"]).is_err());
+    }
+
+    #[test]
+    fn test_split_line_range() {
+        assert_eq!(
+            split_line_range("src/scanner.rs:150-320"),
+            Ok((
+                "src/scanner.rs",
+                Some(LineRange {
+                    start: 150,
+                    end: 320
+                })
+            ))
+        );
+        assert_eq!(
+            split_line_range("*.rs:7"),
+            Ok(("*.rs", Some(LineRange { start: 7, end: 7 })))
+        );
+        assert_eq!(split_line_range("src/*.rs"), Ok(("src/*.rs", None)));
+        assert_eq!(split_line_range("odd:name.rs"), Ok(("odd:name.rs", None)));
+        assert!(split_line_range("a.rs:20-10").is_err());
+        assert!(split_line_range("a.rs:0-3").is_err());
+    }
+}
@@ -5,16 +5,27 @@
  * using the tabled library for clean, consistent table rendering.
  */
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::time::Duration;
 
+use crate::budget::BudgetSummary;
+use crate::scanner::ScannerStatistics;
+use crate::split::OutputPart;
+use crate::utils::{format_file_size, language_for};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize, Serializer};
+
 use tabled::{
     settings::{object::Columns, Alignment, Modify, Padding, Style},
     Table, Tabled,
 };
 
 /// Information about a file in the report
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileReportInfo {
     /// Number of lines in the file
     pub lines: usize,
@@ -22,14 +33,168 @@ pub struct FileReportInfo {
     pub chars: usize,
     /// Number of tokens in the file (if tokenizer is enabled)
     pub tokens: Option<usize>,
+    /// Number of secrets redacted from the file
+    pub redactions: usize,
+    /// Whether only part of the file was kept because of its size
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Why a file or directory was left out of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Matched an ignore pattern
+    Ignored,
+    /// Matched none of the include patterns
+    NotIncluded,
+    /// Larger than the maximum file size, so only listed without content
+    TooLarge,
+    /// Could not be read or processed
+    Error,
+}
+
+impl SkipReason {
+    /// Short description for tables
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SkipReason::Ignored => "ignored",
+            SkipReason::NotIncluded => "not included",
+            SkipReason::TooLarge => "too large",
+            SkipReason::Error => "error",
+        }
+    }
+}
+
+/// A file or directory left out of the output, in the report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SkippedFile {
+    /// Why it was left out
+    pub reason: SkipReason,
+    /// Error message or other details
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Cumulative statistics of a group of files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Aggregate {
+    /// Number of files
+    pub files: usize,
+    /// Number of lines
+    pub lines: usize,
+    /// Number of characters
+    pub chars: usize,
+    /// Number of tokens, counted or estimated from the characters
+    pub tokens: usize,
+}
+
+impl Aggregate {
+    /// Add a file to the totals
+    fn add(&mut self, info: &FileReportInfo) {
+        self.files += 1;
+        self.lines += info.lines;
+        self.chars += info.chars;
+        self.tokens += info.tokens.unwrap_or(info.chars / 4);
+    }
+}
+
+/// Statistics of a directory, including everything below it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DirectoryReport {
+    /// Directory name (empty for the root)
+    pub name: String,
+    /// Totals of the files below the directory
+    pub totals: Aggregate,
+    /// Subdirectories, largest first
+    pub children: Vec<DirectoryReport>,
+}
+
+impl DirectoryReport {
+    /// Build the directory tree of the files in `file_details` below `root`
+    pub fn build(root: &str, file_details: &HashMap<String, FileReportInfo>) -> Self {
+        let mut tree = DirectoryReport::default();
+        for (path, info) in file_details {
+            let rel = Path::new(path)
+                .strip_prefix(root)
+                .unwrap_or(Path::new(path));
+            let mut node = &mut tree;
+            node.totals.add(info);
+            if let Some(parent) = rel.parent() {
+                for component in parent.iter() {
+                    let name = component.to_string_lossy();
+                    let index = match node.children.iter().position(|c| c.name == name) {
+                        Some(index) => index,
+                        None => {
+                            node.children.push(DirectoryReport {
+                                name: name.into_owned(),
+                                ..Default::default()
+                            });
+                            node.children.len() - 1
+                        }
+                    };
+                    node = &mut node.children[index];
+                    node.totals.add(info);
+                }
+            }
+        }
+        tree.sort();
+        tree
+    }
+
+    /// Order subdirectories by tokens, largest first
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| {
+            b.totals
+                .tokens
+                .cmp(&a.totals.tokens)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+}
+
+/// Statistics of the files in one language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LanguageReport {
+    /// Detected language, or the extension (`.ext`) if none was detected
+    pub language: String,
+    /// Totals of the files in the language
+    pub totals: Aggregate,
+}
+
+impl LanguageReport {
+    /// Group the files in `file_details` by language, largest first
+    pub fn build(file_details: &HashMap<String, FileReportInfo>) -> Vec<Self> {
+        let mut groups: BTreeMap<String, Aggregate> = BTreeMap::new();
+        for (path, info) in file_details {
+            let path = Path::new(path);
+            let language = match (language_for(path), path.extension()) {
+                (Some(language), _) => language.to_string(),
+                (None, Some(ext)) => format!(".{}", ext.to_string_lossy().to_ascii_lowercase()),
+                (None, None) => "(no extension)".to_string(),
+            };
+            groups.entry(language).or_default().add(info);
+        }
+
+        let mut languages: Vec<Self> = groups
+            .into_iter()
+            .map(|(language, totals)| LanguageReport { language, totals })
+            .collect();
+        languages.sort_by_key(|group| std::cmp::Reverse(group.totals.tokens));
+        languages
+    }
 }
 
 /// Statistics for a directory scan
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ScanReport {
     /// Output file path
     pub output_file: String,
-    /// Time taken to scan
+    /// Time taken to scan, as `duration_ms` in milliseconds
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
     pub duration: Duration,
     /// Number of files processed
     pub files_processed: usize,
@@ -40,30 +205,129 @@ pub struct ScanReport {
     /// Total number of tokens (if tokenizer is enabled)
     pub total_tokens: Option<usize>,
     /// Details for each file
+    #[serde(serialize_with = "serialize_sorted")]
     pub file_details: HashMap<String, FileReportInfo>,
+    /// Files and directories left out of the output, with the reason
+    #[serde(serialize_with = "serialize_sorted")]
+    pub skipped: HashMap<String, SkippedFile>,
     /// Token cache hits (if tokenizer caching is enabled)
     pub token_cache_hits: Option<usize>,
     /// Token cache misses (if tokenizer caching is enabled)
     pub token_cache_misses: Option<usize>,
+    /// Files reused from the scan index (if the index is enabled)
+    pub index_hits: Option<usize>,
+    /// Files not found or changed in the scan index (if the index is enabled)
+    pub index_misses: Option<usize>,
+    /// Outcome of token budget packing (if a budget was set)
+    pub budget: Option<BudgetSummary>,
+    /// Output parts written (empty unless the output was split)
+    pub parts: Vec<OutputPart>,
+    /// Totals per directory, starting at the scanned directory
+    pub directories: DirectoryReport,
+    /// Totals per language, largest first
+    pub languages: Vec<LanguageReport>,
+    /// Context window of the model in tokens (if a model was given)
+    pub context_window: Option<usize>,
+}
+
+impl ScanReport {
+    /// Build a report from the scanner statistics and the outcome of writing
+    pub fn new(
+        output_file: String,
+        duration: Duration,
+        stats: ScannerStatistics,
+        budget: Option<BudgetSummary>,
+        parts: Vec<OutputPart>,
+    ) -> Self {
+        Self {
+            directories: DirectoryReport::build(&stats.root, &stats.file_details),
+            languages: LanguageReport::build(&stats.file_details),
+            context_window: None,
+            output_file,
+            duration,
+            files_processed: stats.files_processed,
+            total_lines: stats.total_lines,
+            total_chars: stats.total_chars,
+            total_tokens: stats.total_tokens,
+            file_details: stats.file_details,
+            skipped: stats.skipped,
+            token_cache_hits: stats.token_cache_hits,
+            token_cache_misses: stats.token_cache_misses,
+            index_hits: stats.index_hits,
+            index_misses: stats.index_misses,
+            budget,
+            parts,
+        }
+    }
+
+    /// Set the context window the totals are compared against
+    pub fn with_context_window(mut self, context_window: Option<usize>) -> Self {
+        self.context_window = context_window;
+        self
+    }
+}
+
+/// Serialize a map with its keys in order, so reports are stable between runs
+fn serialize_sorted<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+/// Serialize a duration as fractional milliseconds
+fn serialize_millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
 }
 
 /// Format of the report output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
 pub enum ReportFormat {
     /// Console table output
+    #[default]
+    #[value(name = "table")]
     ConsoleTable,
-    // Other formats could be added in the future
-    // JSON, HTML, etc.
+    /// The full report as JSON, for scripts and CI
+    Json,
+    /// Markdown tables, e.g. for pull request comments
+    Markdown,
 }
 
 /// Report generator for scan results
 pub struct Reporter {
     format: ReportFormat,
+    /// Levels of subdirectories shown in the directory table
+    tree_depth: usize,
 }
 
+/// Levels of subdirectories shown in the directory table by default
+pub const DEFAULT_TREE_DEPTH: usize = 2;
+
 impl Reporter {
     /// Create a new reporter
     pub fn new(format: ReportFormat) -> Self {
-        Self { format }
+        Self {
+            format,
+            tree_depth: DEFAULT_TREE_DEPTH,
+        }
+    }
+
+    /// Show subdirectories down to `depth` levels below the scanned directory
+    pub fn with_tree_depth(mut self, depth: usize) -> Self {
+        self.tree_depth = depth;
+        self
+    }
+
+    /// Share of `whole` taken by `part`, as a percentage
+    fn format_percent(&self, part: usize, whole: Option<usize>) -> String {
+        match whole {
+            Some(whole) if whole > 0 => format!("{:.1}%", part as f64 / whole as f64 * 100.0),
+            _ => "-".to_string(),
+        }
     }
 
     /// Format a number with human-readable units
@@ -80,14 +344,53 @@ impl Reporter {
     /// Generate a report string based on scan statistics
     pub fn generate_report(&self, report: &ScanReport) -> String {
         match self.format {
-            ReportFormat::ConsoleTable => self.generate_console_report(report),
-            // Additional formats could be added here
+            ReportFormat::ConsoleTable | ReportFormat::Markdown => {
+                self.generate_console_report(report)
+            }
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(report).expect("scan reports always serialize to JSON")
+            }
         }
     }
 
-    /// Print the report to stdout
+    /// Print the report to stderr, keeping stdout free for the output
     pub fn print_report(&self, report: &ScanReport) {
-        println!("\n{}", self.generate_report(report));
+        eprintln!("\n{}", self.generate_report(report));
+    }
+
+    /// Write the report to a file
+    pub fn write_report(&self, report: &ScanReport, path: &Path) -> io::Result<()> {
+        let mut text = self.generate_report(report);
+        text.push('\n');
+        fs::write(path, text)
+    }
+
+    /// Section title, as a heading in Markdown
+    fn title(&self, emoji: &str, text: &str) -> String {
+        match self.format {
+            ReportFormat::Markdown => format!("## {}", text),
+            _ => format!("{}  {}", emoji, text.to_uppercase()),
+        }
+    }
+
+    /// Metric name, dropping its leading emoji in Markdown
+    fn label(&self, label: &str) -> String {
+        match (self.format, label.split_once(' ')) {
+            (ReportFormat::Markdown, Some((_, text))) => text.to_string(),
+            _ => label.to_string(),
+        }
+    }
+
+    /// Render rows as a table in the style of the report format
+    fn render_table<T: Tabled>(&self, rows: Vec<T>) -> String {
+        let mut table = Table::new(rows);
+        match self.format {
+            ReportFormat::Markdown => table.with(Style::markdown()),
+            _ => table.with(Style::rounded()).with(Padding::new(1, 1, 0, 0)),
+        };
+        table.with(Modify::new(Columns::new(..)).with(Alignment::left()));
+
+        table.to_string()
     }
 
     // Format path to be relative and handle truncation if needed
@@ -159,22 +462,22 @@ impl Reporter {
 
         // Add rows to the summary table
         rows.push(SummaryRow {
-            key: "📂 Output File".to_string(),
+            key: self.label("📂 Output File"),
             value: report.output_file.clone(),
         });
 
         rows.push(SummaryRow {
-            key: "⏱️ Process Time".to_string(),
+            key: self.label("⏱️ Process Time"),
             value: format!("{:.4?}", report.duration),
         });
 
         rows.push(SummaryRow {
-            key: "📄 Files Processed".to_string(),
+            key: self.label("📄 Files Processed"),
             value: self.format_number(report.files_processed),
         });
 
         rows.push(SummaryRow {
-            key: "📝 Total Lines".to_string(),
+            key: self.label("📝 Total Lines"),
             value: self.format_number(report.total_lines),
         });
 
@@ -190,10 +493,57 @@ impl Reporter {
         };
 
         rows.push(SummaryRow {
-            key: "📦 LLM Tokens".to_string(),
+            key: self.label("📦 LLM Tokens"),
             value: token_text,
         });
 
+        // Add the number of redacted secrets if any were found
+        let redactions: usize = report.file_details.values().map(|i| i.redactions).sum();
+        if redactions > 0 {
+            rows.push(SummaryRow {
+                key: self.label("🔒 Secrets Redacted"),
+                value: self.format_number(redactions),
+            });
+        }
+
+        // Add the number of large files that were only partly included
+        let truncated = report.file_details.values().filter(|i| i.truncated).count();
+        if truncated > 0 {
+            rows.push(SummaryRow {
+                key: self.label("📏 Files Truncated"),
+                value: self.format_number(truncated),
+            });
+        }
+
+        // Add the number of files left out, with the full list in the other formats
+        if !report.skipped.is_empty() {
+            rows.push(SummaryRow {
+                key: self.label("🚫 Files Skipped"),
+                value: self.format_number(report.skipped.len()),
+            });
+        }
+
+        // Add the number of parts if the output was split
+        if !report.parts.is_empty() {
+            rows.push(SummaryRow {
+                key: self.label("✂️ Output Parts"),
+                value: self.format_number(report.parts.len()),
+            });
+        }
+
+        // Add token budget usage if packing was applied
+        if let Some(budget) = &report.budget {
+            rows.push(SummaryRow {
+                key: self.label("🎯 Token Budget"),
+                value: format!(
+                    "{} / {} tokens ({} files omitted)",
+                    self.format_number(budget.used),
+                    self.format_number(budget.budget),
+                    budget.omitted.len()
+                ),
+            });
+        }
+
         // Add cache statistics if available
         if let (Some(hits), Some(misses)) = (report.token_cache_hits, report.token_cache_misses) {
             let total = hits + misses;
@@ -204,19 +554,24 @@ impl Reporter {
             };
 
             rows.push(SummaryRow {
-                key: "🔄 Cache Hit Rate".to_string(),
+                key: self.label("🔄 Cache Hit Rate"),
                 value: format!("{} ({} hits / {} total)", hit_rate, hits, total),
             });
         }
 
-        // Create and style the table
-        let mut table = Table::new(rows);
-        table
-            .with(Style::rounded())
-            .with(Padding::new(1, 1, 0, 0))
-            .with(Modify::new(Columns::new(..)).with(Alignment::left()));
+        // Add scan index reuse if the index is enabled
+        if let (Some(hits), Some(misses)) = (report.index_hits, report.index_misses) {
+            rows.push(SummaryRow {
+                key: self.label("📇 Scan Index"),
+                value: format!(
+                    "{} unchanged / {} files",
+                    self.format_number(hits),
+                    self.format_number(hits + misses)
+                ),
+            });
+        }
 
-        table.to_string()
+        self.render_table(rows)
     }
 
     // Create a files table using the tabled crate
@@ -236,7 +591,7 @@ impl Reporter {
 
         // Sort files by character count
         let mut files: Vec<_> = report.file_details.iter().collect();
-        files.sort_by(|(_, a), (_, b)| b.chars.cmp(&a.chars));
+        files.sort_by_key(|(_, info)| std::cmp::Reverse(info.chars));
 
         // Determine if we show all files or just top 10
         let files_to_show = if report.file_details.len() > 15 {
@@ -268,28 +623,252 @@ impl Reporter {
             })
             .collect();
 
-        // Create and style the table
-        let mut table = Table::new(rows);
-        table
-            .with(Style::rounded())
-            .with(Padding::new(1, 1, 0, 0))
-            .with(Modify::new(Columns::new(..)).with(Alignment::left()));
+        self.render_table(rows)
+    }
 
-        table.to_string()
+    // Create a table of output parts using the tabled crate
+    fn create_parts_table(&self, report: &ScanReport) -> String {
+        #[derive(Tabled)]
+        struct PartRow {
+            #[tabled(rename = "Part")]
+            index: usize,
+
+            #[tabled(rename = "File")]
+            path: String,
+
+            #[tabled(rename = "Size")]
+            size: String,
+
+            #[tabled(rename = "Est. Tokens")]
+            tokens: String,
+        }
+
+        let rows: Vec<PartRow> = report
+            .parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| PartRow {
+                index: i + 1,
+                path: self.format_path(&part.path.display().to_string(), 60),
+                size: format_file_size(part.bytes as u64),
+                tokens: self.format_number(part.tokens),
+            })
+            .collect();
+
+        self.render_table(rows)
     }
 
-    // Generate a console table report
+    // Create a table of files with redacted secrets using the tabled crate
+    fn create_redactions_table(&self, report: &ScanReport) -> String {
+        #[derive(Tabled)]
+        struct RedactionRow {
+            #[tabled(rename = "File Path")]
+            path: String,
+
+            #[tabled(rename = "Redactions")]
+            redactions: usize,
+        }
+
+        let mut files: Vec<_> = report
+            .file_details
+            .iter()
+            .filter(|(_, info)| info.redactions > 0)
+            .collect();
+        files.sort_by(|a, b| b.1.redactions.cmp(&a.1.redactions).then(a.0.cmp(b.0)));
+
+        let rows: Vec<RedactionRow> = files
+            .iter()
+            .map(|(path, info)| RedactionRow {
+                path: self.format_path(path, 60),
+                redactions: info.redactions,
+            })
+            .collect();
+
+        self.render_table(rows)
+    }
+
+    // Create a table of cumulative totals per directory using the tabled crate
+    fn create_directories_table(&self, report: &ScanReport) -> String {
+        #[derive(Tabled)]
+        struct DirectoryRow {
+            #[tabled(rename = "Directory")]
+            path: String,
+
+            #[tabled(rename = "Files")]
+            files: String,
+
+            #[tabled(rename = "Lines")]
+            lines: String,
+
+            #[tabled(rename = "Est. Tokens")]
+            tokens: String,
+
+            #[tabled(rename = "% Total")]
+            total: String,
+
+            #[tabled(rename = "% Context")]
+            context: String,
+        }
+
+        // Walk the tree down to the collapse depth, indenting each level
+        fn collect<'a>(
+            dir: &'a DirectoryReport,
+            level: usize,
+            max_depth: usize,
+            rows: &mut Vec<(usize, &'a DirectoryReport)>,
+        ) {
+            rows.push((level, dir));
+            if level < max_depth {
+                for child in &dir.children {
+                    collect(child, level + 1, max_depth, rows);
+                }
+            }
+        }
+        let mut dirs = Vec::new();
+        collect(&report.directories, 0, self.tree_depth, &mut dirs);
+
+        let total = report.directories.totals.tokens;
+        let rows: Vec<DirectoryRow> = dirs
+            .into_iter()
+            .map(|(level, dir)| DirectoryRow {
+                path: match level {
+                    0 => "./".to_string(),
+                    _ => format!("{}{}/", "  ".repeat(level), dir.name),
+                },
+                files: self.format_number(dir.totals.files),
+                lines: self.format_number(dir.totals.lines),
+                tokens: self.format_number(dir.totals.tokens),
+                total: self.format_percent(dir.totals.tokens, Some(total)),
+                context: self.format_percent(dir.totals.tokens, report.context_window),
+            })
+            .collect();
+
+        self.render_table(rows)
+    }
+
+    // Create a table of totals per language using the tabled crate
+    fn create_languages_table(&self, report: &ScanReport) -> String {
+        #[derive(Tabled)]
+        struct LanguageRow {
+            #[tabled(rename = "Language")]
+            language: String,
+
+            #[tabled(rename = "Files")]
+            files: String,
+
+            #[tabled(rename = "Lines")]
+            lines: String,
+
+            #[tabled(rename = "Est. Tokens")]
+            tokens: String,
+
+            #[tabled(rename = "% Total")]
+            total: String,
+
+            #[tabled(rename = "% Context")]
+            context: String,
+        }
+
+        let total = report.directories.totals.tokens;
+        let rows: Vec<LanguageRow> = report
+            .languages
+            .iter()
+            .map(|group| LanguageRow {
+                language: group.language.clone(),
+                files: self.format_number(group.totals.files),
+                lines: self.format_number(group.totals.lines),
+                tokens: self.format_number(group.totals.tokens),
+                total: self.format_percent(group.totals.tokens, Some(total)),
+                context: self.format_percent(group.totals.tokens, report.context_window),
+            })
+            .collect();
+
+        self.render_table(rows)
+    }
+
+    // Create a table of skipped files using the tabled crate
+    fn create_skipped_table(&self, report: &ScanReport) -> String {
+        #[derive(Tabled)]
+        struct SkippedRow {
+            #[tabled(rename = "Path")]
+            path: String,
+
+            #[tabled(rename = "Reason")]
+            reason: &'static str,
+
+            #[tabled(rename = "Detail")]
+            detail: String,
+        }
+
+        let mut files: Vec<_> = report.skipped.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(b.0));
+
+        let rows: Vec<SkippedRow> = files
+            .into_iter()
+            .map(|(path, skipped)| SkippedRow {
+                path: self.format_path(path, 60),
+                reason: skipped.reason.describe(),
+                detail: skipped.detail.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        self.render_table(rows)
+    }
+
+    // Generate a console table report, or the same tables in Markdown
     fn generate_console_report(&self, report: &ScanReport) -> String {
         // Generate summary and files tables
         let summary_table = self.create_summary_table(report);
-        let files_table = self.create_files_table(report);
+        let mut files_table = self.create_files_table(report);
+
+        // List the parts after the files when the output was split
+        if !report.parts.is_empty() {
+            files_table = format!(
+                "{}\n\n{}\n{}",
+                files_table,
+                self.title("✂️", "Output Parts"),
+                self.create_parts_table(report)
+            );
+        }
+
+        // List files with redacted secrets so they can be reviewed
+        if report.file_details.values().any(|info| info.redactions > 0) {
+            files_table = format!(
+                "{}\n\n{}\n{}",
+                files_table,
+                self.title("🔒", "Redacted Secrets"),
+                self.create_redactions_table(report)
+            );
+        }
+
+        // Show where the tokens go by directory and by language
+        if !report.file_details.is_empty() {
+            files_table = format!(
+                "{}\n\n{}\n{}\n\n{}\n{}",
+                files_table,
+                self.title("📁", "Tokens by Directory"),
+                self.create_directories_table(report),
+                self.title("🔤", "Tokens by Language"),
+                self.create_languages_table(report)
+            );
+        }
+
+        // The console only counts skipped files; Markdown reports list them
+        if self.format == ReportFormat::Markdown && !report.skipped.is_empty() {
+            files_table = format!(
+                "{}\n\n{}\n{}",
+                files_table,
+                self.title("🚫", "Skipped Files"),
+                self.create_skipped_table(report)
+            );
+        }
 
         // Create proper section titles
-        let summary_title = "✅  EXTRACTION COMPLETE";
+        let summary_title = self.title("✅", "Extraction Complete");
         let files_title = if report.file_details.len() > 15 {
-            "📋  TOP 10 LARGEST FILES BY CHARACTER COUNT  📋"
+            self.title("📋", "Top 10 Largest Files by Character Count")
         } else {
-            "📋  PROCESSED FILES"
+            self.title("📋", "Processed Files")
         };
 
         // Combine them with appropriate spacing and titles, but put files first
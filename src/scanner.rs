@@ -2,27 +2,48 @@
  * Directory and file scanning functionality
  */
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Read};
+use std::io::Read;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-use glob_match::glob_match;
-use ignore::{DirEntry as IgnoreDirEntry, WalkBuilder};
+use encoding_rs::Encoding;
+use ignore::WalkBuilder;
 use indicatif::ProgressBar;
 use rayon::prelude::*;
-use walkdir::{DirEntry, WalkDir};
+use walkdir::WalkDir;
 
-use crate::config::Config;
+use crate::archive::{self, ArchiveEntry, ArchiveKind};
+use crate::binary::Summarizer;
+use crate::config::{Config, DEFAULT_MAX_DETECT_SIZE, DEFAULT_MAX_FILE_SIZE};
+use crate::encoding;
 use crate::error::{DumpFsError, Result, ResultExt};
-use crate::types::{BinaryNode, DirectoryNode, FileNode, FileType, Metadata, Node, SymlinkNode};
-use crate::utils::{format_file_size, DEFAULT_IGNORE};
-
-use crate::report::FileReportInfo;
+use crate::git::{ChangeSet, FileChange, FileVersion};
+use crate::index::{IndexEntry, ScanIndex};
+use crate::notebook;
+use crate::order::SortKey;
+use crate::pattern::{split_line_range, PatternSet};
+use crate::redact::Redactor;
+use crate::split;
+use crate::stream::{self, NodeVisitor, Pinning, Pruning, TreeBuilder};
+use crate::types::{
+    BinaryNode, BinarySummary, DirectoryNode, FileNode, FileType, LineRange, Metadata, Node,
+    NotebookCell, SymlinkNode,
+};
+use crate::utils::{format_file_size, source_date_epoch, DEFAULT_IGNORE};
+
+use crate::report::{FileReportInfo, SkipReason, SkippedFile};
 use crate::tokenizer::{create_tokenizer, get_global_cache_stats, Tokenizer};
 
+/// Files processed in parallel per thread before their nodes are passed on
+const FILES_PER_THREAD: usize = 8;
+
+/// Absolute and relative path of a directory entry
+type EntryPaths = (PathBuf, PathBuf);
+
 /// Scanner statistics
 #[derive(Debug, Clone, Default)]
 pub struct ScannerStatistics {
@@ -40,6 +61,32 @@ pub struct ScannerStatistics {
     pub token_cache_hits: Option<usize>,
     /// Token cache misses (if tokenizer caching is enabled)
     pub token_cache_misses: Option<usize>,
+    /// Files reused from the scan index (if the index is enabled)
+    pub index_hits: Option<usize>,
+    /// Files not found or changed in the scan index (if the index is enabled)
+    pub index_misses: Option<usize>,
+    /// Path of the scanned directory, as file paths in `file_details` start
+    pub root: String,
+    /// Files and directories left out of the output, and why
+    pub skipped: HashMap<String, SkippedFile>,
+}
+
+/// Content read from a text file
+struct FileContent {
+    /// Text to include in the output
+    text: Option<String>,
+    /// Number of tokens in the text (if tokenizer is enabled)
+    tokens: Option<usize>,
+    /// Statistics recorded for the file (None if it could not be read)
+    info: Option<FileReportInfo>,
+    /// Lines of the file in `text` (None for the whole file)
+    line_range: Option<LineRange>,
+    /// Whether lines of the text were omitted, leaving a marker in their place
+    truncated: bool,
+    /// Encoding the text was converted from (None for UTF-8)
+    encoding: Option<String>,
+    /// Cells of a flattened notebook
+    cells: Option<Vec<NotebookCell>>,
 }
 
 /// Scanner for directory contents
@@ -51,7 +98,23 @@ pub struct Scanner {
     /// Scanner statistics
     statistics: Arc<Mutex<ScannerStatistics>>,
     /// Tokenizer (if enabled)
-    tokenizer: Option<Box<dyn Tokenizer>>,
+    tokenizer: Option<Arc<dyn Tokenizer>>,
+    /// Secret redactor (if enabled)
+    redactor: Option<Redactor>,
+    /// Binary file summarizer (if enabled)
+    summarizer: Option<Summarizer>,
+    /// Index of previously scanned files (if enabled)
+    index: Option<ScanIndex>,
+    /// Directory that include and ignore patterns are relative to
+    root: PathBuf,
+    /// Default and custom ignore patterns
+    ignore: PatternSet,
+    /// Include patterns (empty to include everything)
+    include: PatternSet,
+    /// Include patterns with a line range, and their range
+    line_ranges: Vec<(PatternSet, LineRange)>,
+    /// Encoding of 8-bit text that is not UTF-8
+    fallback_encoding: &'static Encoding,
 }
 
 impl Scanner {
@@ -63,7 +126,7 @@ impl Scanner {
             match create_tokenizer(model, &project_dir) {
                 Ok(t) => {
                     progress.set_message(format!("Using tokenizer for model: {model:?}"));
-                    Some(t)
+                    Some(Arc::from(t))
                 }
                 Err(e) => {
                     eprintln!("Error creating tokenizer: {}", e);
@@ -74,11 +137,104 @@ impl Scanner {
             None
         };
 
+        Self::with_tokenizer(config, progress, tokenizer)
+    }
+
+    /// Create a new scanner that counts tokens with an existing tokenizer
+    ///
+    /// Sharing a tokenizer keeps its token cache warm across scans.
+    pub fn with_tokenizer(
+        config: Config,
+        progress: Arc<ProgressBar>,
+        tokenizer: Option<Arc<dyn Tokenizer>>,
+    ) -> Self {
+        // Patterns are checked by `Config::validate`; fall back to the built-in rules
+        let redactor = config.redact.then(|| {
+            Redactor::new(&config.redact_patterns).unwrap_or_else(|e| {
+                eprintln!("Invalid redaction pattern: {}", e);
+                Redactor::default()
+            })
+        });
+
+        let summarizer = config
+            .binary_summary
+            .then(|| Summarizer::new(config.binary_excerpt, config.binary_excerpt_size));
+
+        let index = if config.index {
+            ScanIndex::for_config(&config)
+        } else {
+            None
+        };
+
+        // A single file target is matched relative to its directory
+        let root = fs::canonicalize(&config.target_dir).unwrap_or(config.target_dir.clone());
+        let root = match root.is_file() {
+            true => root.parent().map(Path::to_path_buf).unwrap_or(root),
+            false => root,
+        };
+
+        // Patterns are checked by `Config::validate`; fall back to matching nothing
+        // Expanded archives and their contents are exempt from the defaults, but not
+        // from custom patterns
+        let default_ignore = DEFAULT_IGNORE.iter().map(|pattern| pattern.to_string());
+        let archives = match config.expand_archives {
+            true => archive::PATTERNS,
+            false => &[],
+        };
+        let archive_exceptions = archives.iter().flat_map(|pattern| {
+            [
+                format!("!{}", pattern),
+                format!("!{}{}", pattern, archive::SEPARATOR),
+            ]
+        });
+        let custom_ignore = config.ignore_patterns.iter().cloned();
+        let ignore_patterns = default_ignore
+            .chain(archive_exceptions)
+            .chain(custom_ignore);
+        let ignore = PatternSet::new(ignore_patterns).unwrap_or_else(|e| {
+            eprintln!("Invalid ignore pattern: {}", e);
+            PatternSet::default()
+        });
+        let mut include_patterns = Vec::new();
+        let mut line_ranges = Vec::new();
+        for spec in &config.include_patterns {
+            match split_line_range(spec) {
+                Ok((pattern, range)) => {
+                    include_patterns.push(pattern);
+                    if let (Some(range), Ok(set)) = (range, PatternSet::new([pattern])) {
+                        line_ranges.push((set, range));
+                    }
+                }
+                Err(e) => eprintln!("Invalid include pattern: {}", e),
+            }
+        }
+        let include = PatternSet::new(include_patterns).unwrap_or_else(|e| {
+            eprintln!("Invalid include pattern: {}", e);
+            PatternSet::default()
+        });
+
+        // The label is checked by `Config::validate`; fall back to the default
+        let fallback_encoding = match &config.fallback_encoding {
+            Some(label) => encoding::for_label(label).unwrap_or_else(|e| {
+                eprintln!("Invalid fallback encoding: {}", e);
+                encoding::DEFAULT_FALLBACK
+            }),
+            None => encoding::DEFAULT_FALLBACK,
+        };
+
         Self {
             config,
             progress,
             statistics: Arc::new(Mutex::new(ScannerStatistics::default())),
             tokenizer,
+            redactor,
+            summarizer,
+            index,
+            root,
+            ignore,
+            include,
+            line_ranges,
+            fallback_encoding,
         }
     }
 
@@ -145,11 +301,117 @@ impl Scanner {
             stats.token_cache_misses = Some(cache_stats.misses);
         }
 
+        if let Some(index) = &self.index {
+            let (hits, misses) = index.stats();
+            stats.index_hits = Some(hits);
+            stats.index_misses = Some(misses);
+        }
+
+        // A single file target is reported below its directory
+        let target = fs::canonicalize(&self.config.target_dir)
+            .unwrap_or_else(|_| self.config.target_dir.clone());
+        let root = match target.is_file() {
+            true => target.parent().map(Path::to_path_buf).unwrap_or(target),
+            false => target,
+        };
+        stats.root = self.get_normalized_path_for_reporting(&root);
+
         Ok(stats)
     }
 
     /// Scan the target directory and return the directory tree
     pub fn scan(&self) -> Result<DirectoryNode> {
+        let mut tree = TreeBuilder::default();
+        self.scan_into(&mut tree)?;
+        tree.into_root()
+            .ok_or_else(|| DumpFsError::Scanner("Scan produced no directory".to_string()))
+    }
+
+    /// Scan the target directory, passing nodes to `visitor` as they are processed
+    ///
+    /// Nodes arrive in the order of the tree `scan` returns, and only a batch
+    /// of files is held in memory at a time. In diff mode the changed files
+    /// are collected first and then passed on.
+    pub fn scan_into(&self, visitor: &mut dyn NodeVisitor) -> Result<()> {
+        self.scan_target(visitor)?;
+        self.save_index();
+        Ok(())
+    }
+
+    /// Update a previously scanned tree after the given paths changed
+    ///
+    /// Only the directories containing changed paths are scanned again, and
+    /// their nodes replaced in `root`. A changed path whose directory is not
+    /// in the tree yet rescans the nearest ancestor that is.
+    pub fn rescan(&self, root: &mut DirectoryNode, changed: &[PathBuf]) -> Result<()> {
+        let abs_root = fs::canonicalize(&self.config.target_dir).with_context(|| {
+            format!(
+                "Failed to canonicalize path: {}",
+                self.config.target_dir.display()
+            )
+        })?;
+
+        // A single file target is cheap to scan again as a whole
+        if !abs_root.is_dir() {
+            *root = self.scan()?;
+            return Ok(());
+        }
+
+        let mut dirs = BTreeSet::new();
+        for path in changed {
+            let Ok(rel) = path.strip_prefix(&abs_root) else {
+                continue;
+            };
+            let mut dir = rel.parent().unwrap_or(Path::new("")).to_path_buf();
+            while !(abs_root.join(&dir).is_dir() && find_directory(root, &dir).is_some()) {
+                dir = dir.parent().unwrap_or(Path::new("")).to_path_buf();
+            }
+            dirs.insert(dir);
+        }
+
+        // Directories are sorted, so ancestors come before their descendants
+        let mut scanned: Vec<PathBuf> = Vec::new();
+        for dir in dirs {
+            if scanned.iter().any(|parent| dir.starts_with(parent)) {
+                continue;
+            }
+
+            let rel_path = match dir.as_os_str().is_empty() {
+                true => root.path.clone(),
+                false => root.path.join(&dir),
+            };
+            let mut node = self.scan_directory(&abs_root.join(&dir), &rel_path)?;
+            if let Some(existing) = find_directory(root, &dir) {
+                // Keep the name the tree was built with (the repository name at the root)
+                node.name = std::mem::take(&mut existing.name);
+                *existing = node;
+            }
+            scanned.push(dir);
+        }
+
+        self.save_index();
+        Ok(())
+    }
+
+    /// Save the scan index; failing to do so only makes the next scan slower
+    fn save_index(&self) {
+        if let Some(index) = &self.index {
+            if let Err(e) = index.save() {
+                eprintln!("Error saving scan index: {}", e);
+            }
+        }
+    }
+
+    /// Modification time all nodes get in reproducible output
+    fn pinned_time(&self) -> Option<SystemTime> {
+        self.config
+            .reproducible
+            .then(|| source_date_epoch().unwrap_or(SystemTime::UNIX_EPOCH))
+    }
+
+    /// Scan the target directory, a single file or the changed files
+    fn scan_target(&self, visitor: &mut dyn NodeVisitor) -> Result<()> {
+        let visitor = &mut Pinning::new(visitor, self.pinned_time());
         let abs_path = fs::canonicalize(&self.config.target_dir).with_context(|| {
             format!(
                 "Failed to canonicalize path: {}",
@@ -157,6 +419,16 @@ impl Scanner {
             )
         })?;
 
+        // A single file (from a blob URL) is scanned as its directory holding only that file
+        let (abs_path, single_file) = if abs_path.is_file() {
+            let parent = abs_path.parent().map(Path::to_path_buf).ok_or_else(|| {
+                DumpFsError::PathNotFound(format!("No parent directory: {}", abs_path.display()))
+            })?;
+            (parent, Some(abs_path))
+        } else {
+            (abs_path, None)
+        };
+
         // Determine the base directory name and path
         let dir_name = if let Some(repo_info) = &self.config.git_repo {
             // For git repos, use the repo name
@@ -178,18 +450,128 @@ impl Scanner {
         // Create the initial relative path
         let rel_path = PathBuf::from(&dir_name);
 
-        self.scan_directory(&abs_path, &rel_path)
+        if let Some(file_path) = single_file {
+            let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+            let node = self.process_file(&file_path, &rel_path.join(&*file_name))?;
+            let root = DirectoryNode {
+                name: dir_name,
+                path: rel_path,
+                metadata: self.get_metadata(&abs_path)?,
+                contents: vec![node],
+            };
+            return Ok(stream::replay(root, visitor)?);
+        }
+
+        // In diff mode only the changed files are scanned
+        if let Some(changes) = &self.config.changes {
+            let mut root = self.scan_changes(&abs_path, &rel_path, changes)?;
+            if let Some(order) = self.config.sort {
+                order.sort_tree(&mut root);
+            }
+            return Ok(stream::replay(root, visitor)?);
+        }
+
+        let root = self.open_directory(&abs_path, &rel_path)?;
+        let mut pruning = Pruning::new(visitor);
+        pruning.enter_directory(root, false)?;
+        self.walk_directory(&abs_path, &rel_path, &mut pruning)
     }
 
     /// Scan a directory and return its node representation
     fn scan_directory(&self, abs_path: &Path, rel_path: &Path) -> Result<DirectoryNode> {
+        let mut tree = TreeBuilder::default();
+        let mut pinning = Pinning::new(&mut tree, self.pinned_time());
+        let mut pruning = Pruning::new(&mut pinning);
+        pruning.enter_directory(self.open_directory(abs_path, rel_path)?, false)?;
+        self.walk_directory(abs_path, rel_path, &mut pruning)?;
+        tree.into_root()
+            .ok_or_else(|| DumpFsError::Scanner("Scan produced no directory".to_string()))
+    }
+
+    /// Node for a directory, without its contents
+    fn open_directory(&self, abs_path: &Path, rel_path: &Path) -> Result<DirectoryNode> {
         let metadata = self.get_metadata(abs_path).with_context(|| {
             format!(
                 "Failed to get metadata for directory: {}",
                 abs_path.display()
             )
         })?;
-        let mut contents = Vec::new();
+        let name = abs_path
+            .file_name()
+            .ok_or_else(|| {
+                DumpFsError::PathNotFound(format!("No file name in path: {}", abs_path.display()))
+            })?
+            .to_string_lossy()
+            .to_string();
+
+        Ok(DirectoryNode {
+            name,
+            path: rel_path.to_path_buf(),
+            metadata,
+            contents: Vec::new(),
+        })
+    }
+
+    /// Pass the contents of an entered directory to the visitor, then leave it
+    ///
+    /// Subdirectories come first, then files, which are processed in parallel
+    /// batches so that only one batch of contents is held in memory.
+    fn walk_directory(
+        &self,
+        abs_path: &Path,
+        rel_path: &Path,
+        visitor: &mut Pruning,
+    ) -> Result<()> {
+        let (dirs, files) = self.list_directory(abs_path, rel_path);
+
+        // Directories left empty by include patterns are dropped
+        let prunable = !self.include.is_empty();
+        for (entry_path, new_rel_path) in dirs {
+            match self.open_directory(&entry_path, &new_rel_path) {
+                Ok(dir_node) => {
+                    visitor.enter_directory(dir_node, prunable)?;
+                    self.walk_directory(&entry_path, &new_rel_path, visitor)?;
+                }
+                Err(e) => {
+                    eprintln!("Error processing directory {}: {}", entry_path.display(), e);
+                    self.skip(&entry_path, SkipReason::Error, Some(e.to_string()));
+                }
+            }
+        }
+
+        let batch_size = rayon::current_num_threads() * FILES_PER_THREAD;
+        for batch in files.chunks(batch_size) {
+            let file_nodes: Vec<Node> = batch
+                .par_iter()
+                .filter_map(|(entry_path, new_rel_path)| {
+                    match self.process_file(entry_path, new_rel_path) {
+                        Ok(Node::Directory(dir)) if self.is_pruned(&dir) => None,
+                        Ok(node) => Some(node),
+                        Err(e) => {
+                            eprintln!("Error processing {}: {}", entry_path.display(), e);
+                            self.skip(entry_path, SkipReason::Error, Some(e.to_string()));
+                            None
+                        }
+                    }
+                })
+                .collect();
+            for node in file_nodes {
+                visitor.visit(node)?;
+            }
+        }
+
+        Ok(visitor.leave_directory()?)
+    }
+
+    /// Absolute and relative paths of the directories and files in a directory
+    /// that pass the ignore and include filters
+    fn list_directory(
+        &self,
+        abs_path: &Path,
+        rel_path: &Path,
+    ) -> (Vec<EntryPaths>, Vec<EntryPaths>) {
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
 
         // Determine which entries to process based on whether we're using gitignore
         if self.config.respect_gitignore {
@@ -203,138 +585,51 @@ impl Scanner {
             }
 
             // Get all entries using the ignore walker
-            let entries: Vec<IgnoreDirEntry> = walker
+            let entries = walker
                 .build()
                 .filter_map(|entry_result| entry_result.ok()) // Use closure instead of Result::ok to resolve type issues
                 .filter(|e| e.path() != abs_path) // Skip the root directory itself
-                .filter(|e| !self.should_ignore(e.path()))
-                .filter(|e| self.should_include(e.path()))
-                .collect();
-
-            // Split into directories and files
-            let (dirs, files): (Vec<_>, Vec<_>) =
-                entries.into_iter().partition(|e| e.path().is_dir());
+                .filter(|e| self.accepts(e.path()));
 
-            // Process directories first (sequential)
-            for entry in dirs {
+            for entry in entries {
                 let entry_path = entry.path();
-                // Use normalize_path to get the correct relative path
-                let normalized_path = self.normalize_path(entry_path);
-                let new_rel_path = if normalized_path.components().count() > 0 {
-                    // If we have a normalized path, use it
-                    normalized_path
-                } else {
-                    // Otherwise, just join with the entry name
-                    let entry_name = entry_path
-                        .file_name()
-                        .ok_or_else(|| {
-                            DumpFsError::PathNotFound(format!(
-                                "No file name in path: {}",
-                                entry_path.display()
-                            ))
-                        })?
-                        .to_string_lossy()
-                        .to_string();
-                    rel_path.join(&entry_name)
+                let Some(entry_name) = entry_path.file_name() else {
+                    eprintln!("Error: No file name in path: {}", entry_path.display());
+                    continue;
                 };
+                let entry_name = entry_name.to_string_lossy().to_string();
 
-                match self.scan_directory(entry_path, &new_rel_path) {
-                    Ok(dir_node) => contents.push(Node::Directory(dir_node)),
-                    Err(e) => {
-                        eprintln!("Error processing directory {}: {}", entry_path.display(), e)
-                    }
+                if entry_path.is_dir() {
+                    dirs.push((entry_path.to_path_buf(), rel_path.join(&entry_name)));
+                } else {
+                    files.push((entry_path.to_path_buf(), rel_path.join(&entry_name)));
                 }
             }
-
-            // Process files in parallel
-            let file_nodes: Vec<Node> = files
-                .par_iter()
-                .filter_map(|entry: &IgnoreDirEntry| {
-                    let entry_path = entry.path();
-                    let entry_name = match entry_path.file_name() {
-                        Some(name) => name.to_string_lossy().to_string(),
-                        None => {
-                            eprintln!("Error: No file name in path: {}", entry_path.display());
-                            return None;
-                        }
-                    };
-                    let new_rel_path = rel_path.join(&entry_name);
-
-                    match self.process_file(entry_path, &new_rel_path) {
-                        Ok(node) => Some(node),
-                        Err(e) => {
-                            eprintln!("Error processing {}: {}", entry_path.display(), e);
-                            None
-                        }
-                    }
-                })
-                .collect();
-
-            contents.extend(file_nodes);
         } else {
             // Use traditional walkdir approach when not respecting .gitignore
-            let entries: Vec<DirEntry> = WalkDir::new(abs_path)
+            let entries = WalkDir::new(abs_path)
                 .max_depth(1)
                 .min_depth(1)
                 .into_iter()
                 .filter_map(|entry_result| entry_result.ok()) // Use closure instead of Result::ok to resolve type issues
-                .filter(|e| !self.should_ignore(e.path()))
-                .filter(|e| self.should_include(e.path()))
-                .collect();
-
-            // Split into directories and files
-            let (dirs, files): (Vec<_>, Vec<_>) =
-                entries.into_iter().partition(|e| e.file_type().is_dir());
+                .filter(|e| self.accepts(e.path()));
 
-            // Process directories first (sequential)
-            for entry in dirs {
+            for entry in entries {
                 let entry_name = entry.file_name().to_string_lossy().to_string();
                 let new_rel_path = rel_path.join(&entry_name);
-
-                match self.scan_directory(entry.path(), &new_rel_path) {
-                    Ok(dir_node) => contents.push(Node::Directory(dir_node)),
-                    Err(e) => eprintln!(
-                        "Error processing directory {}: {}",
-                        entry.path().display(),
-                        e
-                    ),
+                match entry.file_type().is_dir() {
+                    true => dirs.push((entry.path().to_path_buf(), new_rel_path)),
+                    false => files.push((entry.path().to_path_buf(), new_rel_path)),
                 }
             }
-
-            // Process files in parallel
-            let file_nodes: Vec<Node> = files
-                .par_iter()
-                .filter_map(|entry: &DirEntry| {
-                    let entry_name = entry.file_name().to_string_lossy().to_string();
-                    let new_rel_path = rel_path.join(&entry_name);
-
-                    match self.process_file(entry.path(), &new_rel_path) {
-                        Ok(node) => Some(node),
-                        Err(e) => {
-                            eprintln!("Error processing {}: {}", entry.path().display(), e);
-                            None
-                        }
-                    }
-                })
-                .collect();
-
-            contents.extend(file_nodes);
         }
 
-        let name = abs_path
-            .file_name()
-            .ok_or_else(|| {
-                DumpFsError::PathNotFound(format!("No file name in path: {}", abs_path.display()))
-            })?
-            .to_string_lossy()
-            .to_string();
+        if let Some(order) = self.config.sort {
+            order.sort(&mut dirs, |(abs, _)| SortKey::of_path(abs, order));
+            order.sort(&mut files, |(abs, _)| SortKey::of_path(abs, order));
+        }
 
-        Ok(DirectoryNode {
-            name,
-            path: rel_path.to_path_buf(),
-            metadata,
-            contents,
-        })
+        (dirs, files)
     }
 
     /// Process a single file and return its node representation
@@ -370,13 +665,30 @@ impl Scanner {
 
         self.progress.set_message(progress_message);
 
-        let file_type = self
-            .get_file_type(abs_path)
-            .with_context(|| format!("Failed to determine file type for {}", abs_path.display()))?;
-        let metadata = self
+        // Expanded archives become directories; unreadable ones are kept as files
+        if let Some(kind) = self.archive_kind(abs_path) {
+            match self.scan_archive(abs_path, rel_path, kind) {
+                Ok(dir) => return Ok(Node::Directory(dir)),
+                Err(e) => eprintln!("Error expanding archive {}: {}", abs_path.display(), e),
+            }
+        }
+
+        let mut metadata = self
             .get_metadata(abs_path)
             .with_context(|| format!("Failed to get metadata for {}", abs_path.display()))?;
 
+        // Unchanged files are taken from the scan index without reading them
+        let cached = self
+            .index
+            .as_ref()
+            .and_then(|index| index.get(abs_path, &metadata));
+        let file_type = match &cached {
+            Some(entry) => entry.file_type.clone(),
+            None => self.get_file_type(abs_path).with_context(|| {
+                format!("Failed to determine file type for {}", abs_path.display())
+            })?,
+        };
+
         // Use the normalized path for reporting
         let file_path = if let Some(repo_info) = &self.config.git_repo {
             // For repositories, use the format owner/repo/path
@@ -393,37 +705,56 @@ impl Scanner {
 
         match file_type {
             FileType::TextFile => {
+                // Content is always read again; the index only saves counting its tokens
                 let content = self
-                    .read_file_content(abs_path)
+                    .read_file_content(abs_path, cached.as_ref())
                     .with_context(|| format!("Failed to read content of {}", abs_path.display()))?;
+                // The index stores the encoding along with the statistics
+                metadata.encoding = content.encoding;
+                if cached.is_none() {
+                    if let Some(info) = &content.info {
+                        self.remember(abs_path, &metadata, FileType::TextFile, info.clone(), None);
+                    }
+                }
                 Ok(Node::File(FileNode {
                     name: file_name,
                     path: rel_path.to_path_buf(),
                     metadata,
-                    content,
+                    content: content.text,
+                    tokens: content.tokens,
+                    diff: None,
+                    line_range: content.line_range,
+                    truncated: content.truncated,
+                    cells: content.cells,
                 }))
             }
             FileType::BinaryFile => {
                 // Update statistics for binary files
-                {
-                    let mut stats = self.statistics.lock().map_err(|_| {
-                        DumpFsError::Unexpected("Failed to acquire lock on statistics".to_string())
-                    })?;
-                    stats.files_processed += 1;
-                    stats.file_details.insert(
-                        file_path,
-                        FileReportInfo {
-                            lines: 0,
-                            chars: 0,
-                            tokens: None,
-                        },
-                    );
-                }
+                self.record(file_path, FileReportInfo::default())?;
+                // Unchanged files keep their summary, so they are not hashed again
+                let summary = match cached {
+                    Some(entry) => entry.summary,
+                    None => {
+                        let summary = self.summarize_binary(abs_path);
+                        // A file that could not be read is summarized again next time
+                        if summary.is_some() || self.summarizer.is_none() {
+                            self.remember(
+                                abs_path,
+                                &metadata,
+                                FileType::BinaryFile,
+                                FileReportInfo::default(),
+                                summary.clone(),
+                            );
+                        }
+                        summary
+                    }
+                };
 
                 Ok(Node::Binary(BinaryNode {
                     name: file_name,
                     path: rel_path.to_path_buf(),
                     metadata,
+                    summary,
                 }))
             }
             FileType::Symlink => {
@@ -446,6 +777,8 @@ impl Scanner {
                             lines: 0,
                             chars: target.chars().count(),
                             tokens: None,
+                            redactions: 0,
+                            truncated: false,
                         },
                     );
                 }
@@ -464,53 +797,216 @@ impl Scanner {
         }
     }
 
-    /// Check if a file should be ignored based on patterns and defaults
-    pub fn should_ignore(&self, path: &Path) -> bool {
-        let file_name = match path.file_name() {
-            Some(name) => name.to_string_lossy(),
-            None => return true, // If there's no filename, ignore it
+    /// Scan an archive as a directory of the files it contains
+    ///
+    /// Entries are filtered and processed like files on disk, at paths such as
+    /// `bundle.zip!/src/main.rs`. They are not kept in the scan index.
+    fn scan_archive(
+        &self,
+        abs_path: &Path,
+        rel_path: &Path,
+        kind: ArchiveKind,
+    ) -> Result<DirectoryNode> {
+        let metadata = self
+            .get_metadata(abs_path)
+            .with_context(|| format!("Failed to get metadata for {}", abs_path.display()))?;
+        let max_size = self
+            .config
+            .max_detect_size
+            .unwrap_or(DEFAULT_MAX_DETECT_SIZE);
+        let abs_contents = archive::contents_path(abs_path);
+        let rel_contents = archive::contents_path(rel_path);
+        let entries =
+            archive::read_entries(abs_path, kind, archive::Limits::new(max_size), |path| {
+                self.accepts(&abs_contents.join(path))
+            })
+            .with_context(|| format!("Failed to read archive {}", abs_path.display()))?;
+
+        let file_nodes: Vec<(PathBuf, Node)> = entries
+            .into_par_iter()
+            .filter_map(|entry| {
+                let sub_path = entry.path.clone();
+                let path = abs_contents.join(&sub_path);
+                match self.process_archive_entry(
+                    &path,
+                    &rel_contents.join(&sub_path),
+                    entry,
+                    &metadata,
+                ) {
+                    Ok(node) => Some((sub_path, node)),
+                    Err(e) => {
+                        eprintln!("Error processing {}: {}", path.display(), e);
+                        self.skip(&path, SkipReason::Error, Some(e.to_string()));
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let mut root = DirectoryNode {
+            name: abs_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: rel_contents,
+            metadata: metadata.clone(),
+            contents: Vec::new(),
         };
 
-        // Check custom ignore patterns
-        for pattern in &self.config.ignore_patterns {
-            if glob_match(pattern, &file_name) {
-                return true;
-            }
+        // Directories inside the archive take the metadata of the archive
+        for (sub_path, node) in file_nodes {
+            insert_nested(&mut root, &sub_path, node, |_| metadata.clone());
+        }
+        if let Some(order) = self.config.sort {
+            order.sort_tree(&mut root);
         }
 
-        // Check default ignore patterns
-        if DEFAULT_IGNORE.iter().any(|&p| p == file_name) {
-            return true;
+        Ok(root)
+    }
+
+    /// Process a file read from an archive
+    ///
+    /// `path` is the virtual path of the entry below the archive on disk.
+    fn process_archive_entry(
+        &self,
+        path: &Path,
+        rel_path: &Path,
+        entry: ArchiveEntry,
+        archive: &Metadata,
+    ) -> Result<Node> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let metadata = Metadata {
+            size: entry.size,
+            modified: entry.modified.unwrap_or(archive.modified),
+            permissions: match entry.mode {
+                Some(mode) => format!("{:o}", mode & 0o777),
+                None => archive.permissions.clone(),
+            },
+            encoding: None,
+        };
+        let file_path = self.get_normalized_path_for_reporting(path);
+
+        // Entries too large to detect are binary, as on disk
+        let Some(data) = entry.data else {
+            self.record(file_path, FileReportInfo::default())?;
+            return Ok(Node::Binary(BinaryNode {
+                name,
+                path: rel_path.to_path_buf(),
+                metadata,
+                summary: None,
+            }));
+        };
+
+        // Large text is left out, unless only some of its lines were requested
+        let max_file_size = self.config.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+        let sample = &data[..data.len().min(encoding::SAMPLE_SIZE)];
+        if entry.size > max_file_size
+            && self.line_range(path).is_none()
+            && !notebook::is_notebook(path)
+            && encoding::detect(sample, false, self.fallback_encoding).is_some()
+        {
+            self.record(file_path, FileReportInfo::default())?;
+            return Ok(Node::File(FileNode {
+                name,
+                path: rel_path.to_path_buf(),
+                metadata,
+                content: Some(format!(
+                    "File too large to include content. Size: {}",
+                    format_file_size(entry.size)
+                )),
+                tokens: None,
+                diff: None,
+                line_range: None,
+                truncated: false,
+                cells: None,
+            }));
         }
 
-        // Don't process the output file itself
-        if path.ends_with(&self.config.output_file) {
+        let node = self.process_data(path, rel_path, name, metadata, &data)?;
+        if let Node::Binary(_) = node {
+            self.record(file_path, FileReportInfo::default())?;
+        }
+        Ok(node)
+    }
+
+    /// Check if a file should be ignored based on patterns and defaults
+    pub fn should_ignore(&self, path: &Path) -> bool {
+        if self.is_own_output(path) {
             return true;
         }
 
-        false
+        match self.pattern_path(path) {
+            Some(rel_path) => self.ignore.matches(&rel_path, path.is_dir()),
+            None => true, // If there's no filename, ignore it
+        }
+    }
+
+    /// Check if a file is the output file, one of its numbered parts, or the report file
+    fn is_own_output(&self, path: &Path) -> bool {
+        path.ends_with(&self.config.output_file)
+            || split::is_part_path(&self.config.output_file, path)
+            || self
+                .config
+                .report
+                .as_ref()
+                .is_some_and(|r| path.ends_with(r))
     }
 
     /// Check if a file should be included based on patterns
     pub fn should_include(&self, path: &Path) -> bool {
         // If no include patterns, include everything
-        if self.config.include_patterns.is_empty() {
+        if self.include.is_empty() {
             return true;
         }
 
-        let file_name = match path.file_name() {
-            Some(name) => name.to_string_lossy(),
-            None => return false, // If there's no filename, don't include it
-        };
+        // Directories and expanded archives are always entered; the patterns select
+        // the files below them
+        if path.is_dir() || self.archive_kind(path).is_some() {
+            return true;
+        }
 
-        // Check against include patterns
-        for pattern in &self.config.include_patterns {
-            if glob_match(pattern, &file_name) {
-                return true;
-            }
+        match self.pattern_path(path) {
+            Some(rel_path) => self.include.matches(&rel_path, false),
+            None => false, // If there's no filename, don't include it
         }
+    }
+
+    /// Path that patterns are matched against: relative to the scan root, or the file name
+    fn pattern_path(&self, path: &Path) -> Option<PathBuf> {
+        path.strip_prefix(&self.root)
+            .or_else(|_| path.strip_prefix(&self.config.target_dir))
+            .ok()
+            .filter(|rel_path| !rel_path.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .or_else(|| path.file_name().map(PathBuf::from))
+    }
+
+    /// Lines selected by the first include pattern with a range that matches a file
+    fn line_range(&self, path: &Path) -> Option<LineRange> {
+        if self.line_ranges.is_empty() {
+            return None;
+        }
+        let rel_path = self.pattern_path(path)?;
+        self.line_ranges
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&rel_path, false))
+            .map(|(_, range)| *range)
+    }
+
+    /// Kind of archive a file is, if archives are expanded
+    fn archive_kind(&self, path: &Path) -> Option<ArchiveKind> {
+        self.config
+            .expand_archives
+            .then(|| ArchiveKind::from_path(path))
+            .flatten()
+    }
 
-        false
+    /// Whether a scanned directory is dropped because include patterns left it empty
+    fn is_pruned(&self, dir: &DirectoryNode) -> bool {
+        !self.include.is_empty() && dir.contents.is_empty()
     }
 
     /// Determine the type of a file
@@ -532,33 +1028,23 @@ impl Scanner {
 
         if metadata.is_file() {
             // For smaller files, try to detect if they're text
-            if metadata.len() < 8_000_000 {
-                // Read a sample of the file to determine type
-                let mut buffer = vec![0; std::cmp::min(8192, metadata.len() as usize)];
-                if !buffer.is_empty() {
-                    let mut file = File::open(path).with_context(|| {
-                        format!("Failed to open file for type detection: {}", path.display())
-                    })?;
-                    let bytes_read = file.read(&mut buffer).with_context(|| {
-                        format!(
-                            "Failed to read from file for type detection: {}",
-                            path.display()
-                        )
-                    })?;
-                    buffer.truncate(bytes_read);
-
-                    // Simple heuristic for text files: check for valid UTF-8 and high text-to-binary ratio
-                    if String::from_utf8(buffer.clone()).is_ok() {
-                        // Count binary characters (0x00-0x08, 0x0E-0x1F)
-                        let binary_count = buffer
-                            .iter()
-                            .filter(|&&b| (b < 9) || (b > 13 && b < 32))
-                            .count();
-                        let binary_ratio = binary_count as f32 / buffer.len() as f32;
-
-                        if binary_ratio < 0.1 {
-                            return Ok(FileType::TextFile);
-                        }
+            if metadata.len()
+                < self
+                    .config
+                    .max_detect_size
+                    .unwrap_or(DEFAULT_MAX_DETECT_SIZE)
+            {
+                // Read a sample of the file and check that it decodes to text
+                if metadata.len() > 0 {
+                    let encoding = encoding::detect_file(path, self.fallback_encoding)
+                        .with_context(|| {
+                            format!(
+                                "Failed to read from file for type detection: {}",
+                                path.display()
+                            )
+                        })?;
+                    if encoding.is_some() {
+                        return Ok(FileType::TextFile);
                     }
                 }
             }
@@ -583,11 +1069,16 @@ impl Scanner {
             size: fs_metadata.len(),
             modified,
             permissions: format!("{:o}", fs_metadata.permissions().mode() & 0o777),
+            encoding: None,
         })
     }
 
     /// Read the content of a text file and update statistics
-    fn read_file_content(&self, path: &Path) -> Result<Option<String>> {
+    ///
+    /// For files unchanged since the last scan, `cached` holds the encoding and
+    /// token count found then, so neither is worked out again.
+    fn read_file_content(&self, path: &Path, cached: Option<&IndexEntry>) -> Result<FileContent> {
+        let known_tokens = cached.and_then(|entry| entry.info.tokens);
         let metadata = fs::metadata(path).with_context(|| {
             format!(
                 "Failed to get metadata for file content: {}",
@@ -597,107 +1088,518 @@ impl Scanner {
         // Get the normalized path for reporting
         let file_path = self.get_normalized_path_for_reporting(path);
 
-        // Skip large files
-        if metadata.len() > 1_048_576 {
-            // 1MB limit
+        // Truncate or skip large files, unless only some of their lines were requested.
+        // Notebooks are mostly outputs that flattening drops, so they are read in full.
+        let max_file_size = self.config.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE);
+        if metadata.len() > max_file_size
+            && self.line_range(path).is_none()
+            && !notebook::is_notebook(path)
+        {
+            if let Some(truncation) = &self.config.truncate {
+                let encoding = encoding::detect_file(path, self.fallback_encoding)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(self.fallback_encoding);
+                return match truncation.read(path, encoding, self.tokenizer.as_deref()) {
+                    Ok(content) => self.text_content(path, content, encoding, true, known_tokens),
+                    Err(e) => Ok(FileContent {
+                        text: Some(format!("Failed to read file content: {}", e)),
+                        tokens: None,
+                        info: None,
+                        line_range: None,
+                        truncated: false,
+                        encoding: None,
+                        cells: None,
+                    }),
+                };
+            }
+
             let message = format!(
                 "File too large to include content. Size: {}",
                 format_file_size(metadata.len())
             );
 
             // Still update statistics for skipped files
-            {
-                let mut stats = self.statistics.lock().map_err(|_| {
-                    DumpFsError::Unexpected("Failed to acquire lock on statistics".to_string())
-                })?;
-                stats.files_processed += 1;
-                stats.file_details.insert(
-                    file_path,
-                    FileReportInfo {
-                        lines: 0,
-                        chars: 0,
-                        tokens: None,
-                    },
-                );
-            }
+            self.record(file_path, FileReportInfo::default())?;
+            self.skip(
+                path,
+                SkipReason::TooLarge,
+                Some(format_file_size(metadata.len())),
+            );
 
-            return Ok(Some(message));
+            return Ok(FileContent {
+                text: Some(message),
+                tokens: None,
+                info: Some(FileReportInfo::default()),
+                line_range: None,
+                truncated: false,
+                encoding: None,
+                cells: None,
+            });
         }
 
         // Read file content
-        let mut content = String::new();
-        match File::open(path) {
-            Ok(file) => {
-                let mut line_count = 0;
-                let mut char_count = 0;
-
-                // Count lines and chars
-                let reader = BufReader::new(&file);
-                for line in reader.lines() {
-                    match line {
-                        Ok(line) => {
-                            line_count += 1;
-                            char_count += line.chars().count();
-                            // Add newline char that's stripped by lines() iterator
-                            char_count += 1;
-                        }
-                        Err(_) => break,
-                    }
-                }
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.skip(path, SkipReason::Error, Some(e.to_string()));
+                return Ok(FileContent {
+                    text: Some(format!("Failed to open file: {}", e)),
+                    tokens: None,
+                    info: None,
+                    line_range: None,
+                    truncated: false,
+                    encoding: None,
+                    cells: None,
+                });
+            }
+        };
+        let mut bytes = Vec::new();
+        if let Err(e) = file.read_to_end(&mut bytes) {
+            self.skip(path, SkipReason::Error, Some(e.to_string()));
+            return Ok(FileContent {
+                text: Some(format!("Failed to read file content: {}", e)),
+                tokens: None,
+                info: None,
+                line_range: None,
+                truncated: false,
+                encoding: None,
+                cells: None,
+            });
+        }
 
-                // Re-read file for content
-                let mut file = File::open(path).with_context(|| {
-                    format!("Failed to re-open file for content: {}", path.display())
-                })?;
-                match file.read_to_string(&mut content) {
-                    Ok(_) => {}
-                    Err(e) => return Ok(Some(format!("Failed to read file content: {}", e))),
-                }
+        // Convert to UTF-8 from whatever encoding the file uses
+        let known_encoding = cached
+            .and_then(|entry| entry.encoding.as_deref())
+            .and_then(|label| encoding::for_label(label).ok());
+        let (content, encoding) = match known_encoding {
+            Some(known) => {
+                let (text, encoding, _) = known.decode(&bytes);
+                (text.into_owned(), encoding)
+            }
+            None => encoding::decode(&bytes, self.fallback_encoding),
+        };
+        let content = self.transform(path, content);
+        self.text_content(path, content, encoding, false, known_tokens)
+    }
 
-                // Count tokens if tokenizer is enabled
-                let token_count = if let Some(tokenizer) = &self.tokenizer {
-                    match tokenizer.count_tokens(&content) {
-                        Ok(count) => Some(count.tokens),
-                        Err(e) => {
-                            eprintln!("Error counting tokens for {}: {}", path.display(), e);
-                            None
-                        }
-                    }
-                } else {
+    /// Replace content with a flattened form for file types that have one
+    ///
+    /// Notebooks that cannot be parsed are kept as they are.
+    fn transform(&self, path: &Path, content: String) -> String {
+        if !notebook::is_notebook(path) {
+            return content;
+        }
+        match notebook::flatten(&content, self.config.notebook_output_lines) {
+            Ok(flattened) => flattened,
+            Err(e) => {
+                eprintln!("Error flattening notebook {}: {}", path.display(), e);
+                content
+            }
+        }
+    }
+
+    /// Redact, count lines, characters and tokens of text content and update statistics
+    ///
+    /// Tokens are only counted if `known_tokens` does not give their number already.
+    fn text_content(
+        &self,
+        path: &Path,
+        content: String,
+        encoding: &'static Encoding,
+        truncated: bool,
+        known_tokens: Option<usize>,
+    ) -> Result<FileContent> {
+        let file_path = self.get_normalized_path_for_reporting(path);
+
+        // Redact secrets before anything is counted or written
+        let (content, redactions) = match &self.redactor {
+            Some(redactor) => redactor.redact(content),
+            None => (content, 0),
+        };
+
+        // Keep only the selected lines
+        let (content, line_range) = match self.line_range(path) {
+            Some(range) => {
+                let (content, range) = range.slice(&content);
+                (content, Some(range))
+            }
+            None => (content, None),
+        };
+
+        // Find the cells of a flattened notebook shown in full
+        let cells = match line_range {
+            None if !truncated && notebook::is_notebook(path) => notebook::cells(&content),
+            _ => None,
+        };
+
+        // Count lines and chars, including the newline that ends each line
+        let line_count = content.lines().count();
+        let char_count = content.lines().map(|line| line.chars().count() + 1).sum();
+
+        // Count tokens if tokenizer is enabled
+        let token_count = if known_tokens.is_some() {
+            known_tokens
+        } else if let Some(tokenizer) = &self.tokenizer {
+            match tokenizer.count_tokens(&content) {
+                Ok(count) => Some(count.tokens),
+                Err(e) => {
+                    eprintln!("Error counting tokens for {}: {}", path.display(), e);
                     None
-                };
+                }
+            }
+        } else {
+            None
+        };
 
-                // Update statistics
-                {
-                    let mut stats = self.statistics.lock().map_err(|_| {
-                        DumpFsError::Unexpected("Failed to acquire lock on statistics".to_string())
-                    })?;
-                    stats.files_processed += 1;
-                    stats.total_lines += line_count;
-                    stats.total_chars += char_count;
+        // Update statistics
+        let info = FileReportInfo {
+            lines: line_count,
+            chars: char_count,
+            tokens: token_count,
+            redactions,
+            truncated,
+        };
+        self.record(file_path, info.clone())?;
+
+        Ok(FileContent {
+            text: Some(content),
+            tokens: token_count,
+            info: Some(info),
+            line_range,
+            truncated,
+            encoding: encoding::label(encoding),
+            cells,
+        })
+    }
+
+    /// Summarize a binary file (None if summaries are disabled or it could not be read)
+    fn summarize_binary(&self, path: &Path) -> Option<BinarySummary> {
+        let summarizer = self.summarizer.as_ref()?;
+        match summarizer.summarize_file(path) {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                eprintln!("Error summarizing {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Add a processed file to the statistics
+    fn record(&self, file_path: String, info: FileReportInfo) -> Result<()> {
+        let mut stats = self.statistics.lock().map_err(|_| {
+            DumpFsError::Unexpected("Failed to acquire lock on statistics".to_string())
+        })?;
+        stats.files_processed += 1;
+        stats.total_lines += info.lines;
+        stats.total_chars += info.chars;
+
+        // Update token count if available
+        if let Some(tokens) = info.tokens {
+            stats.total_tokens = Some(stats.total_tokens.unwrap_or(0) + tokens);
+        }
 
-                    // Update token count if available
-                    if let Some(tokens) = token_count {
-                        stats.total_tokens = Some(stats.total_tokens.unwrap_or(0) + tokens);
+        stats.file_details.insert(file_path, info);
+        Ok(())
+    }
+
+    /// Add a file or directory left out of the output to the statistics
+    fn skip(&self, path: &Path, reason: SkipReason, detail: Option<String>) {
+        let file_path = self.get_normalized_path_for_reporting(path);
+        if let Ok(mut stats) = self.statistics.lock() {
+            stats
+                .skipped
+                .insert(file_path, SkippedFile { reason, detail });
+        }
+    }
+
+    /// Whether an entry passes the ignore and include patterns, recording it as skipped if not
+    fn accepts(&self, path: &Path) -> bool {
+        // Files written by dumpfs itself are not worth reporting
+        if self.is_own_output(path) {
+            false
+        } else if self.should_ignore(path) {
+            self.skip(path, SkipReason::Ignored, None);
+            false
+        } else if !self.should_include(path) {
+            self.skip(path, SkipReason::NotIncluded, None);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Store the type, statistics and binary summary of a processed file in the scan index
+    fn remember(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        file_type: FileType,
+        info: FileReportInfo,
+        summary: Option<BinarySummary>,
+    ) {
+        if let Some(index) = &self.index {
+            index.insert(
+                path,
+                IndexEntry {
+                    size: metadata.size,
+                    modified: metadata.modified,
+                    file_type,
+                    info,
+                    encoding: metadata.encoding.clone(),
+                    summary,
+                },
+            );
+        }
+    }
+
+    /// Build the directory tree from the files in a change set
+    ///
+    /// Only changed files below `abs_path` are included, nested under the
+    /// directories that contain them.
+    fn scan_changes(
+        &self,
+        abs_path: &Path,
+        rel_path: &Path,
+        changes: &ChangeSet,
+    ) -> Result<DirectoryNode> {
+        let metadata = self.get_metadata(abs_path).with_context(|| {
+            format!(
+                "Failed to get metadata for directory: {}",
+                abs_path.display()
+            )
+        })?;
+
+        // Select changed files below the target that pass the ignore and include filters
+        let selected: Vec<&FileChange> = changes
+            .changes
+            .range(abs_path.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(abs_path))
+            .map(|(_, change)| change)
+            .filter(|change| {
+                change
+                    .path
+                    .ancestors()
+                    .take_while(|p| *p != abs_path)
+                    .all(|p| !self.should_ignore(p))
+                    && self.should_include(&change.path)
+            })
+            .collect();
+
+        // Process files in parallel
+        let file_nodes: Vec<(PathBuf, Node)> = selected
+            .par_iter()
+            .filter_map(|change| {
+                let sub_path = change.path.strip_prefix(abs_path).ok()?;
+                match self.process_change(change, &rel_path.join(sub_path), changes.modified) {
+                    Ok(node) => node.map(|node| (sub_path.to_path_buf(), node)),
+                    Err(e) => {
+                        eprintln!("Error processing {}: {}", change.path.display(), e);
+                        None
                     }
+                }
+            })
+            .collect();
 
-                    stats.file_details.insert(
-                        file_path,
-                        FileReportInfo {
-                            lines: line_count,
-                            chars: char_count,
-                            tokens: token_count,
-                        },
-                    );
+        let mut root = DirectoryNode {
+            name: abs_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path: rel_path.to_path_buf(),
+            metadata: metadata.clone(),
+            contents: Vec::new(),
+        };
+
+        // Directories removed in the change only exist in the diff
+        for (sub_path, node) in file_nodes {
+            insert_nested(&mut root, &sub_path, node, |dir| {
+                self.get_metadata(&abs_path.join(dir))
+                    .unwrap_or_else(|_| metadata.clone())
+            });
+        }
+
+        Ok(root)
+    }
+
+    /// Process a changed file, reading it from disk or from its blob
+    fn process_change(
+        &self,
+        change: &FileChange,
+        rel_path: &Path,
+        modified: SystemTime,
+    ) -> Result<Option<Node>> {
+        let output = self.config.diff_output;
+        let name = rel_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut node = match &change.version {
+            FileVersion::Worktree => self.process_file(&change.path, rel_path)?,
+            FileVersion::Blob { data, executable } => {
+                self.progress.inc(1);
+                let metadata = Metadata {
+                    size: data.len() as u64,
+                    modified,
+                    permissions: if *executable { "755" } else { "644" }.to_string(),
+                    encoding: None,
+                };
+                self.process_data(&change.path, rel_path, name, metadata, data)?
+            }
+            // Deleted files only have a diff to show
+            FileVersion::Deleted => {
+                self.progress.inc(1);
+                if !output.diff() {
+                    return Ok(None);
                 }
+                Node::File(FileNode {
+                    name,
+                    path: rel_path.to_path_buf(),
+                    metadata: Metadata {
+                        size: 0,
+                        modified,
+                        permissions: "000".to_string(),
+                        encoding: None,
+                    },
+                    content: None,
+                    tokens: None,
+                    diff: None,
+                    line_range: None,
+                    truncated: false,
+                    cells: None,
+                })
             }
-            Err(e) => {
-                return Ok(Some(format!("Failed to open file: {}", e)));
+        };
+
+        // Redacted after the content, whose statistics replace the file's entry
+        let diff = match output.diff() {
+            true => Some(self.redact_patch(&change.path, change.patch.clone())?),
+            false => None,
+        };
+
+        if let Node::File(file) = &mut node {
+            if !output.content() {
+                file.content = None;
+                file.tokens = None;
             }
+            file.diff = diff;
+        }
+
+        Ok(Some(node))
+    }
+
+    /// Process the content of a file that is only in memory, such as a blob
+    ///
+    /// Text is counted like the content of files on disk; binary data is
+    /// summarized but not added to the statistics.
+    fn process_data(
+        &self,
+        path: &Path,
+        rel_path: &Path,
+        name: String,
+        mut metadata: Metadata,
+        data: &[u8],
+    ) -> Result<Node> {
+        if encoding::detect(data, true, self.fallback_encoding).is_none() {
+            return Ok(Node::Binary(BinaryNode {
+                name,
+                path: rel_path.to_path_buf(),
+                metadata,
+                summary: self.summarizer.as_ref().map(|s| s.summarize(data)),
+            }));
         }
 
-        Ok(Some(content))
+        let (text, encoding) = encoding::decode(data, self.fallback_encoding);
+        let text = self.transform(path, text);
+        let content = self.text_content(path, text, encoding, false, None)?;
+        metadata.encoding = content.encoding;
+        Ok(Node::File(FileNode {
+            name,
+            path: rel_path.to_path_buf(),
+            metadata,
+            content: content.text,
+            tokens: content.tokens,
+            diff: None,
+            line_range: content.line_range,
+            truncated: content.truncated,
+            cells: content.cells,
+        }))
+    }
+
+    /// Redact secrets from a patch, adding the count to the file's report
+    fn redact_patch(&self, path: &Path, patch: String) -> Result<String> {
+        let Some(redactor) = &self.redactor else {
+            return Ok(patch);
+        };
+
+        let (patch, redactions) = redactor.redact(patch);
+        if redactions > 0 {
+            let mut stats = self.statistics.lock().map_err(|_| {
+                DumpFsError::Unexpected("Failed to acquire lock on statistics".to_string())
+            })?;
+            stats
+                .file_details
+                .entry(self.get_normalized_path_for_reporting(path))
+                .or_default()
+                .redactions += redactions;
+        }
+        Ok(patch)
+    }
+}
+
+/// Add a node at `sub_path` below `root`, creating its parent directories as needed
+///
+/// New directories get their metadata from `dir_metadata`, given their path below `root`.
+fn insert_nested(
+    root: &mut DirectoryNode,
+    sub_path: &Path,
+    node: Node,
+    dir_metadata: impl Fn(&Path) -> Metadata,
+) {
+    let mut dir = root;
+    let mut dir_sub_path = PathBuf::new();
+    for component in sub_path.parent().into_iter().flat_map(|p| p.components()) {
+        let name = component.as_os_str().to_string_lossy().to_string();
+        dir_sub_path.push(&name);
+        let position = dir
+            .contents
+            .iter()
+            .position(|n| matches!(n, Node::Directory(d) if d.name == name));
+        let index = match position {
+            Some(index) => index,
+            None => {
+                dir.contents.push(Node::Directory(DirectoryNode {
+                    path: dir.path.join(&name),
+                    name,
+                    metadata: dir_metadata(&dir_sub_path),
+                    contents: Vec::new(),
+                }));
+                dir.contents.len() - 1
+            }
+        };
+        dir = match &mut dir.contents[index] {
+            Node::Directory(d) => d,
+            _ => unreachable!("position only matches directories"),
+        };
+    }
+    dir.contents.push(node);
+}
+
+/// Find the directory at `rel_path` below `root`
+fn find_directory<'a>(
+    root: &'a mut DirectoryNode,
+    rel_path: &Path,
+) -> Option<&'a mut DirectoryNode> {
+    let mut dir = root;
+    for component in rel_path.components() {
+        let name = component.as_os_str().to_string_lossy();
+        dir = dir.contents.iter_mut().find_map(|node| match node {
+            Node::Directory(child) if child.name == name => Some(child),
+            _ => None,
+        })?;
     }
+    Some(dir)
 }
 
 #[cfg(test)]
@@ -707,9 +1609,11 @@ mod tests {
 
     use indicatif::ProgressBar;
 
-    use crate::config::{Config, GitCachePolicy};
-    use crate::git::{GitHost, GitRepoInfo};
+    use crate::config::Config;
+    use crate::git::{GitCachePolicy, GitHost, GitRepoInfo};
+    use crate::index::{self, ScanIndex};
     use crate::scanner::Scanner;
+    use crate::types::Node;
 
     #[test]
     fn test_normalize_path() {
@@ -720,6 +1624,8 @@ mod tests {
             host: GitHost::GitHub,
             owner: "username".to_string(),
             name: "repo".to_string(),
+            git_ref: None,
+            subpath: None,
             cache_path: repo_path.clone(),
         };
 
@@ -737,6 +1643,7 @@ mod tests {
             git_cache_policy: GitCachePolicy::AlwaysPull,
             include_metadata: false,
             clip: false,
+            ..Default::default()
         };
 
         let scanner = Scanner::new(config, Arc::new(ProgressBar::hidden()));
@@ -772,6 +1679,8 @@ mod tests {
             host: GitHost::GitHub,
             owner: "username".to_string(),
             name: "repo".to_string(),
+            git_ref: None,
+            subpath: None,
             cache_path: repo_path.clone(),
         };
 
@@ -789,6 +1698,7 @@ mod tests {
             git_cache_policy: GitCachePolicy::AlwaysPull,
             include_metadata: false,
             clip: false,
+            ..Default::default()
         };
 
         let scanner = Scanner::new(config, Arc::new(ProgressBar::hidden()));
@@ -810,4 +1720,96 @@ mod tests {
         let other_display = scanner.get_normalized_path_for_reporting(&other_path);
         assert_eq!(other_display, "/other/path/file.txt");
     }
+
+    #[test]
+    fn test_scan_index_reuses_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(project.join("kept.txt"), "kept\n").unwrap();
+        std::fs::write(project.join("edited.txt"), "before\n").unwrap();
+        let index_path = dir.path().join("index.json");
+
+        let config = Config {
+            target_dir: project.clone(),
+            output_file: dir.path().join("output.md"),
+            num_threads: 1,
+            ..Default::default()
+        };
+        let scan = |config: &Config| {
+            let mut scanner = Scanner::new(config.clone(), Arc::new(ProgressBar::hidden()));
+            scanner.index = Some(ScanIndex::open(
+                index_path.clone(),
+                index::fingerprint(config),
+            ));
+            let root = scanner.scan().unwrap();
+            (root, scanner.get_statistics().unwrap())
+        };
+
+        let (_, stats) = scan(&config);
+        assert_eq!((stats.index_hits, stats.index_misses), (Some(0), Some(2)));
+
+        // Change the size so the edit is detected regardless of mtime resolution
+        std::fs::write(project.join("edited.txt"), "after edit\n").unwrap();
+        let (root, stats) = scan(&config);
+        assert_eq!((stats.index_hits, stats.index_misses), (Some(1), Some(1)));
+        assert_eq!(stats.total_lines, 2);
+
+        let content = |name: &str| {
+            root.contents.iter().find_map(|node| match node {
+                Node::File(f) if f.name == name => f.content.clone(),
+                _ => None,
+            })
+        };
+        assert_eq!(content("kept.txt").as_deref(), Some("kept\n"));
+        assert_eq!(content("edited.txt").as_deref(), Some("after edit\n"));
+
+        // The index holds statistics only, never the content of files
+        let saved = std::fs::read_to_string(&index_path).unwrap();
+        assert!(!saved.contains("after edit"));
+    }
+
+    #[test]
+    fn test_scan_index_keeps_binary_summaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        let asset = project.join("asset.bin");
+        std::fs::write(&asset, b"\x00\x01\x02 first").unwrap();
+        let index_path = dir.path().join("index.json");
+
+        let config = Config {
+            target_dir: project.clone(),
+            output_file: dir.path().join("output.md"),
+            num_threads: 1,
+            binary_summary: true,
+            ..Default::default()
+        };
+        let scan = || {
+            let mut scanner = Scanner::new(config.clone(), Arc::new(ProgressBar::hidden()));
+            scanner.index = Some(ScanIndex::open(
+                index_path.clone(),
+                index::fingerprint(&config),
+            ));
+            let root = scanner.scan().unwrap();
+            root.contents.into_iter().find_map(|node| match node {
+                Node::Binary(binary) => binary.summary,
+                _ => None,
+            })
+        };
+
+        let first = scan().unwrap();
+        assert!(first.sha256.is_some());
+
+        // Same size and modification time: the summary comes from the index, unhashed
+        let modified = std::fs::metadata(&asset).unwrap().modified().unwrap();
+        std::fs::write(&asset, b"\x00\x01\x02 other").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&asset)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(scan(), Some(first));
+    }
 }
@@ -0,0 +1,298 @@
+/*!
+ * HTTP server exposing scans as an API
+ *
+ * `POST /scan` takes a JSON object with a `path` (directory or Git URL) and
+ * any of the keys accepted in `.dumpfs.toml`, and answers with the rendered
+ * output and the scan report. Repositories are cloned into the usual cache,
+ * and tokenizers (with their token caches) are kept across requests.
+ */
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use indicatif::ProgressBar;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::budget;
+use crate::config::{Config, Settings};
+use crate::error::{DumpFsError, Result};
+use crate::git;
+use crate::report::ScanReport;
+use crate::scanner::Scanner;
+use crate::tokenizer::{create_tokenizer, Tokenizer};
+
+/// Response to a successful scan request
+#[derive(Debug, Serialize)]
+pub struct ScanResponse {
+    /// Output rendered in the requested format
+    pub output: String,
+    /// Statistics of the scan
+    pub report: ScanReport,
+}
+
+/// Error answered to a request, with its HTTP status
+#[derive(Debug)]
+struct ApiError {
+    status: u16,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self {
+            status: 400,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<DumpFsError> for ApiError {
+    fn from(error: DumpFsError) -> Self {
+        Self {
+            status: 500,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// State shared by all requests
+#[derive(Default)]
+struct ServerState {
+    /// Tokenizers by model id and project directory
+    tokenizers: Mutex<HashMap<(String, String), Arc<dyn Tokenizer>>>,
+}
+
+impl ServerState {
+    /// Tokenizer for the configured model, created on first use
+    fn tokenizer(&self, config: &Config) -> Result<Option<Arc<dyn Tokenizer>>> {
+        let Some(model) = config.model else {
+            return Ok(None);
+        };
+        let project_dir = config.target_dir.to_string_lossy().to_string();
+        let key = (model.model_id().to_string(), project_dir);
+
+        let mut tokenizers = self.tokenizers.lock().map_err(|_| {
+            DumpFsError::Unexpected("Failed to acquire lock on tokenizers".to_string())
+        })?;
+        if let Some(tokenizer) = tokenizers.get(&key) {
+            return Ok(Some(tokenizer.clone()));
+        }
+
+        let tokenizer: Arc<dyn Tokenizer> = Arc::from(create_tokenizer(model, &key.1)?);
+        tokenizers.insert(key, tokenizer.clone());
+        Ok(Some(tokenizer))
+    }
+}
+
+/// HTTP server for scan requests
+pub struct Server {
+    http: tiny_http::Server,
+    state: Arc<ServerState>,
+}
+
+impl Server {
+    /// Bind the server to `addr` (e.g. `127.0.0.1:7878`, or port 0 for any free port)
+    pub fn bind(addr: &str) -> Result<Self> {
+        let http = tiny_http::Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self {
+            http,
+            state: Arc::new(ServerState::default()),
+        })
+    }
+
+    /// Address the server is listening on
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Answer requests until the process exits, each on its own thread
+    pub fn run(&self) {
+        for request in self.http.incoming_requests() {
+            let state = self.state.clone();
+            thread::spawn(move || handle(&state, request));
+        }
+    }
+}
+
+/// Route a request and send the response
+fn handle(state: &ServerState, mut request: Request) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let result = match (request.method(), path.as_str()) {
+        (Method::Get, "/health") => Ok(json!({
+            "status": "ok",
+            "version": crate::VERSION,
+        })),
+        (Method::Post, "/scan") => {
+            let mut body = String::new();
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => scan(state, &body).and_then(|response| {
+                    serde_json::to_value(response).map_err(|e| DumpFsError::from(e).into())
+                }),
+                Err(e) => Err(ApiError::bad_request(e)),
+            }
+        }
+        _ => Err(ApiError {
+            status: 404,
+            message: format!("No route for {} {}", request.method(), path),
+        }),
+    };
+
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(e) => (e.status, json!({ "error": e.message })),
+    };
+    let header =
+        Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header);
+    if let Err(e) = request.respond(response) {
+        eprintln!("Error sending response: {}", e);
+    }
+}
+
+/// Run the scan described by a JSON request body
+fn scan(state: &ServerState, body: &str) -> std::result::Result<ScanResponse, ApiError> {
+    let mut fields: serde_json::Map<String, Value> =
+        serde_json::from_str(body).map_err(ApiError::bad_request)?;
+    let path = match fields.remove("path") {
+        Some(Value::String(path)) => path,
+        _ => return Err(ApiError::bad_request("`path` must be a string")),
+    };
+    let settings: Settings =
+        serde_json::from_value(Value::Object(fields)).map_err(ApiError::bad_request)?;
+    if settings.split.is_some() || settings.watch == Some(true) {
+        return Err(ApiError::bad_request(
+            "`split` and `watch` are not supported by the server",
+        ));
+    }
+
+    let mut config = Config::from_settings(PathBuf::from(&path), settings);
+    config.clip = false;
+    config.stdout = false;
+
+    let (target_dir, repo_url, git_repo) = git::process_path(
+        &path,
+        config.git_ref.as_deref(),
+        config.git_cache_policy,
+        None,
+    )
+    .map_err(DumpFsError::from)?;
+    config.target_dir = target_dir;
+    config.repo_url = repo_url;
+    config.git_repo = git_repo;
+
+    // Nothing is written, but the output path is shown in the report and excluded from the scan
+    config.output_file = config.target_dir.join(config.format.default_file_name());
+    config.validate().map_err(ApiError::bad_request)?;
+
+    if let Some(spec) = &config.diff {
+        let changes = git::changed_files(&config.target_dir, spec).map_err(DumpFsError::from)?;
+        config.changes = Some(Arc::new(changes));
+    }
+
+    let start = Instant::now();
+    let scanner = Scanner::with_tokenizer(
+        config.clone(),
+        Arc::new(ProgressBar::hidden()),
+        state.tokenizer(&config)?,
+    );
+    let mut root_node = scanner.scan()?;
+
+    let budget_summary = match &config.token_budget {
+        Some(budget) => Some(budget::pack(
+            &config,
+            &mut root_node,
+            budget.resolve(config.model)?,
+        )?),
+        None => None,
+    };
+
+    let mut output = Vec::new();
+    config
+        .format
+        .render(config.clone(), &root_node, &mut output)
+        .map_err(DumpFsError::from)?;
+
+    let report = ScanReport::new(
+        config.output_file.display().to_string(),
+        start.elapsed(),
+        scanner.get_statistics()?,
+        budget_summary,
+        Vec::new(),
+    )
+    .with_context_window(config.model.map(|model| model.context_window()));
+
+    Ok(ScanResponse {
+        output: String::from_utf8_lossy(&output).into_owned(),
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_scan_over_http() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.path().join("notes.log"), "skip me\n").unwrap();
+
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let client = reqwest::blocking::Client::new();
+        let url = format!("http://{}/scan", addr);
+
+        let response = client
+            .post(&url)
+            .json(&json!({
+                "path": dir.path(),
+                "ignore_patterns": ["*.log"],
+                "format": "jsonl",
+                "index": false,
+            }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().unwrap();
+        let output = body["output"].as_str().unwrap();
+        assert!(output.contains("fn main() {}"));
+        assert!(!output.contains("skip me"));
+        assert_eq!(body["report"]["files_processed"], 1);
+
+        // Invalid requests are rejected with a message
+        let response = client
+            .post(&url)
+            .json(&json!({ "path": dir.path(), "colour": "blue" }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let body: Value = response.json().unwrap();
+        assert!(body["error"].as_str().unwrap().contains("colour"));
+
+        let response = client
+            .get(format!("http://{}/missing", addr))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,610 @@
+/*!
+ * Splitting output into numbered parts
+ *
+ * Files are assigned to parts in tree order so that every part stays under a
+ * token or byte limit. A file is only cut into pieces when it is larger than
+ * the limit on its own, and pieces are cut finer until each one fits. Token
+ * counts of the markup around files are estimated, so a token limit is kept
+ * only as closely as that estimate.
+ */
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::budget::file_tokens;
+use crate::config::Config;
+use crate::truncate::covered_lines;
+use crate::types::{DirectoryNode, FileNode, LineRange, Node};
+use crate::writer::{PartInfo, Writer};
+
+/// Maximum size of a single output part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitLimit {
+    /// Maximum number of tokens per part
+    Tokens(usize),
+    /// Maximum number of bytes per part
+    Bytes(usize),
+}
+
+impl SplitLimit {
+    /// The limit as a plain number in its own unit
+    pub fn value(&self) -> usize {
+        match self {
+            SplitLimit::Tokens(n) | SplitLimit::Bytes(n) => *n,
+        }
+    }
+}
+
+impl FromStr for SplitLimit {
+    type Err = String;
+
+    /// Parse a limit such as `32000`, `32k` (tokens) or `500kb`, `2mb`, `4096b` (bytes)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_ascii_lowercase();
+        let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let number: usize = digits
+            .parse()
+            .map_err(|_| format!("invalid split limit `{}`", s))?;
+
+        let limit = match &lower[digits.len()..] {
+            "" | "t" | "tokens" => SplitLimit::Tokens(number),
+            "k" => SplitLimit::Tokens(number * 1_000),
+            "b" => SplitLimit::Bytes(number),
+            "kb" => SplitLimit::Bytes(number * 1024),
+            "mb" => SplitLimit::Bytes(number * 1024 * 1024),
+            unit => {
+                return Err(format!(
+                    "unknown unit `{}` in split limit `{}` (expected tokens, k, b, kb or mb)",
+                    unit, s
+                ))
+            }
+        };
+
+        if limit.value() == 0 {
+            return Err("split limit must be greater than zero".to_string());
+        }
+
+        Ok(limit)
+    }
+}
+
+impl fmt::Display for SplitLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitLimit::Tokens(n) => write!(f, "{} tokens", n),
+            SplitLimit::Bytes(n) => write!(f, "{} bytes", n),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SplitLimit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Tokens(usize),
+            Spec(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Tokens(tokens) => Ok(SplitLimit::Tokens(tokens)),
+            Raw::Spec(spec) => spec.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// A written output part
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputPart {
+    /// Path of the part file
+    pub path: PathBuf,
+    /// Size of the part in bytes
+    pub bytes: usize,
+    /// Estimated number of tokens in the part
+    pub tokens: usize,
+}
+
+/// Path of a numbered part, e.g. `.dumpfs.context.part-001.md`
+pub fn part_path(output_file: &Path, index: usize) -> PathBuf {
+    let stem = output_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match output_file.extension() {
+        Some(ext) => format!("{}.part-{:03}.{}", stem, index, ext.to_string_lossy()),
+        None => format!("{}.part-{:03}", stem, index),
+    };
+    output_file.with_file_name(name)
+}
+
+/// Whether `path` has the file name of a numbered part of `output_file`
+pub fn is_part_path(output_file: &Path, path: &Path) -> bool {
+    let (Some(name), Some(stem)) = (path.file_name(), output_file.file_stem()) else {
+        return false;
+    };
+    let name = name.to_string_lossy();
+    let Some(rest) = name.strip_prefix(&format!("{}.part-", stem.to_string_lossy())) else {
+        return false;
+    };
+
+    let (index, ext) = rest.split_once('.').unwrap_or((rest, ""));
+    let expected_ext = output_file.extension().unwrap_or_default();
+    index.len() >= 3 && index.chars().all(|c| c.is_ascii_digit()) && ext == expected_ext
+}
+
+/// A leaf node with the chain of directories leading to it
+struct Leaf<'a> {
+    ancestors: Vec<&'a DirectoryNode>,
+    node: Node,
+    /// Measured cost of the leaf in bytes and tokens
+    cost: (usize, usize),
+}
+
+/// Split the tree into parts under `limit` and write each one next to `config.output_file`
+pub fn write_parts(
+    config: &Config,
+    root_node: &DirectoryNode,
+    limit: SplitLimit,
+) -> io::Result<Vec<OutputPart>> {
+    let format = config.format.clone();
+    // Measure with the widest header any realistic part count could produce
+    let measure = format.writer(
+        config.clone(),
+        Some(PartInfo {
+            index: 999,
+            total: 999,
+        }),
+    );
+
+    let empty = empty_like(root_node);
+    let base_bytes = render_len(measure.as_ref(), &empty)?;
+    let base = (base_bytes, base_bytes / 4);
+    if pick(limit, base) >= limit.value() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Split limit of {} leaves no room for files: the header of each part alone takes {}",
+                limit,
+                match limit {
+                    SplitLimit::Tokens(_) => format!("{} tokens", base.1),
+                    SplitLimit::Bytes(_) => format!("{} bytes", base.0),
+                }
+            ),
+        ));
+    }
+    let capacity = limit.value() - pick(limit, base);
+
+    // Collect leaves in tree order, cutting oversized files into pieces
+    let mut leaves = Vec::new();
+    collect_leaves(root_node, &mut Vec::new(), &mut leaves);
+
+    let mut measured = Vec::with_capacity(leaves.len());
+    for (ancestors, node) in leaves {
+        let cost = leaf_cost(measure.as_ref(), &empty, &ancestors, &node, base_bytes)?;
+        match node {
+            Node::File(file) if pick(limit, cost) > capacity => {
+                // Aim for pieces a little under capacity to leave room for per-file
+                // overhead, and cut finer while a piece is still too large
+                let content_len = file.content.as_ref().map_or(0, |c| c.len());
+                let mut count = pick(limit, cost)
+                    .div_ceil((capacity * 9 / 10).max(1))
+                    .max(2);
+                let pieces = loop {
+                    let mut pieces = Vec::new();
+                    for piece in cut_file(&file, count) {
+                        let node = Node::File(piece);
+                        let cost =
+                            leaf_cost(measure.as_ref(), &empty, &ancestors, &node, base_bytes)?;
+                        pieces.push((node, cost));
+                    }
+                    let fits = pieces
+                        .iter()
+                        .all(|(_, cost)| pick(limit, *cost) <= capacity);
+                    if fits || count >= content_len {
+                        break pieces;
+                    }
+                    count = (count * 2).min(content_len);
+                };
+                for (node, cost) in pieces {
+                    measured.push(Leaf {
+                        ancestors: ancestors.clone(),
+                        node,
+                        cost,
+                    });
+                }
+            }
+            node => measured.push(Leaf {
+                ancestors,
+                node,
+                cost,
+            }),
+        }
+    }
+
+    // Greedily fill parts in order
+    let mut groups: Vec<Vec<Leaf>> = vec![Vec::new()];
+    let mut used = 0;
+    for leaf in measured {
+        let cost = pick(limit, leaf.cost);
+        let current = groups.last_mut().expect("groups is never empty");
+        if !current.is_empty() && used + cost > capacity {
+            groups.push(Vec::new());
+            used = 0;
+        }
+        used += cost;
+        groups.last_mut().expect("groups is never empty").push(leaf);
+    }
+
+    // Parts of an earlier, longer output would otherwise be left behind
+    remove_parts(&config.output_file)?;
+
+    let total = groups.len();
+    let mut parts = Vec::with_capacity(total);
+    for (i, group) in groups.into_iter().enumerate() {
+        let index = i + 1;
+        let tokens = base.1 + group.iter().map(|leaf| leaf.cost.1).sum::<usize>();
+
+        let mut part_root = empty_like(root_node);
+        for leaf in group {
+            insert_leaf(&mut part_root, &leaf.ancestors, leaf.node);
+        }
+
+        let path = part_path(&config.output_file, index);
+        let mut part_config = config.clone();
+        part_config.output_file = path.clone();
+        let writer = format.writer(part_config, Some(PartInfo { index, total }));
+
+        let mut out = BufWriter::new(File::create(&path)?);
+        let mut counter = CountingWriter {
+            inner: &mut out,
+            count: 0,
+        };
+        writer.render(&part_root, &mut counter)?;
+        let bytes = counter.count;
+        out.flush()?;
+
+        parts.push(OutputPart {
+            path,
+            bytes,
+            tokens,
+        });
+    }
+
+    Ok(parts)
+}
+
+/// Select the cost component matching the limit's unit
+fn pick(limit: SplitLimit, cost: (usize, usize)) -> usize {
+    match limit {
+        SplitLimit::Bytes(_) => cost.0,
+        SplitLimit::Tokens(_) => cost.1,
+    }
+}
+
+/// Measure the cost of a leaf as the growth it causes over an empty tree
+///
+/// Directory wrappers are counted for every leaf, so the sum over a part is an
+/// upper bound on the rendered size.
+pub(crate) fn leaf_cost(
+    writer: &dyn Writer,
+    empty: &DirectoryNode,
+    ancestors: &[&DirectoryNode],
+    node: &Node,
+    base_bytes: usize,
+) -> io::Result<(usize, usize)> {
+    let mut tree = empty.clone();
+    insert_leaf(&mut tree, ancestors, node.clone());
+    let bytes = render_len(writer, &tree)?.saturating_sub(base_bytes);
+
+    let tokens = match node {
+        Node::File(file) => {
+            let content_bytes = file.content.as_ref().map(|c| c.len()).unwrap_or(0);
+            file_tokens(file) + bytes.saturating_sub(content_bytes) / 4
+        }
+        _ => bytes / 4,
+    };
+
+    Ok((bytes, tokens))
+}
+
+/// Render a tree and return its size in bytes
+pub(crate) fn render_len(writer: &dyn Writer, root: &DirectoryNode) -> io::Result<usize> {
+    let mut counter = CountingWriter {
+        inner: io::sink(),
+        count: 0,
+    };
+    writer.render(root, &mut counter)?;
+    Ok(counter.count)
+}
+
+/// Remove the numbered parts of `output_file` written by an earlier run
+fn remove_parts(output_file: &Path) -> io::Result<()> {
+    let dir = match output_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if is_part_path(output_file, &path) && path.is_file() {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Cut a file into about `pieces` pieces, along line boundaries where possible
+fn cut_file(file: &FileNode, pieces: usize) -> Vec<FileNode> {
+    let content = file.content.as_deref().unwrap_or_default();
+    let total_tokens = file_tokens(file);
+
+    let target = content.len().div_ceil(pieces);
+
+    let mut chunks: Vec<&str> = Vec::new();
+    let mut start = 0;
+    while start < content.len() {
+        let mut end = (start + target).min(content.len());
+        while !content.is_char_boundary(end) {
+            end += 1;
+        }
+        if end < content.len() {
+            if let Some(newline) = content[start..end].rfind('\n') {
+                end = start + newline + 1;
+            }
+        }
+        chunks.push(&content[start..end]);
+        start = end;
+    }
+
+    if chunks.len() < 2 {
+        return vec![file.clone()];
+    }
+
+    // Pieces keep the line numbers they have in the file
+    let first_line = file.line_range.map_or(1, |range| range.start);
+    let mut next_line = first_line;
+
+    let count = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let lines = match file.truncated {
+                true => covered_lines(chunk).max(1),
+                false => chunk.split_inclusive('\n').count().max(1),
+            };
+            let line_range = LineRange {
+                start: next_line,
+                end: next_line + lines - 1,
+            };
+            next_line += lines;
+
+            let suffix = format!(" [piece {}/{}]", i + 1, count);
+            let mut path = file.path.clone().into_os_string();
+            path.push(&suffix);
+            FileNode {
+                name: format!("{}{}", file.name, suffix),
+                path: PathBuf::from(path),
+                metadata: file.metadata.clone(),
+                content: Some(chunk.to_string()),
+                tokens: Some(total_tokens * chunk.len() / content.len().max(1)),
+                // The diff follows the first piece
+                diff: if i == 0 { file.diff.clone() } else { None },
+                line_range: Some(line_range),
+                truncated: file.truncated,
+                // Pieces do not keep whole cells
+                cells: None,
+            }
+        })
+        .collect()
+}
+
+/// Copy of a directory node without its contents
+pub(crate) fn empty_like(dir: &DirectoryNode) -> DirectoryNode {
+    DirectoryNode {
+        name: dir.name.clone(),
+        path: dir.path.clone(),
+        metadata: dir.metadata.clone(),
+        contents: Vec::new(),
+    }
+}
+
+/// Collect leaf nodes in tree order along with their ancestor directories
+pub(crate) fn collect_leaves<'a>(
+    dir: &'a DirectoryNode,
+    ancestors: &mut Vec<&'a DirectoryNode>,
+    leaves: &mut Vec<(Vec<&'a DirectoryNode>, Node)>,
+) {
+    for node in &dir.contents {
+        match node {
+            Node::Directory(dir_node) if dir_node.contents.is_empty() => {
+                leaves.push((ancestors.clone(), node.clone()))
+            }
+            Node::Directory(dir_node) => {
+                ancestors.push(dir_node);
+                collect_leaves(dir_node, ancestors, leaves);
+                ancestors.pop();
+            }
+            leaf => leaves.push((ancestors.clone(), leaf.clone())),
+        }
+    }
+}
+
+/// Insert a leaf into a tree, recreating its ancestor directories as needed
+fn insert_leaf(root: &mut DirectoryNode, ancestors: &[&DirectoryNode], node: Node) {
+    let mut dir = root;
+    for ancestor in ancestors {
+        let position = dir
+            .contents
+            .iter()
+            .position(|n| matches!(n, Node::Directory(d) if d.path == ancestor.path));
+        let index = match position {
+            Some(index) => index,
+            None => {
+                dir.contents.push(Node::Directory(empty_like(ancestor)));
+                dir.contents.len() - 1
+            }
+        };
+        dir = match &mut dir.contents[index] {
+            Node::Directory(d) => d,
+            _ => unreachable!("position only matches directories"),
+        };
+    }
+    dir.contents.push(node);
+}
+
+/// Writer adapter that counts the bytes passing through it
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_split_limit() {
+        assert_eq!("32000".parse(), Ok(SplitLimit::Tokens(32_000)));
+        assert_eq!("32k".parse(), Ok(SplitLimit::Tokens(32_000)));
+        assert_eq!("500kb".parse(), Ok(SplitLimit::Bytes(500 * 1024)));
+        assert_eq!("2MB".parse(), Ok(SplitLimit::Bytes(2 * 1024 * 1024)));
+        assert!("0".parse::<SplitLimit>().is_err());
+        assert!("10 parsecs".parse::<SplitLimit>().is_err());
+    }
+
+    #[test]
+    fn test_part_path() {
+        assert_eq!(
+            part_path(Path::new("/repo/.dumpfs.context.md"), 1),
+            PathBuf::from("/repo/.dumpfs.context.part-001.md")
+        );
+        assert_eq!(
+            part_path(Path::new("out"), 12),
+            PathBuf::from("out.part-012")
+        );
+        assert!(is_part_path(
+            Path::new("/repo/.dumpfs.context.md"),
+            Path::new("/repo/.dumpfs.context.part-003.md")
+        ));
+        assert!(!is_part_path(
+            Path::new("/repo/.dumpfs.context.md"),
+            Path::new("/repo/.dumpfs.context.part-003.txt")
+        ));
+        assert!(!is_part_path(Path::new("out"), Path::new("out.part-ab")));
+    }
+
+    #[test]
+    fn test_cut_file_with_tiny_capacity() {
+        let file = FileNode {
+            name: "a.txt".to_string(),
+            path: PathBuf::from("a.txt"),
+            metadata: crate::types::Metadata {
+                size: 8,
+                modified: std::time::SystemTime::UNIX_EPOCH,
+                permissions: "644".to_string(),
+                encoding: None,
+            },
+            content: Some("one\ntwo\n".to_string()),
+            tokens: None,
+            diff: None,
+            line_range: None,
+            truncated: false,
+            cells: None,
+        };
+        let pieces = cut_file(&file, 8);
+        assert!(pieces.len() > 1);
+        let joined: String = pieces.iter().filter_map(|p| p.content.as_deref()).collect();
+        assert_eq!(joined, "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_limit_below_header_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = DirectoryNode {
+            name: "root".to_string(),
+            path: dir.path().to_path_buf(),
+            metadata: crate::types::Metadata {
+                size: 0,
+                modified: std::time::SystemTime::UNIX_EPOCH,
+                permissions: "755".to_string(),
+                encoding: None,
+            },
+            contents: Vec::new(),
+        };
+        let config = Config {
+            target_dir: dir.path().to_path_buf(),
+            output_file: dir.path().join("out.md"),
+            ..Default::default()
+        };
+        let err = write_parts(&config, &root, SplitLimit::Bytes(10)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("header"), "{}", err);
+    }
+
+    #[test]
+    fn test_pieces_are_cut_until_they_fit() {
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = crate::types::Metadata {
+            size: 0,
+            modified: std::time::SystemTime::UNIX_EPOCH,
+            permissions: "644".to_string(),
+            encoding: None,
+        };
+        // Quotes double in size when escaped for JSON, so even cuts of the content
+        // give a first piece that is much larger than the rest
+        let quotes = format!("{}\n", "\"".repeat(49)).repeat(60);
+        let plain = format!("{}\n", "b".repeat(49)).repeat(60);
+        let content = quotes + &plain;
+        let root = DirectoryNode {
+            name: "root".to_string(),
+            path: dir.path().to_path_buf(),
+            metadata: metadata.clone(),
+            contents: vec![Node::File(FileNode {
+                name: "long.txt".to_string(),
+                path: dir.path().join("long.txt"),
+                metadata,
+                content: Some(content),
+                tokens: None,
+                diff: None,
+                line_range: None,
+                truncated: false,
+                cells: None,
+            })],
+        };
+        let config = Config {
+            target_dir: dir.path().to_path_buf(),
+            output_file: dir.path().join("out.json"),
+            format: crate::FsWriterFormatter::Json,
+            ..Default::default()
+        };
+
+        let limit = SplitLimit::Bytes(4000);
+        let parts = write_parts(&config, &root, limit).unwrap();
+        assert!(parts.len() > 2);
+        for part in &parts {
+            assert!(part.bytes <= limit.value(), "{} bytes", part.bytes);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+/*!
+ * Streaming of scanned nodes
+ *
+ * The scanner passes nodes to a `NodeVisitor` as soon as they are processed,
+ * in the order they appear in the tree. Writers implement the trait to
+ * serialize nodes as they arrive, so that only a batch of file contents is
+ * held in memory at a time. `TreeBuilder` collects them into a
+ * `DirectoryNode` instead, which is how `Scanner::scan` builds its tree.
+ */
+
+use std::io;
+use std::time::SystemTime;
+
+use crate::types::{DirectoryNode, Node};
+
+/// Receiver of the nodes of a scan, in output order
+///
+/// Directories are entered before their contents and left after them. Files,
+/// binary files and symlinks arrive through `visit`, and so do directories
+/// that are complete already, such as expanded archives.
+pub trait NodeVisitor {
+    /// Enter a directory; `dir` has no contents yet
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()>;
+
+    /// Add a node to the current directory
+    fn visit(&mut self, node: Node) -> io::Result<()>;
+
+    /// Leave the current directory
+    fn leave_directory(&mut self) -> io::Result<()>;
+}
+
+/// Visitor that collects nodes into a directory tree
+#[derive(Debug, Default)]
+pub struct TreeBuilder {
+    /// Directories entered and not left yet, outermost first
+    open: Vec<DirectoryNode>,
+    /// The outermost directory, once it has been left
+    root: Option<DirectoryNode>,
+}
+
+impl TreeBuilder {
+    /// The collected tree, or None if no directory was completed
+    pub fn into_root(self) -> Option<DirectoryNode> {
+        self.root
+    }
+}
+
+impl NodeVisitor for TreeBuilder {
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()> {
+        self.open.push(dir);
+        Ok(())
+    }
+
+    fn visit(&mut self, node: Node) -> io::Result<()> {
+        match self.open.last_mut() {
+            Some(dir) => {
+                dir.contents.push(node);
+                Ok(())
+            }
+            None => Err(io::Error::other("node visited outside of a directory")),
+        }
+    }
+
+    fn leave_directory(&mut self) -> io::Result<()> {
+        let dir = self
+            .open
+            .pop()
+            .ok_or_else(|| io::Error::other("left a directory that was not entered"))?;
+        match self.open.last_mut() {
+            Some(parent) => parent.contents.push(Node::Directory(dir)),
+            None => self.root = Some(dir),
+        }
+        Ok(())
+    }
+}
+
+/// Pass a scanned tree to a visitor, as if it was being scanned
+pub fn replay(root: DirectoryNode, visitor: &mut dyn NodeVisitor) -> io::Result<()> {
+    let DirectoryNode {
+        name,
+        path,
+        metadata,
+        contents,
+    } = root;
+    visitor.enter_directory(DirectoryNode {
+        name,
+        path,
+        metadata,
+        contents: Vec::new(),
+    })?;
+    for node in contents {
+        match node {
+            Node::Directory(dir) => replay(dir, visitor)?,
+            node => visitor.visit(node)?,
+        }
+    }
+    visitor.leave_directory()
+}
+
+/// Visitor wrapper that enters directories only once something is found in them
+///
+/// Directories entered as prunable are dropped if they are left empty, e.g.
+/// because include patterns matched nothing below them.
+pub(crate) struct Pruning<'a> {
+    visitor: &'a mut dyn NodeVisitor,
+    /// Open directories, holding those not yet passed on to the visitor
+    open: Vec<Option<DirectoryNode>>,
+}
+
+impl<'a> Pruning<'a> {
+    pub fn new(visitor: &'a mut dyn NodeVisitor) -> Self {
+        Self {
+            visitor,
+            open: Vec::new(),
+        }
+    }
+
+    /// Enter a directory, holding it back until it has contents if it is prunable
+    pub fn enter_directory(&mut self, dir: DirectoryNode, prunable: bool) -> io::Result<()> {
+        if prunable {
+            self.open.push(Some(dir));
+            return Ok(());
+        }
+        self.flush()?;
+        self.visitor.enter_directory(dir)?;
+        self.open.push(None);
+        Ok(())
+    }
+
+    /// Add a node to the current directory, entering the directories held back
+    pub fn visit(&mut self, node: Node) -> io::Result<()> {
+        self.flush()?;
+        self.visitor.visit(node)
+    }
+
+    /// Leave the current directory, dropping it if it was held back
+    pub fn leave_directory(&mut self) -> io::Result<()> {
+        match self.open.pop() {
+            Some(Some(_)) => Ok(()),
+            _ => self.visitor.leave_directory(),
+        }
+    }
+
+    /// Pass on the directories held back, outermost first
+    fn flush(&mut self) -> io::Result<()> {
+        for dir in self.open.iter_mut() {
+            if let Some(dir) = dir.take() {
+                self.visitor.enter_directory(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Visitor wrapper that sets the modification time of every node it passes on
+///
+/// Used for reproducible output; with no time given, nodes pass unchanged.
+pub(crate) struct Pinning<'a> {
+    visitor: &'a mut dyn NodeVisitor,
+    modified: Option<SystemTime>,
+}
+
+impl<'a> Pinning<'a> {
+    pub fn new(visitor: &'a mut dyn NodeVisitor, modified: Option<SystemTime>) -> Self {
+        Self { visitor, modified }
+    }
+}
+
+impl NodeVisitor for Pinning<'_> {
+    fn enter_directory(&mut self, mut dir: DirectoryNode) -> io::Result<()> {
+        if let Some(modified) = self.modified {
+            dir.metadata.modified = modified;
+        }
+        self.visitor.enter_directory(dir)
+    }
+
+    fn visit(&mut self, mut node: Node) -> io::Result<()> {
+        if let Some(modified) = self.modified {
+            pin_modified(&mut node, modified);
+        }
+        self.visitor.visit(node)
+    }
+
+    fn leave_directory(&mut self) -> io::Result<()> {
+        self.visitor.leave_directory()
+    }
+}
+
+/// Set the modification time of a node and of everything below it
+fn pin_modified(node: &mut Node, modified: SystemTime) {
+    node.metadata_mut().modified = modified;
+    if let Node::Directory(dir) = node {
+        for child in &mut dir.contents {
+            pin_modified(child, modified);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::types::{FileNode, Metadata};
+
+    fn metadata() -> Metadata {
+        Metadata {
+            size: 0,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            permissions: "644".to_string(),
+            encoding: None,
+        }
+    }
+
+    fn dir(path: &str) -> DirectoryNode {
+        DirectoryNode {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: PathBuf::from(path),
+            metadata: metadata(),
+            contents: Vec::new(),
+        }
+    }
+
+    fn file(path: &str) -> Node {
+        Node::File(FileNode {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: PathBuf::from(path),
+            metadata: metadata(),
+            content: Some(path.to_string()),
+            tokens: None,
+            diff: None,
+            line_range: None,
+            truncated: false,
+            cells: None,
+        })
+    }
+
+    fn paths(dir: &DirectoryNode) -> Vec<String> {
+        let mut found = vec![dir.path.display().to_string()];
+        for node in &dir.contents {
+            match node {
+                Node::Directory(child) => found.extend(paths(child)),
+                Node::File(file) => found.push(file.path.display().to_string()),
+                _ => {}
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn test_replay_rebuilds_tree() {
+        let mut src = dir("root/src");
+        src.contents.push(file("root/src/main.rs"));
+        let mut root = dir("root");
+        root.contents.push(Node::Directory(src));
+        root.contents.push(file("root/README.md"));
+
+        let mut builder = TreeBuilder::default();
+        replay(root.clone(), &mut builder).unwrap();
+        let rebuilt = builder.into_root().unwrap();
+        assert_eq!(paths(&rebuilt), paths(&root));
+        assert_eq!(
+            paths(&rebuilt),
+            vec!["root", "root/src", "root/src/main.rs", "root/README.md"]
+        );
+    }
+
+    #[test]
+    fn test_pruning_drops_empty_directories() {
+        let mut builder = TreeBuilder::default();
+        let mut pruning = Pruning::new(&mut builder);
+        pruning.enter_directory(dir("root"), false).unwrap();
+        pruning.enter_directory(dir("root/empty"), true).unwrap();
+        pruning
+            .enter_directory(dir("root/empty/deeper"), true)
+            .unwrap();
+        pruning.leave_directory().unwrap();
+        pruning.leave_directory().unwrap();
+        pruning.enter_directory(dir("root/a"), true).unwrap();
+        pruning.enter_directory(dir("root/a/b"), true).unwrap();
+        pruning.visit(file("root/a/b/lib.rs")).unwrap();
+        pruning.leave_directory().unwrap();
+        pruning.leave_directory().unwrap();
+        pruning.enter_directory(dir("root/kept"), false).unwrap();
+        pruning.leave_directory().unwrap();
+        pruning.leave_directory().unwrap();
+
+        let root = builder.into_root().unwrap();
+        assert_eq!(
+            paths(&root),
+            vec!["root", "root/a", "root/a/b", "root/a/b/lib.rs", "root/kept"]
+        );
+    }
+
+    #[test]
+    fn test_pinning_sets_modification_times() {
+        let mut archive = dir("root/bundle.zip!");
+        archive.contents.push(file("root/bundle.zip!/a.txt"));
+
+        let mut builder = TreeBuilder::default();
+        let mut pinning = Pinning::new(&mut builder, Some(SystemTime::UNIX_EPOCH));
+        pinning.enter_directory(dir("root")).unwrap();
+        pinning.visit(file("root/b.txt")).unwrap();
+        pinning.visit(Node::Directory(archive)).unwrap();
+        pinning.leave_directory().unwrap();
+
+        fn times(dir: &DirectoryNode) -> Vec<SystemTime> {
+            let mut found = vec![dir.metadata.modified];
+            for node in &dir.contents {
+                match node {
+                    Node::Directory(child) => found.extend(times(child)),
+                    node => found.push(node.metadata().modified),
+                }
+            }
+            found
+        }
+        let root = builder.into_root().unwrap();
+        assert_eq!(times(&root), vec![SystemTime::UNIX_EPOCH; 4]);
+    }
+}
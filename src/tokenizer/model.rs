@@ -95,7 +95,9 @@ pub enum Model {
 impl Model {
     /// Get the context window size for this model
     pub fn context_window(&self) -> usize {
-        self.get_int("context_window").unwrap_or(0) as usize
+        self.get_str("context_window")
+            .and_then(|window| window.parse().ok())
+            .unwrap_or(0)
     }
 
     /// Get the provider of this model
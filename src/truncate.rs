@@ -0,0 +1,354 @@
+/*!
+ * Truncation of large text files
+ *
+ * Text files above the size limit are normally replaced with a short notice.
+ * With a truncation strategy, part of the file is kept instead: its first
+ * lines, its last lines, both ends, or as many leading lines as fit in a
+ * number of tokens. Omitted lines are replaced with an elision marker. Files
+ * are read line by line, so only the kept part is held in memory. Files that
+ * are not UTF-8 are decoded as a whole first.
+ */
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_8};
+use serde::{Deserialize, Deserializer};
+
+use crate::tokenizer::Tokenizer;
+
+/// Part of a large text file to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncation {
+    /// First N lines
+    Head(usize),
+    /// Last N lines
+    Tail(usize),
+    /// First N and last N lines
+    HeadTail(usize),
+    /// Leading lines that fit in N tokens
+    Tokens(usize),
+}
+
+impl FromStr for Truncation {
+    type Err = String;
+
+    /// Parse a strategy such as `head:200`, `tail:200`, `head-tail:100` or `tokens:4000`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (strategy, count) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("invalid truncation `{}` (expected <strategy>:<N>)", s))?;
+        let count: usize = count
+            .parse()
+            .map_err(|_| format!("invalid count in truncation `{}`", s))?;
+        if count == 0 {
+            return Err("truncation count must be greater than zero".to_string());
+        }
+
+        match strategy.to_ascii_lowercase().as_str() {
+            "head" => Ok(Truncation::Head(count)),
+            "tail" => Ok(Truncation::Tail(count)),
+            "head-tail" => Ok(Truncation::HeadTail(count)),
+            "tokens" => Ok(Truncation::Tokens(count)),
+            _ => Err(format!(
+                "unknown truncation strategy `{}` (expected head, tail, head-tail or tokens)",
+                strategy
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Truncation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Truncation::Head(n) => write!(f, "head:{}", n),
+            Truncation::Tail(n) => write!(f, "tail:{}", n),
+            Truncation::HeadTail(n) => write!(f, "head-tail:{}", n),
+            Truncation::Tokens(n) => write!(f, "tokens:{}", n),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Truncation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Truncation {
+    /// Read the kept part of a file, with a marker where lines were omitted
+    ///
+    /// Without a tokenizer, `Tokens` estimates four characters per token.
+    pub fn read(
+        &self,
+        path: &Path,
+        encoding: &'static Encoding,
+        tokenizer: Option<&dyn Tokenizer>,
+    ) -> io::Result<String> {
+        let file = File::open(path)?;
+        let reader: Box<dyn BufRead> = match encoding == UTF_8 {
+            true => Box::new(BufReader::new(file)),
+            false => {
+                let mut bytes = Vec::new();
+                BufReader::new(file).read_to_end(&mut bytes)?;
+                let (text, _) = encoding.decode_with_bom_removal(&bytes);
+                Box::new(Cursor::new(text.into_owned().into_bytes()))
+            }
+        };
+        let mut lines = Lines::new(reader);
+
+        match *self {
+            Truncation::Head(n) => {
+                let head = lines.take(n)?;
+                let omitted = lines.skip_rest()?;
+                Ok(join(&head, omitted, &[]))
+            }
+            Truncation::Tail(n) => {
+                let (omitted, tail) = lines.last(n)?;
+                Ok(join(&[], omitted, &tail))
+            }
+            Truncation::HeadTail(n) => {
+                let head = lines.take(n)?;
+                let (omitted, tail) = lines.last(n)?;
+                Ok(join(&head, omitted, &tail))
+            }
+            Truncation::Tokens(n) => {
+                // Read enough lines for the estimate, then keep the prefix that fits
+                let budget_chars = n.saturating_mul(4);
+                let mut head = Vec::new();
+                let mut chars = 0;
+                while chars <= budget_chars {
+                    match lines.next()? {
+                        Some(line) => {
+                            chars += line.chars().count();
+                            head.push(line);
+                        }
+                        None => break,
+                    }
+                }
+
+                let keep = match tokenizer {
+                    Some(tokenizer) => fitting_lines(&head, n, tokenizer),
+                    None => fitting_lines_estimated(&head, budget_chars),
+                };
+                let omitted = head.len() - keep + lines.skip_rest()?;
+                Ok(join(&head[..keep], omitted, &[]))
+            }
+        }
+    }
+}
+
+/// Number of leading lines whose tokens fit in `max_tokens`
+fn fitting_lines(lines: &[String], max_tokens: usize, tokenizer: &dyn Tokenizer) -> usize {
+    let fits = |count: usize| {
+        tokenizer
+            .count_tokens(&lines[..count].concat())
+            .map(|count| count.tokens <= max_tokens)
+            .unwrap_or(false)
+    };
+
+    // Binary search for the largest prefix that fits
+    let (mut low, mut high) = (0, lines.len());
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if fits(mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+/// Number of leading lines whose characters fit in `max_chars`
+fn fitting_lines_estimated(lines: &[String], max_chars: usize) -> usize {
+    let mut chars = 0;
+    lines
+        .iter()
+        .take_while(|line| {
+            chars += line.chars().count();
+            chars <= max_chars
+        })
+        .count()
+}
+
+/// Join kept lines around a marker for the omitted ones
+fn join(head: &[String], omitted: usize, tail: &[String]) -> String {
+    let mut text = head.concat();
+    if omitted > 0 {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("[... {} lines omitted ...]\n", omitted));
+    }
+    for line in tail {
+        text.push_str(line);
+    }
+    text
+}
+
+/// Number of lines a marker (`[... N lines omitted ...]`) stands for
+pub(crate) fn omitted_lines(line: &str) -> Option<usize> {
+    line.strip_prefix("[... ")?
+        .strip_suffix(" lines omitted ...]")?
+        .parse()
+        .ok()
+}
+
+/// Number of lines of the file that truncated text covers, including omitted ones
+pub(crate) fn covered_lines(text: &str) -> usize {
+    text.lines()
+        .map(|line| omitted_lines(line).unwrap_or(1))
+        .sum()
+}
+
+/// Lines of a file, keeping line endings and replacing invalid UTF-8
+struct Lines<R> {
+    reader: R,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Next line, including its newline
+    fn next(&mut self) -> io::Result<Option<String>> {
+        self.buffer.clear();
+        match self.reader.read_until(b'\n', &mut self.buffer)? {
+            0 => Ok(None),
+            _ => Ok(Some(String::from_utf8_lossy(&self.buffer).into_owned())),
+        }
+    }
+
+    /// Up to `n` next lines
+    fn take(&mut self, n: usize) -> io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        while lines.len() < n {
+            match self.next()? {
+                Some(line) => lines.push(line),
+                None => break,
+            }
+        }
+        Ok(lines)
+    }
+
+    /// The last `n` of the remaining lines, and how many lines came before them
+    fn last(&mut self, n: usize) -> io::Result<(usize, Vec<String>)> {
+        let mut tail = VecDeque::with_capacity(n + 1);
+        let mut skipped = 0;
+        while let Some(line) = self.next()? {
+            tail.push_back(line);
+            if tail.len() > n {
+                tail.pop_front();
+                skipped += 1;
+            }
+        }
+        Ok((skipped, tail.into()))
+    }
+
+    /// Count the remaining lines without keeping them
+    fn skip_rest(&mut self) -> io::Result<usize> {
+        let mut count = 0;
+        loop {
+            self.buffer.clear();
+            match self.reader.read_until(b'\n', &mut self.buffer)? {
+                0 => return Ok(count),
+                _ => count += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    fn numbered_file(lines: usize) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        for i in 1..=lines {
+            writeln!(file, "line {}", i).unwrap();
+        }
+        file
+    }
+
+    #[test]
+    fn test_parse_truncation() {
+        assert_eq!("head:200".parse(), Ok(Truncation::Head(200)));
+        assert_eq!("tail:5".parse(), Ok(Truncation::Tail(5)));
+        assert_eq!("head-tail:10".parse(), Ok(Truncation::HeadTail(10)));
+        assert_eq!("tokens:4000".parse(), Ok(Truncation::Tokens(4000)));
+        assert!("head".parse::<Truncation>().is_err());
+        assert!("middle:3".parse::<Truncation>().is_err());
+        assert!("tail:0".parse::<Truncation>().is_err());
+        assert_eq!(Truncation::HeadTail(10).to_string(), "head-tail:10");
+    }
+
+    #[test]
+    fn test_line_strategies() {
+        let file = numbered_file(10);
+
+        let head = Truncation::Head(2).read(file.path(), UTF_8, None).unwrap();
+        assert_eq!(head, "line 1\nline 2\n[... 8 lines omitted ...]\n");
+
+        let tail = Truncation::Tail(2).read(file.path(), UTF_8, None).unwrap();
+        assert_eq!(tail, "[... 8 lines omitted ...]\nline 9\nline 10\n");
+
+        let both = Truncation::HeadTail(3)
+            .read(file.path(), UTF_8, None)
+            .unwrap();
+        assert_eq!(
+            both,
+            "line 1\nline 2\nline 3\n[... 4 lines omitted ...]\nline 8\nline 9\nline 10\n"
+        );
+
+        // Nothing is omitted from files that already fit
+        let all = Truncation::HeadTail(5)
+            .read(file.path(), UTF_8, None)
+            .unwrap();
+        assert_eq!(all.lines().count(), 10);
+        assert!(!all.contains("omitted"));
+    }
+
+    #[test]
+    fn test_non_utf8_file() {
+        let mut file = NamedTempFile::new().unwrap();
+        let text: String = (1..=4).map(|i| format!("línea {}\n", i)).collect();
+        let bytes: Vec<u8> = text
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        file.write_all(&[0xFF, 0xFE]).unwrap();
+        file.write_all(&bytes).unwrap();
+
+        let head = Truncation::Head(1)
+            .read(file.path(), encoding_rs::UTF_16LE, None)
+            .unwrap();
+        assert_eq!(head, "línea 1\n[... 3 lines omitted ...]\n");
+    }
+
+    #[test]
+    fn test_token_strategy() {
+        // Each line is 7 characters, so 4 tokens (16 characters) fit two lines
+        let file = numbered_file(9);
+        let text = Truncation::Tokens(4)
+            .read(file.path(), UTF_8, None)
+            .unwrap();
+        assert_eq!(text, "line 1\nline 2\n[... 7 lines omitted ...]\n");
+    }
+}
@@ -2,11 +2,16 @@
  * Core types and data structures for the DumpFS application
  */
 
+use std::fmt;
 use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::SystemTime;
 
+use serde::{Deserialize, Serialize};
+
 /// Represents different types of filesystem entries
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileType {
     /// Text file with readable content
     TextFile,
@@ -21,7 +26,7 @@ pub enum FileType {
 }
 
 /// Metadata about a filesystem entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
     /// Size in bytes
     pub size: u64,
@@ -29,10 +34,13 @@ pub struct Metadata {
     pub modified: SystemTime,
     /// File permissions in octal format
     pub permissions: String,
+    /// Encoding text content was converted from (None for UTF-8)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
 }
 
 /// Represents a directory in the file system
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectoryNode {
     /// Directory name
     pub name: String,
@@ -45,7 +53,7 @@ pub struct DirectoryNode {
 }
 
 /// Represents a text file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileNode {
     /// File name
     pub name: String,
@@ -53,12 +61,61 @@ pub struct FileNode {
     pub path: PathBuf,
     /// File metadata
     pub metadata: Metadata,
-    /// File content (may be None if too large)
+    /// File content (None if omitted from the output)
     pub content: Option<String>,
+    /// Number of tokens in the content (if a tokenizer is enabled)
+    pub tokens: Option<usize>,
+    /// Unified diff of the file (in diff mode)
+    pub diff: Option<String>,
+    /// Lines of the file in `content` (None for the whole file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_range: Option<LineRange>,
+    /// Whether lines of `content` were omitted, leaving a marker in their place
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Cells of a flattened notebook and where they are in `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cells: Option<Vec<NotebookCell>>,
+}
+
+/// Kind of a notebook cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellKind {
+    /// Source code, followed by its outputs
+    Code,
+    /// Markdown text
+    Markdown,
+    /// Unrendered text
+    Raw,
+}
+
+impl CellKind {
+    /// Name used in notebooks and in the output
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellKind::Code => "code",
+            CellKind::Markdown => "markdown",
+            CellKind::Raw => "raw",
+        }
+    }
+}
+
+/// Location of a cell in flattened notebook content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotebookCell {
+    /// Index of the cell in the notebook
+    pub index: usize,
+    /// Kind of the cell
+    pub cell_type: CellKind,
+    /// First line of the cell, after its header
+    pub start_line: usize,
+    /// Last line of the cell
+    pub end_line: usize,
 }
 
 /// Represents a binary file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BinaryNode {
     /// File name
     pub name: String,
@@ -66,10 +123,33 @@ pub struct BinaryNode {
     pub path: PathBuf,
     /// File metadata
     pub metadata: Metadata,
+    /// What the content is (None if summaries are disabled)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<BinarySummary>,
+}
+
+/// Summary of the content of a binary file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinarySummary {
+    /// MIME type detected from magic bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// Width in pixels (images only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    /// Height in pixels (images only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// SHA-256 of the content, in hex (None for files above the hashing size limit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Hex dump or base64 of the content (small files only, if enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excerpt: Option<String>,
 }
 
 /// Represents a symbolic link
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SymlinkNode {
     /// Link name
     pub name: String,
@@ -82,7 +162,8 @@ pub struct SymlinkNode {
 }
 
 /// A generic filesystem node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Node {
     /// Directory node
     Directory(DirectoryNode),
@@ -93,3 +174,94 @@ pub enum Node {
     /// Symbolic link node
     Symlink(SymlinkNode),
 }
+
+impl Node {
+    /// Name of the file, directory or link
+    pub fn name(&self) -> &str {
+        match self {
+            Node::Directory(dir) => &dir.name,
+            Node::File(file) => &file.name,
+            Node::Binary(binary) => &binary.name,
+            Node::Symlink(symlink) => &symlink.name,
+        }
+    }
+
+    /// Metadata of the file, directory or link
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Node::Directory(dir) => &dir.metadata,
+            Node::File(file) => &file.metadata,
+            Node::Binary(binary) => &binary.metadata,
+            Node::Symlink(symlink) => &symlink.metadata,
+        }
+    }
+
+    /// Mutable metadata of the file, directory or link
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        match self {
+            Node::Directory(dir) => &mut dir.metadata,
+            Node::File(file) => &mut file.metadata,
+            Node::Binary(binary) => &mut binary.metadata,
+            Node::Symlink(symlink) => &mut symlink.metadata,
+        }
+    }
+}
+
+/// Range of 1-based line numbers, both ends included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LineRange {
+    /// First line
+    pub start: usize,
+    /// Last line
+    pub end: usize,
+}
+
+impl LineRange {
+    /// Lines of `text` in this range, and the range clamped to the lines that exist
+    pub fn slice(&self, text: &str) -> (String, LineRange) {
+        let selected: Vec<&str> = text
+            .split_inclusive('\n')
+            .skip(self.start - 1)
+            .take(self.end - self.start + 1)
+            .collect();
+        let end = (self.start + selected.len())
+            .saturating_sub(1)
+            .max(self.start);
+        (
+            selected.concat(),
+            LineRange {
+                start: self.start,
+                end,
+            },
+        )
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    /// Parse a range such as `150-320`, or a single line such as `42`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').unwrap_or((s, s));
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid line range `{}`", s))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+
+        if start == 0 || end < start {
+            return Err(format!(
+                "invalid line range `{}` (lines start at 1 and the end must not precede the start)",
+                s
+            ));
+        }
+        Ok(LineRange { start, end })
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
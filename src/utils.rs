@@ -5,6 +5,7 @@
 use std::io;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 use ignore::WalkBuilder;
 use indicatif::ProgressBar;
@@ -16,6 +17,16 @@ use crate::scanner::Scanner;
 
 /// Count total files for progress tracking
 pub fn count_files(dir: &Path, config: &Config) -> io::Result<u64> {
+    // In diff mode only changed files are processed
+    if let Some(changes) = &config.changes {
+        let dir = dir.canonicalize()?;
+        return Ok(changes
+            .changes
+            .keys()
+            .filter(|p| p.starts_with(&dir))
+            .count() as u64);
+    }
+
     let scanner = Scanner::new(config.clone(), Arc::new(ProgressBar::hidden()));
     let mut count = 0;
 
@@ -51,6 +62,92 @@ pub fn count_files(dir: &Path, config: &Config) -> io::Result<u64> {
     Ok(count)
 }
 
+/// Parse a file size such as `4096`, `500kb`, `2mb` or `1gb` into bytes
+pub fn parse_file_size(s: &str) -> Result<u64, String> {
+    let lower = s.trim().to_ascii_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let number: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid file size `{}`", s))?;
+
+    let multiplier = match &lower[digits.len()..] {
+        "" | "b" => 1,
+        "kb" | "k" => 1024,
+        "mb" | "m" => 1024 * 1024,
+        "gb" | "g" => 1024 * 1024 * 1024,
+        unit => {
+            return Err(format!(
+                "unknown unit `{}` in file size `{}` (expected b, kb, mb or gb)",
+                unit, s
+            ))
+        }
+    };
+
+    Ok(number * multiplier)
+}
+
+/// Time given by `SOURCE_DATE_EPOCH`, for pinning timestamps in reproducible output
+pub fn source_date_epoch() -> Option<SystemTime> {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Markdown code fence longer than any run of backticks in the text
+pub fn code_fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Guess the programming language of a file from its name
+pub fn language_for(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Dockerfile" => return Some("dockerfile"),
+        "Makefile" | "makefile" | "GNUmakefile" => return Some("make"),
+        _ => {}
+    }
+
+    let language = match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "scala" => "scala",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "lua" => "lua",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "json" => "json",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "nix" => "nix",
+        "zig" => "zig",
+        "txt" => "text",
+        _ => return None,
+    };
+    Some(language)
+}
+
 /// Format a human-readable file size
 pub fn format_file_size(size: u64) -> String {
     const KB: u64 = 1024;
@@ -0,0 +1,250 @@
+/*!
+ * Watch mode: regenerate the output when files change
+ *
+ * Filesystem events are filtered with the scanner's ignore rules and the
+ * project's `.gitignore` files, collected until no new event arrives for
+ * `DEBOUNCE`, and then only the directories containing the changed paths are
+ * scanned again.
+ */
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::event::{EventKind, MetadataKind, ModifyKind};
+use notify::{Event, RecursiveMode, Watcher};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::scanner::Scanner;
+use crate::split;
+use crate::types::DirectoryNode;
+
+/// Quiet period after the last event before the output is regenerated
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Decides which filesystem events should trigger a rescan
+struct EventFilter<'a> {
+    /// Canonical target directory
+    root: PathBuf,
+    /// Canonical output file path
+    output_file: PathBuf,
+    /// Rules from the root `.gitignore` and the custom gitignore file
+    gitignore: Option<Gitignore>,
+    scanner: &'a Scanner,
+}
+
+impl<'a> EventFilter<'a> {
+    fn new(config: &Config, scanner: &'a Scanner, root: PathBuf) -> Self {
+        let output_file = config
+            .output_file
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .and_then(|parent| fs::canonicalize(parent).ok())
+            .or_else(|| std::env::current_dir().ok())
+            .map(|parent| parent.join(config.output_file.file_name().unwrap_or_default()))
+            .unwrap_or_else(|| config.output_file.clone());
+
+        let gitignore = config.respect_gitignore.then(|| {
+            let mut builder = GitignoreBuilder::new(&root);
+            builder.add(root.join(".gitignore"));
+            if let Some(path) = &config.gitignore_path {
+                builder.add(path);
+            }
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        });
+
+        Self {
+            root,
+            output_file,
+            gitignore,
+            scanner,
+        }
+    }
+
+    /// Whether a change to `path` can affect the output
+    fn is_relevant(&self, path: &Path) -> bool {
+        let Ok(rel_path) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        // Our own output would otherwise trigger an endless loop of rewrites
+        if path == self.output_file || split::is_part_path(&self.output_file, path) {
+            return false;
+        }
+
+        // Ignored directories (e.g. `.git`, `target`) hide everything below them
+        let mut current = self.root.clone();
+        for component in rel_path.components() {
+            current.push(component);
+            if self.scanner.should_ignore(&current) {
+                return false;
+            }
+        }
+
+        if self.is_gitignored(path, rel_path) {
+            return false;
+        }
+
+        // Include patterns only apply to files; deleted paths are always relevant
+        !path.is_file() || self.scanner.should_include(path)
+    }
+
+    /// Whether the `.gitignore` files of the directories above `path` ignore it
+    ///
+    /// As in git, the file closest to `path` that matches it decides. Nested
+    /// files are read for every event, so edits to them apply right away.
+    fn is_gitignored(&self, path: &Path, rel_path: &Path) -> bool {
+        let Some(root_gitignore) = &self.gitignore else {
+            return false;
+        };
+
+        // Files in the directories between the root and `path`, innermost first
+        let nested: Vec<Gitignore> = rel_path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| self.root.join(dir))
+            .filter(|dir| dir.join(".gitignore").is_file())
+            .map(|dir| {
+                let mut builder = GitignoreBuilder::new(&dir);
+                builder.add(dir.join(".gitignore"));
+                builder.build().unwrap_or_else(|_| Gitignore::empty())
+            })
+            .collect();
+
+        let is_dir = path.is_dir();
+        for gitignore in nested.iter().chain([root_gitignore]) {
+            let matched = gitignore.matched_path_or_any_parents(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+/// Whether an event can change the content of the tree
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Access(_) => false,
+        // Reading files for the dump updates access times
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)) => false,
+        _ => true,
+    }
+}
+
+/// Watch the target directory and call `on_change` with the updated tree after each change
+///
+/// `root` is the tree from the initial scan. This function only returns on
+/// error, or when the watcher stops delivering events.
+pub fn watch<F>(
+    config: &Config,
+    scanner: &Scanner,
+    mut root: DirectoryNode,
+    mut on_change: F,
+) -> Result<()>
+where
+    F: FnMut(&DirectoryNode, &[PathBuf]) -> Result<()>,
+{
+    let target = fs::canonicalize(&config.target_dir)?;
+    // A single file target is watched through its directory
+    let watch_dir = match target.is_dir() {
+        true => target.clone(),
+        false => target
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or(target.clone()),
+    };
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&watch_dir, RecursiveMode::Recursive)?;
+
+    let filter = EventFilter::new(config, scanner, watch_dir);
+    let collect = |event: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>| match event {
+        Ok(event) if is_change(&event.kind) => changed.extend(
+            event
+                .paths
+                .into_iter()
+                .filter(|path| filter.is_relevant(path))
+                .filter(|path| target == filter.root || *path == target),
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!("Watch error: {}", e),
+    };
+
+    // Block until the first event, then wait for the burst to settle
+    while let Ok(event) = rx.recv() {
+        let mut changed = BTreeSet::new();
+        collect(event, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect(event, &mut changed);
+        }
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        let changed: Vec<PathBuf> = changed.into_iter().collect();
+        scanner.rescan(&mut root, &changed)?;
+        on_change(&root, &changed)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use indicatif::ProgressBar;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_event_filter() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target").join("debug")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n*.orig\n").unwrap();
+        fs::write(root.join("src").join("lib.rs"), "").unwrap();
+        fs::write(
+            root.join("src").join(".gitignore"),
+            "*.tmp\ngenerated/\n!keep.orig\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("src").join("generated")).unwrap();
+
+        let config = Config {
+            target_dir: root.clone(),
+            output_file: root.join(".dumpfs.context.md"),
+            respect_gitignore: true,
+            ..Default::default()
+        };
+        let scanner = Scanner::new(config.clone(), Arc::new(ProgressBar::hidden()));
+        let filter = EventFilter::new(&config, &scanner, root.clone());
+
+        assert!(filter.is_relevant(&root.join("src").join("lib.rs")));
+        assert!(filter.is_relevant(&root.join("src").join("deleted.rs")));
+        assert!(!filter.is_relevant(&root.join("target").join("debug").join("app")));
+        assert!(!filter.is_relevant(&root.join("build.log")));
+        // Nested `.gitignore` files apply below their directory and take precedence
+        assert!(!filter.is_relevant(&root.join("src").join("scratch.tmp")));
+        assert!(!filter.is_relevant(&root.join("src").join("generated").join("api.rs")));
+        assert!(filter.is_relevant(&root.join("scratch.tmp")));
+        assert!(!filter.is_relevant(&root.join("lib.orig")));
+        assert!(filter.is_relevant(&root.join("src").join("keep.orig")));
+        assert!(!filter.is_relevant(&root.join(".dumpfs.context.md")));
+        assert!(!filter.is_relevant(&root.join(".dumpfs.context.part-002.md")));
+        assert!(!filter.is_relevant(Path::new("/elsewhere/file.rs")));
+    }
+}
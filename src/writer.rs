@@ -1,18 +1,24 @@
 /*!
- * XML writer implementation for DumpFS
+ * Output writer implementations for DumpFS
  */
 
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 use clap::ValueEnum;
 use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use serde::Serialize;
 
 use crate::config::Config;
 use crate::git::GitHost;
-use crate::types::{BinaryNode, DirectoryNode, FileNode, Metadata, Node, SymlinkNode};
+use crate::stream::{NodeVisitor, TreeBuilder};
+use crate::truncate::{covered_lines, omitted_lines};
+use crate::types::{
+    BinaryNode, DirectoryNode, FileNode, LineRange, Metadata, Node, NotebookCell, SymlinkNode,
+};
+use crate::utils::{code_fence, language_for, source_date_epoch};
 
 /// Enum for writer formats
 #[derive(Default, Debug, Clone, ValueEnum)]
@@ -20,42 +26,171 @@ pub enum FsWriterFormatter {
     Xml,
     #[default]
     Txt,
+    /// The whole directory tree as a single JSON document
+    Json,
+    /// One JSON object per text file
+    Jsonl,
 }
 
 impl FsWriterFormatter {
+    /// Default output file name for this format
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            FsWriterFormatter::Xml => ".dumpfs.context.xml",
+            FsWriterFormatter::Txt => ".dumpfs.context.md",
+            FsWriterFormatter::Json => ".dumpfs.context.json",
+            FsWriterFormatter::Jsonl => ".dumpfs.context.jsonl",
+        }
+    }
+
     pub fn write(&self, config: Config, root_node: &DirectoryNode) -> io::Result<()> {
+        self.writer(config, None).write(root_node)
+    }
+
+    /// Render directory contents in this format into any output sink
+    pub fn render(
+        &self,
+        config: Config,
+        root_node: &DirectoryNode,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        self.writer(config, None).render(root_node, out)
+    }
+
+    /// Create a writer that renders nodes into `out` as the scanner passes them on
+    ///
+    /// The output is the same as from `render`, except that the XML overview
+    /// follows the directory contents. Token budgets and split outputs need
+    /// the whole tree first and are not applied.
+    pub fn stream<'a>(
+        &self,
+        config: Config,
+        out: &'a mut dyn Write,
+    ) -> io::Result<Box<dyn StreamWriter + 'a>> {
+        Ok(match self {
+            FsWriterFormatter::Xml => {
+                let xml = XmlWriter::new(config);
+                let mut writer = quick_xml::Writer::new_with_indent(out, b' ', 2);
+                xml.write_header(&mut writer)?;
+                Box::new(XmlStream {
+                    xml,
+                    writer,
+                    outline: TreeBuilder::default(),
+                })
+            }
+            FsWriterFormatter::Txt => {
+                let txt = TxtWriter::new(config);
+                txt.write_header(out)?;
+                Box::new(TxtStream { txt, out, depth: 0 })
+            }
+            FsWriterFormatter::Json => Box::new(JsonStream {
+                out,
+                open: Vec::new(),
+            }),
+            FsWriterFormatter::Jsonl => Box::new(JsonlStream {
+                jsonl: JsonlWriter::new(config),
+                out,
+                root: None,
+            }),
+        })
+    }
+
+    /// Create the writer for this format, optionally for one part of a split output
+    pub(crate) fn writer(&self, config: Config, part: Option<PartInfo>) -> Box<dyn Writer> {
         match self {
-            FsWriterFormatter::Xml => XmlWriter::new(config).write(root_node),
-            FsWriterFormatter::Txt => TxtWriter::new(config).write(root_node),
+            FsWriterFormatter::Xml => Box::new(XmlWriter::new(config).with_part(part)),
+            FsWriterFormatter::Txt => Box::new(TxtWriter::new(config).with_part(part)),
+            // Each part of a JSON output is a complete tree on its own
+            FsWriterFormatter::Json => Box::new(JsonWriter::new(config).with_part(part)),
+            FsWriterFormatter::Jsonl => Box::new(JsonlWriter::new(config).with_part(part)),
         }
     }
 }
 
+/// Position of an output file within a split output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PartInfo {
+    /// 1-based index of the part
+    pub index: usize,
+    /// Total number of parts
+    pub total: usize,
+}
+
 /// Trait for writing directory contents
-trait Writer {
-    fn write(&self, root_node: &DirectoryNode) -> io::Result<()>;
+pub(crate) trait Writer {
+    /// Path of the file `write` creates
+    fn output_file(&self) -> &Path;
+
+    /// Render directory contents into any output sink
+    fn render(&self, root_node: &DirectoryNode, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Write directory contents to the output file
+    fn write(&self, root_node: &DirectoryNode) -> io::Result<()> {
+        let file = File::create(self.output_file())?;
+        let mut writer = BufWriter::new(file);
+        self.render(root_node, &mut writer)?;
+        writer.flush()
+    }
 }
 
 /// XML writer for directory contents
-struct XmlWriter {
+pub(crate) struct XmlWriter {
     config: Config,
+    part: Option<PartInfo>,
 }
 
 impl XmlWriter {
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self { config, part: None }
+    }
+
+    /// Mark the output as one part of a split output
+    pub fn with_part(mut self, part: Option<PartInfo>) -> Self {
+        self.part = part;
+        self
+    }
+
+    /// Write the XML declaration, open the `directory_scan` element and write system info
+    fn write_header<W: Write>(&self, writer: &mut quick_xml::Writer<W>) -> io::Result<()> {
+        // Write XML declaration
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+        // Start directory_scan element with timestamp, which reproducible output
+        // only has when SOURCE_DATE_EPOCH pins it
+        let mut start_tag = BytesStart::new("directory_scan");
+        let timestamp = match self.config.reproducible {
+            true => source_date_epoch().map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+            false => Some(Local::now().to_rfc3339()),
+        };
+        if let Some(timestamp) = &timestamp {
+            start_tag.push_attribute(("timestamp", timestamp.as_str()));
+        }
+        writer.write_event(Event::Start(start_tag))?;
+
+        // Write part header for split outputs
+        if let Some(part) = &self.part {
+            let mut part_tag = BytesStart::new("part");
+            part_tag.push_attribute(("index", part.index.to_string().as_str()));
+            part_tag.push_attribute(("total", part.total.to_string().as_str()));
+            writer.write_event(Event::Empty(part_tag))?;
+        }
+
+        // Write system info
+        self.write_system_info(writer)
     }
 
     fn write_system_info<W: Write>(&self, writer: &mut quick_xml::Writer<W>) -> io::Result<()> {
         writer.write_event(Event::Start(BytesStart::new("system_info")))?;
 
-        // Write hostname
-        writer.write_event(Event::Start(BytesStart::new("hostname")))?;
-        let hostname = hostname::get()
-            .map(|h| h.to_string_lossy().to_string())
-            .unwrap_or_else(|_| "unknown".to_string());
-        writer.write_event(Event::Text(BytesText::new(&hostname)))?;
-        writer.write_event(Event::End(BytesEnd::new("hostname")))?;
+        // Write hostname, except in reproducible output
+        if !self.config.reproducible {
+            writer.write_event(Event::Start(BytesStart::new("hostname")))?;
+            let hostname = hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            writer.write_event(Event::Text(BytesText::new(&hostname)))?;
+            writer.write_event(Event::End(BytesEnd::new("hostname")))?;
+        }
 
         // Write OS
         writer.write_event(Event::Start(BytesStart::new("os")))?;
@@ -99,6 +234,20 @@ impl XmlWriter {
             writer.write_event(Event::Text(BytesText::new(&git_repo.name)))?;
             writer.write_event(Event::End(BytesEnd::new("name")))?;
 
+            // Write checked out ref if one was requested
+            if let Some(git_ref) = &git_repo.git_ref {
+                writer.write_event(Event::Start(BytesStart::new("ref")))?;
+                writer.write_event(Event::Text(BytesText::new(git_ref)))?;
+                writer.write_event(Event::End(BytesEnd::new("ref")))?;
+            }
+
+            // Write scanned subpath for tree and blob URLs
+            if let Some(subpath) = &git_repo.subpath {
+                writer.write_event(Event::Start(BytesStart::new("path")))?;
+                writer.write_event(Event::Text(BytesText::new(&subpath.to_string_lossy())))?;
+                writer.write_event(Event::End(BytesEnd::new("path")))?;
+            }
+
             writer.write_event(Event::End(BytesEnd::new("git_repository")))?;
         }
 
@@ -111,6 +260,19 @@ impl XmlWriter {
         &self,
         dir: &DirectoryNode,
         writer: &mut quick_xml::Writer<W>,
+    ) -> io::Result<()> {
+        self.write_directory_start(dir, writer)?;
+        for node in &dir.contents {
+            self.write_node(node, writer)?;
+        }
+        Self::write_directory_end(writer)
+    }
+
+    /// Open a directory element, up to the start of its contents
+    fn write_directory_start<W: Write>(
+        &self,
+        dir: &DirectoryNode,
+        writer: &mut quick_xml::Writer<W>,
     ) -> io::Result<()> {
         let mut start_tag = BytesStart::new("directory");
         start_tag.push_attribute(("name", dir.name.as_str()));
@@ -124,22 +286,33 @@ impl XmlWriter {
 
         // Write contents
         writer.write_event(Event::Start(BytesStart::new("contents")))?;
+        Ok(())
+    }
 
-        for node in &dir.contents {
-            match node {
-                Node::Directory(dir_node) => self.write_directory(dir_node, writer)?,
-                Node::File(file_node) => self.write_file(file_node, writer)?,
-                Node::Binary(bin_node) => self.write_binary(bin_node, writer)?,
-                Node::Symlink(sym_node) => self.write_symlink(sym_node, writer)?,
-            }
-        }
-
+    /// Close a directory element opened by `write_directory_start`
+    fn write_directory_end<W: Write>(writer: &mut quick_xml::Writer<W>) -> io::Result<()> {
         writer.write_event(Event::End(BytesEnd::new("contents")))?;
         writer.write_event(Event::End(BytesEnd::new("directory")))?;
-
         Ok(())
     }
 
+    fn write_node<W: Write>(
+        &self,
+        node: &Node,
+        writer: &mut quick_xml::Writer<W>,
+    ) -> io::Result<()> {
+        match node {
+            Node::Directory(dir_node) => self.write_directory(dir_node, writer),
+            // Files omitted by the token budget are only listed in the overview
+            Node::File(file_node) if file_node.content.is_none() && file_node.diff.is_none() => {
+                Ok(())
+            }
+            Node::File(file_node) => self.write_file(file_node, writer),
+            Node::Binary(bin_node) => self.write_binary(bin_node, writer),
+            Node::Symlink(sym_node) => self.write_symlink(sym_node, writer),
+        }
+    }
+
     fn write_file<W: Write>(
         &self,
         file: &FileNode,
@@ -148,6 +321,10 @@ impl XmlWriter {
         let mut start_tag = BytesStart::new("file");
         start_tag.push_attribute(("name", file.name.as_str()));
         start_tag.push_attribute(("path", file.path.to_string_lossy().as_ref()));
+        if let Some(range) = &file.line_range {
+            start_tag.push_attribute(("start_line", range.start.to_string().as_str()));
+            start_tag.push_attribute(("end_line", range.end.to_string().as_str()));
+        }
         writer.write_event(Event::Start(start_tag))?;
 
         // Write metadata only if enabled
@@ -155,19 +332,69 @@ impl XmlWriter {
             self.write_metadata(&file.metadata, writer)?;
         }
 
-        // Write content
-        writer.write_event(Event::Start(BytesStart::new("content")))?;
+        // Write content (absent for deleted files and diff-only output)
         if let Some(content) = &file.content {
-            // Use CDATA section to preserve formatting and avoid XML parsing issues
-            writer.write_event(Event::CData(BytesCData::new(content)))?;
+            writer.write_event(Event::Start(BytesStart::new("content")))?;
+            match &file.cells {
+                Some(cells) => self.write_cells(content, cells, writer)?,
+                None => {
+                    let content = match self.config.line_numbers {
+                        true => number_lines(content, file.line_range, file.truncated),
+                        false => content.clone(),
+                    };
+                    // Use CDATA section to preserve formatting and avoid XML parsing issues
+                    writer.write_event(Event::CData(BytesCData::new(&content)))?;
+                }
+            }
+            writer.write_event(Event::End(BytesEnd::new("content")))?;
+        }
+
+        // Write unified diff in diff mode
+        if let Some(diff) = &file.diff {
+            writer.write_event(Event::Start(BytesStart::new("diff")))?;
+            writer.write_event(Event::CData(BytesCData::new(diff)))?;
+            writer.write_event(Event::End(BytesEnd::new("diff")))?;
         }
-        writer.write_event(Event::End(BytesEnd::new("content")))?;
 
         writer.write_event(Event::End(BytesEnd::new("file")))?;
 
         Ok(())
     }
 
+    /// Write the cells of a flattened notebook with their index in the notebook
+    fn write_cells<W: Write>(
+        &self,
+        content: &str,
+        cells: &[NotebookCell],
+        writer: &mut quick_xml::Writer<W>,
+    ) -> io::Result<()> {
+        let lines: Vec<&str> = content.lines().collect();
+        for cell in cells {
+            let mut start_tag = BytesStart::new("cell");
+            start_tag.push_attribute(("index", cell.index.to_string().as_str()));
+            start_tag.push_attribute(("type", cell.cell_type.name()));
+            writer.write_event(Event::Start(start_tag))?;
+
+            let body = lines
+                .get(cell.start_line - 1..cell.end_line.min(lines.len()))
+                .unwrap_or_default()
+                .join("\n");
+            let body = match self.config.line_numbers && cell.end_line >= cell.start_line {
+                true => {
+                    let range = LineRange {
+                        start: cell.start_line,
+                        end: cell.end_line,
+                    };
+                    number_lines(&body, Some(range), false)
+                }
+                false => body,
+            };
+            writer.write_event(Event::CData(BytesCData::new(&body)))?;
+            writer.write_event(Event::End(BytesEnd::new("cell")))?;
+        }
+        Ok(())
+    }
+
     fn write_binary<W: Write>(
         &self,
         binary: &BinaryNode,
@@ -176,6 +403,18 @@ impl XmlWriter {
         let mut start_tag = BytesStart::new("binary");
         start_tag.push_attribute(("name", binary.name.as_str()));
         start_tag.push_attribute(("path", binary.path.to_string_lossy().as_ref()));
+        if let Some(summary) = &binary.summary {
+            if let Some(mime) = &summary.mime {
+                start_tag.push_attribute(("mime", mime.as_str()));
+            }
+            if let (Some(width), Some(height)) = (summary.width, summary.height) {
+                start_tag.push_attribute(("width", width.to_string().as_str()));
+                start_tag.push_attribute(("height", height.to_string().as_str()));
+            }
+            if let Some(sha256) = &summary.sha256 {
+                start_tag.push_attribute(("sha256", sha256.as_str()));
+            }
+        }
         writer.write_event(Event::Start(start_tag))?;
 
         // Write metadata only if enabled
@@ -183,6 +422,17 @@ impl XmlWriter {
             self.write_metadata(&binary.metadata, writer)?;
         }
 
+        // Write the excerpt of small files
+        if let Some(excerpt) = binary.summary.as_ref().and_then(|s| s.excerpt.as_ref()) {
+            let mut excerpt_tag = BytesStart::new("excerpt");
+            if let Some(format) = &self.config.binary_excerpt {
+                excerpt_tag.push_attribute(("format", format.name()));
+            }
+            writer.write_event(Event::Start(excerpt_tag))?;
+            writer.write_event(Event::CData(BytesCData::new(excerpt)))?;
+            writer.write_event(Event::End(BytesEnd::new("excerpt")))?;
+        }
+
         writer.write_event(Event::End(BytesEnd::new("binary")))?;
 
         Ok(())
@@ -246,6 +496,13 @@ impl XmlWriter {
                 Node::File(file_node) => {
                     let mut file_tag = BytesStart::new("file");
                     file_tag.push_attribute(("name", file_node.name.as_str()));
+                    // Mark files left out by the token budget so the model knows they exist
+                    if file_node.content.is_none() && file_node.diff.is_none() {
+                        file_tag.push_attribute(("omitted", "true"));
+                        if let Some(tokens) = file_node.tokens {
+                            file_tag.push_attribute(("tokens", tokens.to_string().as_str()));
+                        }
+                    }
                     writer.write_event(Event::Empty(file_tag))?;
                 }
                 Node::Binary(bin_node) => {
@@ -289,6 +546,13 @@ impl XmlWriter {
         writer.write_event(Event::Text(BytesText::new(&metadata.permissions)))?;
         writer.write_event(Event::End(BytesEnd::new("permissions")))?;
 
+        // Write the original encoding of converted text
+        if let Some(encoding) = &metadata.encoding {
+            writer.write_event(Event::Start(BytesStart::new("encoding")))?;
+            writer.write_event(Event::Text(BytesText::new(encoding)))?;
+            writer.write_event(Event::End(BytesEnd::new("encoding")))?;
+        }
+
         writer.write_event(Event::End(BytesEnd::new("metadata")))?;
 
         Ok(())
@@ -296,22 +560,13 @@ impl XmlWriter {
 }
 
 impl Writer for XmlWriter {
-    fn write(&self, root_node: &DirectoryNode) -> io::Result<()> {
-        let file = File::create(&self.config.output_file)?;
-        let writer = BufWriter::new(file);
-        let mut xml_writer = quick_xml::Writer::new_with_indent(writer, b' ', 2);
-
-        // Write XML declaration
-        xml_writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
-
-        // Start directory_scan element with timestamp
-        let mut start_tag = BytesStart::new("directory_scan");
-        let timestamp = Local::now().to_rfc3339();
-        start_tag.push_attribute(("timestamp", timestamp.as_str()));
-        xml_writer.write_event(Event::Start(start_tag))?;
+    fn output_file(&self) -> &Path {
+        &self.config.output_file
+    }
 
-        // Write system info
-        self.write_system_info(&mut xml_writer)?;
+    fn render(&self, root_node: &DirectoryNode, out: &mut dyn Write) -> io::Result<()> {
+        let mut xml_writer = quick_xml::Writer::new_with_indent(out, b' ', 2);
+        self.write_header(&mut xml_writer)?;
 
         // Write repository structure summary
         self.write_overview(root_node, &mut xml_writer)?;
@@ -327,9 +582,10 @@ impl Writer for XmlWriter {
 }
 
 /// Simple text writer for directory contents
-struct TxtWriter {
+pub(crate) struct TxtWriter {
     config: Config,
     root_node_path: PathBuf,
+    part: Option<PartInfo>,
 }
 
 impl TxtWriter {
@@ -337,21 +593,30 @@ impl TxtWriter {
         Self {
             config,
             root_node_path: Default::default(),
+            part: None,
         }
     }
 
-    fn write_system_info<W: Write>(&self, writer: &mut W) -> io::Result<()> {
-        let hostname = hostname::get()
-            .map(|h| h.to_string_lossy().to_string())
-            .unwrap_or_else(|_| "unknown".to_string());
+    /// Mark the output as one part of a split output
+    pub fn with_part(mut self, part: Option<PartInfo>) -> Self {
+        self.part = part;
+        self
+    }
 
-        writeln!(writer, "Hostname: {}", hostname)?;
+    fn write_system_info<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        // Reproducible output leaves out the hostname
+        if !self.config.reproducible {
+            let hostname = hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            writeln!(writer, "Hostname: {}", hostname)?;
+        }
         writeln!(writer, "OS: {}", std::env::consts::OS)?;
         writeln!(writer, "Kernel: {}", std::env::consts::FAMILY)?;
         Ok(())
     }
 
-    fn write_repo_info<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+    fn write_repo_info<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
         if let Some(git_repo) = &self.config.git_repo {
             writeln!(writer, "URL: {}", git_repo.url)?;
             let host_name = match &git_repo.host {
@@ -363,24 +628,58 @@ impl TxtWriter {
             writeln!(writer, "Host: {}", host_name)?;
             writeln!(writer, "Owner: {}", git_repo.owner)?;
             writeln!(writer, "Repository: {}", git_repo.name)?;
+            if let Some(git_ref) = &git_repo.git_ref {
+                writeln!(writer, "Ref: {}", git_ref)?;
+            }
+            if let Some(subpath) = &git_repo.subpath {
+                writeln!(writer, "Path: {}", subpath.display())?;
+            }
         }
         Ok(())
     }
 
-    fn write_directory<W: Write>(&self, dir: &DirectoryNode, writer: &mut W) -> io::Result<()> {
+    fn write_omitted_files<W: Write>(&self, dir: &DirectoryNode, writer: &mut W) -> io::Result<()> {
         for node in &dir.contents {
             match node {
-                Node::Directory(dir_node) => self.write_directory(dir_node, writer)?,
-                Node::File(file_node) => self.write_file(file_node, writer)?,
-                Node::Binary(bin_node) => self.write_binary(bin_node, writer)?,
-                Node::Symlink(sym_node) => self.write_symlink(sym_node, writer)?,
+                Node::Directory(dir_node) => self.write_omitted_files(dir_node, writer)?,
+                Node::File(file_node)
+                    if file_node.content.is_none() && file_node.diff.is_none() =>
+                {
+                    let filename = file_node
+                        .path
+                        .strip_prefix(&self.root_node_path)
+                        .unwrap_or(&file_node.path);
+                    match file_node.tokens {
+                        Some(tokens) => {
+                            writeln!(writer, "{} ({} tokens)", filename.display(), tokens)?
+                        }
+                        None => writeln!(writer, "{}", filename.display())?,
+                    }
+                }
+                _ => {}
             }
         }
         Ok(())
     }
 
+    fn write_directory<W: Write>(&self, dir: &DirectoryNode, writer: &mut W) -> io::Result<()> {
+        for node in &dir.contents {
+            self.write_node(node, writer)?;
+        }
+        Ok(())
+    }
+
+    fn write_node<W: Write>(&self, node: &Node, writer: &mut W) -> io::Result<()> {
+        match node {
+            Node::Directory(dir_node) => self.write_directory(dir_node, writer),
+            Node::File(file_node) => self.write_file(file_node, writer),
+            Node::Binary(bin_node) => self.write_binary(bin_node, writer),
+            Node::Symlink(sym_node) => self.write_symlink(sym_node, writer),
+        }
+    }
+
     fn write_file<W: Write>(&self, file: &FileNode, writer: &mut W) -> io::Result<()> {
-        if let Some(content) = &file.content {
+        if file.content.is_some() || file.diff.is_some() {
             let filename = file
                 .path
                 .strip_prefix(&self.root_node_path)
@@ -391,23 +690,72 @@ impl TxtWriter {
                 .unwrap_or_default();
 
             writeln!(writer, "\n================================================")?;
-            writeln!(writer, "{}", filename.display())?;
+            match &file.line_range {
+                Some(range) => writeln!(writer, "{} (lines {})", filename.display(), range)?,
+                None => writeln!(writer, "{}", filename.display())?,
+            }
             writeln!(writer, "================================================\n")?;
 
             if self.config.include_metadata {
                 self.write_metadata(&file.metadata, writer)?;
             }
-            writeln!(writer, "```{}", extension)?;
-            writeln!(writer, "{}", content)?;
-            writeln!(writer, "```")?;
+            if let Some(content) = &file.content {
+                let fence = code_fence(content);
+                writeln!(writer, "{}{}", fence, extension)?;
+                match self.config.line_numbers {
+                    true => writeln!(
+                        writer,
+                        "{}",
+                        number_lines(content, file.line_range, file.truncated)
+                    )?,
+                    false => writeln!(writer, "{}", content)?,
+                }
+                writeln!(writer, "{}", fence)?;
+            }
+            if let Some(diff) = &file.diff {
+                let fence = code_fence(diff);
+                writeln!(writer, "{}diff", fence)?;
+                write!(writer, "{}", diff)?;
+                writeln!(writer, "{}", fence)?;
+            }
         }
         Ok(())
     }
 
     fn write_binary<W: Write>(&self, binary: &BinaryNode, writer: &mut W) -> io::Result<()> {
+        let Some(summary) = &binary.summary else {
+            if self.config.include_metadata {
+                self.write_metadata(&binary.metadata, writer)?;
+            }
+            return Ok(());
+        };
+
+        let filename = binary
+            .path
+            .strip_prefix(&self.root_node_path)
+            .unwrap_or(&binary.path);
+        writeln!(writer, "\n================================================")?;
+        writeln!(writer, "{} (binary)", filename.display())?;
+        writeln!(writer, "================================================\n")?;
+
         if self.config.include_metadata {
             self.write_metadata(&binary.metadata, writer)?;
         }
+        if let Some(mime) = &summary.mime {
+            writeln!(writer, "  Type: {}", mime)?;
+        }
+        if let (Some(width), Some(height)) = (summary.width, summary.height) {
+            writeln!(writer, "  Dimensions: {}x{}", width, height)?;
+        }
+        if let Some(sha256) = &summary.sha256 {
+            writeln!(writer, "  SHA-256: {}", sha256)?;
+        }
+        if let Some(excerpt) = &summary.excerpt {
+            let format = self.config.binary_excerpt.map(|f| f.name());
+            writeln!(writer, "```{}", format.unwrap_or_default())?;
+            write!(writer, "{}", excerpt)?;
+            writeln!(writer, "```")?;
+        }
         Ok(())
     }
 
@@ -432,14 +780,54 @@ impl TxtWriter {
             chrono::DateTime::<chrono::Local>::from(metadata.modified).to_rfc3339()
         )?;
         writeln!(writer, "  Permissions: {}", metadata.permissions)?;
+        if let Some(encoding) = &metadata.encoding {
+            writeln!(writer, "  Encoding: {}", encoding)?;
+        }
         Ok(())
     }
 }
 
 impl Writer for TxtWriter {
-    fn write(&self, root_node: &DirectoryNode) -> io::Result<()> {
-        let file = File::create(&self.config.output_file)?;
-        let mut writer = BufWriter::new(file);
+    fn output_file(&self) -> &Path {
+        &self.config.output_file
+    }
+
+    fn render(&self, root_node: &DirectoryNode, mut writer: &mut dyn Write) -> io::Result<()> {
+        self.write_header(&mut writer)?;
+
+        // List files left out by the token budget
+        if self.config.token_budget.is_some() {
+            let mut omitted = Vec::new();
+            self.write_omitted_files(root_node, &mut omitted)?;
+            if !omitted.is_empty() {
+                writeln!(writer, "================ OMITTED FILES ================")?;
+                writeln!(writer, "Not included to stay within the token budget:")?;
+                writer.write_all(&omitted)?;
+                writeln!(writer)?;
+            }
+        }
+
+        // Write directory structure
+        writeln!(writer, "<codebase name=\"{}\">", root_node.name)?;
+        self.write_directory(root_node, &mut writer)?;
+        writeln!(writer, "</codebase>")?;
+
+        Ok(())
+    }
+}
+
+impl TxtWriter {
+    /// Write the part, system info and repository sections that precede the files
+    fn write_header<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        // Write part header for split outputs
+        if let Some(part) = &self.part {
+            writeln!(
+                writer,
+                "=================== PART {} OF {} ===================",
+                part.index, part.total
+            )?;
+            writeln!(writer)?;
+        }
 
         if self.config.include_metadata {
             // Write system info section
@@ -447,21 +835,405 @@ impl Writer for TxtWriter {
                 writer,
                 "=================== SYSTEM INFO ==================="
             )?;
-            self.write_system_info(&mut writer)?;
+            self.write_system_info(writer)?;
             writeln!(writer)?;
         }
         // Write repository info if available
         if self.config.git_repo.is_some() {
             writeln!(writer, "=================== REPOSITORY ===================")?;
-            self.write_repo_info(&mut writer)?;
+            self.write_repo_info(writer)?;
             writeln!(writer)?;
         }
+        Ok(())
+    }
+}
 
-        // Write directory structure
-        writeln!(writer, "<codebase name=\"{}\">", root_node.name)?;
-        self.write_directory(root_node, &mut writer)?;
-        writeln!(writer, "</codebase>")?;
+/// JSON writer serializing the whole directory tree
+pub(crate) struct JsonWriter {
+    config: Config,
+    part: Option<PartInfo>,
+}
+
+impl JsonWriter {
+    pub fn new(config: Config) -> Self {
+        Self { config, part: None }
+    }
+
+    /// Mark the output as one part of a split output
+    pub fn with_part(mut self, part: Option<PartInfo>) -> Self {
+        self.part = part;
+        self
+    }
+}
+
+/// JSON document: the scanned tree, preceded by the part header in split outputs
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part: Option<PartInfo>,
+    #[serde(flatten)]
+    root: &'a DirectoryNode,
+}
+
+impl Writer for JsonWriter {
+    fn output_file(&self) -> &Path {
+        &self.config.output_file
+    }
+
+    fn render(&self, root_node: &DirectoryNode, mut writer: &mut dyn Write) -> io::Result<()> {
+        let document = JsonDocument {
+            part: self.part,
+            root: root_node,
+        };
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+        writeln!(writer)
+    }
+}
+
+/// A single line of JSON Lines output
+#[derive(Debug, Serialize)]
+struct JsonlRecord<'a> {
+    /// Path relative to the scan root
+    path: &'a Path,
+    /// Language guessed from the file extension
+    language: Option<&'static str>,
+    metadata: &'a Metadata,
+    /// File content (None if omitted from the output)
+    content: Option<&'a str>,
+    tokens: Option<usize>,
+    /// Unified diff (in diff mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<&'a str>,
+    /// First line in `content` (if only some lines were selected)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_line: Option<usize>,
+    /// Last line in `content` (if only some lines were selected)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_line: Option<usize>,
+}
+
+/// JSON Lines writer emitting one object per text file
+pub(crate) struct JsonlWriter {
+    config: Config,
+    part: Option<PartInfo>,
+}
+
+impl JsonlWriter {
+    pub fn new(config: Config) -> Self {
+        Self { config, part: None }
+    }
+
+    /// Mark the output as one part of a split output
+    pub fn with_part(mut self, part: Option<PartInfo>) -> Self {
+        self.part = part;
+        self
+    }
+
+    fn write_directory(
+        &self,
+        root: &Path,
+        dir: &DirectoryNode,
+        writer: &mut dyn Write,
+    ) -> io::Result<()> {
+        for node in &dir.contents {
+            match node {
+                Node::Directory(dir_node) => self.write_directory(root, dir_node, writer)?,
+                Node::File(file_node) => self.write_file(root, file_node, writer)?,
+                Node::Binary(_) | Node::Symlink(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, root: &Path, file: &FileNode, writer: &mut dyn Write) -> io::Result<()> {
+        let record = JsonlRecord {
+            path: file.path.strip_prefix(root).unwrap_or(&file.path),
+            language: language_for(&file.path),
+            metadata: &file.metadata,
+            content: file.content.as_deref(),
+            tokens: file.tokens,
+            diff: file.diff.as_deref(),
+            start_line: file.line_range.map(|range| range.start),
+            end_line: file.line_range.map(|range| range.end),
+        };
+        serde_json::to_writer(&mut *writer, &record)?;
+        writeln!(writer)
+    }
+}
+
+impl Writer for JsonlWriter {
+    fn output_file(&self) -> &Path {
+        &self.config.output_file
+    }
+
+    fn render(&self, root_node: &DirectoryNode, writer: &mut dyn Write) -> io::Result<()> {
+        // Split outputs start each part with a `{"part": ...}` record
+        if let Some(part) = self.part {
+            serde_json::to_writer(&mut *writer, &serde_json::json!({ "part": part }))?;
+            writeln!(writer)?;
+        }
+        self.write_directory(&root_node.path, root_node, writer)
+    }
+}
+
+/// Writer that serializes nodes as the scanner passes them on
+pub trait StreamWriter: NodeVisitor {
+    /// Write what follows the scanned tree and flush the output
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// Streaming XML writer
+///
+/// The overview needs every name in the tree, so it follows the contents.
+struct XmlStream<'a> {
+    xml: XmlWriter,
+    writer: quick_xml::Writer<&'a mut dyn Write>,
+    /// Nodes written so far, without their contents, for the overview
+    outline: TreeBuilder,
+}
+
+impl NodeVisitor for XmlStream<'_> {
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()> {
+        self.xml.write_directory_start(&dir, &mut self.writer)?;
+        self.outline.enter_directory(dir)
+    }
+
+    fn visit(&mut self, node: Node) -> io::Result<()> {
+        self.xml.write_node(&node, &mut self.writer)?;
+        self.outline.visit(outline(&node))
+    }
 
+    fn leave_directory(&mut self) -> io::Result<()> {
+        XmlWriter::write_directory_end(&mut self.writer)?;
+        self.outline.leave_directory()
+    }
+}
+
+impl StreamWriter for XmlStream<'_> {
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(root) = std::mem::take(&mut self.outline).into_root() {
+            self.xml.write_overview(&root, &mut self.writer)?;
+        }
+        self.writer
+            .write_event(Event::End(BytesEnd::new("directory_scan")))?;
+        self.writer.get_mut().flush()
+    }
+}
+
+/// Copy of a node with only what the overview shows
+fn outline(node: &Node) -> Node {
+    let placeholder = |text: &Option<String>| text.as_ref().map(|_| String::new());
+    match node {
+        Node::Directory(dir) => Node::Directory(DirectoryNode {
+            name: dir.name.clone(),
+            path: dir.path.clone(),
+            metadata: dir.metadata.clone(),
+            contents: dir.contents.iter().map(outline).collect(),
+        }),
+        Node::File(file) => Node::File(FileNode {
+            name: file.name.clone(),
+            path: file.path.clone(),
+            metadata: file.metadata.clone(),
+            content: placeholder(&file.content),
+            tokens: file.tokens,
+            diff: placeholder(&file.diff),
+            line_range: file.line_range,
+            truncated: file.truncated,
+            cells: None,
+        }),
+        Node::Binary(binary) => Node::Binary(BinaryNode {
+            name: binary.name.clone(),
+            path: binary.path.clone(),
+            metadata: binary.metadata.clone(),
+            summary: None,
+        }),
+        Node::Symlink(symlink) => Node::Symlink(symlink.clone()),
+    }
+}
+
+/// Streaming text writer
+struct TxtStream<'a> {
+    txt: TxtWriter,
+    out: &'a mut dyn Write,
+    /// Number of directories entered and not left
+    depth: usize,
+}
+
+impl NodeVisitor for TxtStream<'_> {
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()> {
+        if self.depth == 0 {
+            writeln!(self.out, "<codebase name=\"{}\">", dir.name)?;
+        }
+        self.depth += 1;
         Ok(())
     }
+
+    fn visit(&mut self, node: Node) -> io::Result<()> {
+        self.txt.write_node(&node, &mut self.out)
+    }
+
+    fn leave_directory(&mut self) -> io::Result<()> {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 {
+            writeln!(self.out, "</codebase>")?;
+        }
+        Ok(())
+    }
+}
+
+impl StreamWriter for TxtStream<'_> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Streaming JSON writer, producing the same document as `JsonWriter`
+struct JsonStream<'a> {
+    out: &'a mut dyn Write,
+    /// Open directories, and whether anything was written to their contents
+    open: Vec<bool>,
+}
+
+impl JsonStream<'_> {
+    /// Begin an element of the contents of the current directory
+    fn start_element(&mut self) -> io::Result<()> {
+        let depth = self.open.len();
+        if let Some(has_contents) = self.open.last_mut() {
+            if *has_contents {
+                write!(self.out, ",")?;
+            }
+            *has_contents = true;
+            write!(self.out, "\n{}", json_indent(2 * depth))?;
+        }
+        Ok(())
+    }
+
+    /// Write a pretty-printed value whose first line is indented already
+    fn write_value<T: Serialize + ?Sized>(&mut self, value: &T, level: usize) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(value)?;
+        let indent = format!("\n{}", json_indent(level));
+        write!(self.out, "{}", json.replace('\n', &indent))
+    }
+}
+
+impl NodeVisitor for JsonStream<'_> {
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()> {
+        let depth = self.open.len();
+        self.start_element()?;
+
+        // Nested directories are tagged like the other nodes
+        let level = 2 * depth + 1;
+        let field = json_indent(level);
+        writeln!(self.out, "{{")?;
+        if depth > 0 {
+            writeln!(self.out, "{}\"type\": \"directory\",", field)?;
+        }
+        write!(self.out, "{}\"name\": ", field)?;
+        self.write_value(&dir.name, level)?;
+        write!(self.out, ",\n{}\"path\": ", field)?;
+        self.write_value(&dir.path, level)?;
+        write!(self.out, ",\n{}\"metadata\": ", field)?;
+        self.write_value(&dir.metadata, level)?;
+        write!(self.out, ",\n{}\"contents\": [", field)?;
+        self.open.push(false);
+        Ok(())
+    }
+
+    fn visit(&mut self, node: Node) -> io::Result<()> {
+        self.start_element()?;
+        self.write_value(&node, 2 * self.open.len())
+    }
+
+    fn leave_directory(&mut self) -> io::Result<()> {
+        let has_contents = self
+            .open
+            .pop()
+            .ok_or_else(|| io::Error::other("left a directory that was not entered"))?;
+        let depth = self.open.len();
+        if has_contents {
+            write!(self.out, "\n{}", json_indent(2 * depth + 1))?;
+        }
+        write!(self.out, "]\n{}}}", json_indent(2 * depth))?;
+        if depth == 0 {
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+}
+
+impl StreamWriter for JsonStream<'_> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Indentation of pretty-printed JSON at a nesting level
+fn json_indent(level: usize) -> String {
+    "  ".repeat(level)
+}
+
+/// Streaming JSON Lines writer
+struct JsonlStream<'a> {
+    jsonl: JsonlWriter,
+    out: &'a mut dyn Write,
+    /// Path of the scan root, that record paths are relative to
+    root: Option<PathBuf>,
+}
+
+impl NodeVisitor for JsonlStream<'_> {
+    fn enter_directory(&mut self, dir: DirectoryNode) -> io::Result<()> {
+        self.root.get_or_insert(dir.path);
+        Ok(())
+    }
+
+    fn visit(&mut self, node: Node) -> io::Result<()> {
+        let root = self.root.as_deref().unwrap_or(Path::new(""));
+        match &node {
+            Node::Directory(dir) => self.jsonl.write_directory(root, dir, self.out),
+            Node::File(file) => self.jsonl.write_file(root, file, self.out),
+            Node::Binary(_) | Node::Symlink(_) => Ok(()),
+        }
+    }
+
+    fn leave_directory(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl StreamWriter for JsonlStream<'_> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Prefix each line of file content with its line number
+///
+/// Numbering starts at the first line of `line_range`. In truncated content,
+/// markers are not numbered, and the lines they stand for are skipped.
+fn number_lines(content: &str, line_range: Option<LineRange>, truncated: bool) -> String {
+    let first_line = line_range.map_or(1, |range| range.start);
+    let last_line = line_range.map_or_else(
+        || match truncated {
+            true => first_line + covered_lines(content) - 1,
+            false => first_line + content.lines().count() - 1,
+        },
+        |range| range.end,
+    );
+    let width = last_line.to_string().len();
+
+    let mut numbered = String::with_capacity(content.len() + content.lines().count() * (width + 3));
+    let mut number = first_line;
+    for line in content.lines() {
+        if let Some(omitted) = omitted_lines(line).filter(|_| truncated) {
+            numbered.push_str(&format!("{:>width$} | {}\n", "", line));
+            number += omitted;
+            continue;
+        }
+        numbered.push_str(&format!("{:>width$} | {}\n", number, line));
+        number += 1;
+    }
+    if !content.ends_with('\n') {
+        numbered.pop();
+    }
+    numbered
 }